@@ -1,11 +1,18 @@
+pub mod change_detection;
 pub mod commands;
 pub mod component;
+pub mod dump;
 pub mod ecs_dependency_graph;
 pub mod entity;
+pub mod error;
+pub mod event;
 pub mod prelude;
 pub mod query;
+pub mod reflect;
 pub mod resource;
+pub mod schedule;
 pub mod scheduler;
+pub mod snapshot;
 pub mod storages;
 pub mod system;
 pub mod world;
@@ -0,0 +1,225 @@
+// change_detection.rs
+
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+/// Value of the world change tick at a certain point in time.
+/// The world change tick gets incremented every time a system runs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tick(u32);
+
+impl Tick {
+    pub const fn new(tick: u32) -> Self {
+        Self(tick)
+    }
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Returns true, if this tick happened after the last run of a system.
+    /// Both ticks are compared by their distance to the current run of the system,
+    /// so that the comparison stays correct after the world change tick wrapped around.
+    pub fn is_newer_than(self, last_run: Tick, this_run: Tick) -> bool {
+        this_run.0.wrapping_sub(self.0) < this_run.0.wrapping_sub(last_run.0)
+    }
+}
+
+/// Ticks of when a component was added to its entity and last mutably accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub added: Tick,
+    pub changed: Tick,
+}
+
+impl ComponentTicks {
+    pub(crate) fn new(tick: Tick) -> Self {
+        Self {
+            added: tick,
+            changed: tick,
+        }
+    }
+
+    pub fn is_added(&self, last_run: Tick, this_run: Tick) -> bool {
+        self.added.is_newer_than(last_run, this_run)
+    }
+
+    pub fn is_changed(&self, last_run: Tick, this_run: Tick) -> bool {
+        self.changed.is_newer_than(last_run, this_run)
+    }
+}
+
+/// Ticks of the previous and the current run of a system.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SystemTicks {
+    pub last_run: Tick,
+    pub this_run: Tick,
+}
+
+/// Mutable reference to a component,
+/// which marks the component as changed once it gets mutably dereferenced.
+pub struct Mut<'a, T> {
+    value: &'a mut T,
+    ticks: &'a mut ComponentTicks,
+    this_run: Tick,
+}
+
+impl<'a, T> Mut<'a, T> {
+    pub(crate) fn new(value: &'a mut T, ticks: &'a mut ComponentTicks, this_run: Tick) -> Self {
+        Self {
+            value,
+            ticks,
+            this_run,
+        }
+    }
+
+    /// Marks the component as changed and returns the inner mutable reference.
+    pub fn into_inner(self) -> &'a mut T {
+        self.ticks.changed = self.this_run;
+        self.value
+    }
+
+    /// Returns the inner mutable reference without marking the component as changed.
+    pub fn bypass_change_detection(&mut self) -> &mut T {
+        self.value
+    }
+
+    pub fn ticks(&self) -> ComponentTicks {
+        *self.ticks
+    }
+}
+
+impl<'a, T> Deref for Mut<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<'a, T> DerefMut for Mut<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.ticks.changed = self.this_run;
+        self.value
+    }
+}
+
+impl<'a, T: std::fmt::Debug> std::fmt::Debug for Mut<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// Change ticks of a table column or of an AoS table field,
+/// indexed by the row of the entity inside of its table.
+pub struct ChangeTicksIterUnsafe<'c> {
+    ptr: NonNull<ComponentTicks>,
+    this_run: Tick,
+    marker: PhantomData<&'c mut ComponentTicks>,
+}
+
+impl<'c> ChangeTicksIterUnsafe<'c> {
+    pub(crate) fn new(ticks: &'c mut Vec<ComponentTicks>, this_run: Tick) -> Self {
+        Self {
+            ptr: NonNull::new(ticks.as_mut_ptr()).expect("Vec pointer should never be null."),
+            this_run,
+            marker: PhantomData,
+        }
+    }
+
+    /// #SAFETY:
+    /// The index needs to be inside of the bounds of the ticks vec
+    /// and the row should only be accessed once at the same time.
+    pub(crate) unsafe fn wrap<T>(&mut self, index: usize, value: &'c mut T) -> Mut<'c, T> {
+        let ticks = unsafe { self.ptr.add(index).as_mut() };
+        Mut::new(value, ticks, self.this_run)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ecs::{
+        prelude::*,
+        query::query_filter::{Added, Changed},
+        system::ResMut,
+    };
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Pos(i32);
+    impl Component for Pos {}
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Vel(i32);
+    impl Component for Vel {
+        const STORAGE: StorageTypes = StorageTypes::TableAoS;
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Selected;
+    impl Component for Selected {
+        const STORAGE: StorageTypes = StorageTypes::SparseSet;
+    }
+
+    #[derive(Default)]
+    struct Counts {
+        frame: usize,
+        added: Vec<usize>,
+        changed: Vec<usize>,
+        selected: Vec<usize>,
+    }
+
+    fn move_system(counts: ResMut<Counts>, mut query: Query<(&mut Pos, &Vel)>) {
+        // only move entities during the second frame
+        if counts.frame != 1 {
+            return;
+        }
+        for (mut pos, vel) in query.iter() {
+            if vel.0 != 0 {
+                pos.0 += vel.0;
+            }
+        }
+    }
+
+    fn added_system(mut counts: ResMut<Counts>, mut query: Query<EntityKey, Added<Pos>>) {
+        let count = query.iter().count();
+        counts.added.push(count);
+    }
+
+    fn changed_system(mut counts: ResMut<Counts>, mut query: Query<&Pos, Changed<Pos>>) {
+        let count = query.iter().count();
+        counts.changed.push(count);
+    }
+
+    fn selected_system(mut counts: ResMut<Counts>, mut query: Query<&Pos, Changed<Selected>>) {
+        let count = query.iter().count();
+        counts.selected.push(count);
+        counts.frame += 1;
+    }
+
+    #[test]
+    fn test_added_and_changed_filters() {
+        let mut world = World::new();
+        world.add_resource(Counts::default());
+        world.add_systems((move_system, added_system, changed_system, selected_system).chain());
+
+        world.add_entity((Pos(0), Vel(1)));
+        world.add_entity((Pos(0), Vel(0)));
+        let selected = world.add_entity((Pos(0), Vel(0), Selected));
+
+        // frame 0: all entities were added
+        world.init_and_run();
+        // frame 1: one entity moved, one new entity was spawned
+        world.add_entity((Pos(5), Vel(0)));
+        world.run();
+        // frame 2: nothing changed, besides the mutably accessed sparse set component
+        let _ = world.get_single_component_mut::<Selected>(selected);
+        world.run();
+
+        let counts = world.get_resource::<Counts>().unwrap();
+        assert_eq!(vec![3, 1, 0], counts.added);
+        assert_eq!(vec![3, 2, 0], counts.changed);
+        assert_eq!(vec![1, 0, 1], counts.selected);
+    }
+}
@@ -4,7 +4,12 @@ use std::{cell::UnsafeCell, marker::PhantomData};
 
 use crate::{
     ecs::{
-        entity::Entities,
+        change_detection::SystemTicks,
+        entity::{
+            Entities,
+            hierarchy::{DespawnRecursiveCommand, Parent, SetParentCommand},
+            relation::{AddRelationCommand, Relation, RemoveRelationCommand},
+        },
         prelude::Component,
         system::{SystemId, SystemParamId},
     },
@@ -81,6 +86,7 @@ impl<'w, 's> SystemParam for Commands<'w, 's> {
         system_param_index: &mut usize,
         _system_param_ids: &[SystemParamId],
         world_data: *mut WorldData,
+        _system_ticks: SystemTicks,
     ) -> Self::Item<'r> {
         *system_param_index += 1;
         //TODO: access too command queue is not thread safe here
@@ -97,7 +103,7 @@ impl<'w, 's> SystemParam for Commands<'w, 's> {
         system_param_ids: &mut Vec<SystemParamId>,
         _world_data: &mut WorldData,
     ) {
-        system_param_ids.push(SystemParamId::NotRelevant);
+        system_param_ids.push(SystemParamId::Commands);
     }
 }
 
@@ -170,6 +176,44 @@ impl<'w, 's> Commands<'w, 's> {
             .push(Box::new(DespawnCommand { entity_key }));
     }
 
+    /// Despawns the entity together with all of its descendants.
+    pub fn despawn_recursive(&mut self, entity_key: EntityKey) {
+        self.command_queue
+            .push(Box::new(DespawnRecursiveCommand { entity_key }));
+    }
+
+    /// Makes the child entity a child of the parent entity and removes it from its previous parent.
+    /// The command is ignored, if the child is the parent itself or one of its ancestors,
+    /// or if one of the entities does not exist anymore.
+    pub fn set_parent(&mut self, child: EntityKey, parent: EntityKey) {
+        self.command_queue
+            .push(Box::new(SetParentCommand { child, parent }));
+    }
+
+    /// Removes the child entity from the children of its parent.
+    pub fn remove_parent(&mut self, child: EntityKey) {
+        self.remove_component::<Parent>(child);
+    }
+
+    /// Adds the relation (R, target) to the entity.
+    pub fn add_relation<R: Relation>(&mut self, entity_key: EntityKey, target: EntityKey) {
+        self.command_queue.push(Box::new(AddRelationCommand::<R> {
+            entity_key,
+            target,
+            _relation_marker: PhantomData,
+        }));
+    }
+
+    /// Removes the relation (R, target) from the entity.
+    pub fn remove_relation<R: Relation>(&mut self, entity_key: EntityKey, target: EntityKey) {
+        self.command_queue
+            .push(Box::new(RemoveRelationCommand::<R> {
+                entity_key,
+                target,
+                _relation_marker: PhantomData,
+            }));
+    }
+
     pub fn add_component<T: Component>(
         &mut self,
         entity_key: EntityKey,
@@ -377,4 +421,30 @@ mod test {
         world.init_and_run();
         world.run();
     }
+
+    #[derive(Debug, PartialEq)]
+    struct Label(usize);
+    impl Component for Label {}
+
+    #[derive(Default)]
+    struct Spawned(Option<EntityKey>);
+
+    fn ordered_commands_system(mut commands: Commands, mut spawned: ResMut<Spawned>) {
+        let entity = commands.spawn(Label(1));
+        commands.add_component(entity, Label(2), true);
+        commands.add_component(entity, Label(3), true);
+        spawned.0 = Some(entity);
+    }
+
+    #[test]
+    fn command_order_test() {
+        let mut world = World::new();
+        world.add_resource(Spawned::default());
+        world.add_systems(ordered_commands_system);
+        world.init_and_run();
+
+        // commands are executed in the order they were issued in
+        let entity = world.get_resource::<Spawned>().unwrap().0.unwrap();
+        assert_eq!(Some(&Label(3)), world.get_single_component::<Label>(entity));
+    }
 }
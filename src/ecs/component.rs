@@ -1,12 +1,26 @@
 // component.rs
 
 use std::{
-    alloc::Layout, any::TypeId, borrow::Cow, collections::HashMap, hash::Hash, mem::needs_drop,
-    ptr::drop_in_place, u32, usize,
+    alloc::Layout,
+    any::TypeId,
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+    mem::needs_drop,
+    ptr::{NonNull, drop_in_place},
+    sync::Arc,
+    u32, usize,
 };
 
 use crate::{
-    ecs::{entity::EntityKey, world::WorldData},
+    ecs::{
+        entity::{
+            EntityKey,
+            relation::{Relation, RelationKind},
+        },
+        world::WorldData,
+    },
     utils::{
         ecs_id::{EcsId, impl_ecs_id},
         sorted_vec::SortedVec,
@@ -27,13 +41,63 @@ pub trait Component: 'static + Sized {
 
 #[derive(Debug)]
 pub struct ComponentInfo {
-    #[allow(unused)]
     pub(crate) name: Cow<'static, str>,
-    #[allow(unused)]
     pub(crate) comp_id: ComponentId,
     pub(crate) type_id: TypeId,
     pub(crate) layout: Layout,
     pub(crate) drop: Option<unsafe fn(*mut u8)>,
+    pub(crate) storage: StorageTypes,
+    // hooks of the component type, which are called for type erased components
+    // e.g. when an entity with all its components is removed
+    pub(crate) hooks: ComponentHooks,
+    // component id of the relation kind and the target entity,
+    // if this component is a relation pair
+    pub(crate) relation: Option<(ComponentId, EntityKey)>,
+    // formats the type erased component, if the component type is registered for world dumps
+    pub(crate) debug: Option<DebugFn>,
+}
+
+// Type shared by all components, which are registered at runtime without a Rust type.
+pub(crate) struct DynamicComponent;
+
+/// Formats a type erased component with the Debug implementation of its type.
+pub(crate) type DebugFn = unsafe fn(NonNull<u8>, &mut fmt::Formatter<'_>) -> fmt::Result;
+
+/// Hook called with the world and the entity, which a component is added to,
+/// removed from or replaced on.
+pub type ComponentHook = Arc<dyn Fn(&mut WorldData, EntityKey) + Send + Sync>;
+
+/// Hooks of a component type.
+/// The static hooks of the Component trait are called before the hooks registered at runtime,
+/// which are called in their order of registration.
+#[derive(Default, Clone)]
+pub(crate) struct ComponentHooks {
+    pub(crate) on_add: Vec<ComponentHook>,
+    pub(crate) on_remove: Vec<ComponentHook>,
+    pub(crate) on_replace: Vec<ComponentHook>,
+}
+
+impl ComponentHooks {
+    fn new<T: Component>() -> Self {
+        let mut hooks = Self::default();
+        if let Some(on_add) = T::on_add() {
+            hooks.on_add.push(Arc::new(on_add));
+        }
+        if let Some(on_remove) = T::on_remove() {
+            hooks.on_remove.push(Arc::new(on_remove));
+        }
+        hooks
+    }
+}
+
+impl std::fmt::Debug for ComponentHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComponentHooks")
+            .field("on_add", &self.on_add.len())
+            .field("on_remove", &self.on_remove.len())
+            .field("on_replace", &self.on_replace.len())
+            .finish()
+    }
 }
 
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Copy, Hash, Debug)]
@@ -46,6 +110,8 @@ pub struct Archetype {
     pub(crate) archetype_id: ArchetypeId,
     pub(crate) soa_comp_ids: SortedVec<ComponentId>,
     pub(crate) aos_comp_ids: SortedVec<ComponentId>,
+    // relation pairs and their relation kinds, which don't store any data inside of the table
+    pub(crate) relation_comp_ids: SortedVec<ComponentId>,
 }
 
 impl Archetype {
@@ -53,13 +119,40 @@ impl Archetype {
         archetype_id: ArchetypeId,
         soa_comp_ids: SortedVec<ComponentId>,
         aos_comp_ids: SortedVec<ComponentId>,
+        relation_comp_ids: SortedVec<ComponentId>,
     ) -> Self {
         Self {
             archetype_id,
             soa_comp_ids,
             aos_comp_ids,
+            relation_comp_ids,
         }
     }
+
+    pub(crate) fn contains_comp(&self, comp_id: &ComponentId) -> bool {
+        self.soa_comp_ids.get_vec().binary_search(comp_id).is_ok()
+            || self.aos_comp_ids.get_vec().binary_search(comp_id).is_ok()
+            || self
+                .relation_comp_ids
+                .get_vec()
+                .binary_search(comp_id)
+                .is_ok()
+    }
+
+    // An archetype is only empty for entities, which contain sparse set components only.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.soa_comp_ids.get_vec().is_empty()
+            && self.aos_comp_ids.get_vec().is_empty()
+            && self.relation_comp_ids.get_vec().is_empty()
+    }
+
+    /// Returns the component ids of all table components and relations of the archetype.
+    pub(crate) fn all_comp_ids(&self) -> impl Iterator<Item = &ComponentId> {
+        self.soa_comp_ids
+            .iter()
+            .chain(self.aos_comp_ids.iter())
+            .chain(self.relation_comp_ids.iter())
+    }
 }
 
 #[derive(Eq, PartialEq, Clone, Copy, Hash, Debug)]
@@ -71,22 +164,109 @@ impl_ecs_id!(ArchetypeId);
 pub struct ArchetypeHash(u32);
 
 impl ComponentInfo {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn comp_id(&self) -> ComponentId {
+        self.comp_id
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn storage(&self) -> StorageTypes {
+        self.storage
+    }
+
     unsafe fn drop_ptr<T>(ptr: *mut u8) {
         let typed_ptr: *mut T = ptr.cast::<T>();
         let _ = unsafe { drop_in_place(typed_ptr) };
     }
 
-    pub fn new<T: 'static>(comp_id: u32) -> Self {
+    pub(crate) unsafe fn debug_ptr<T: fmt::Debug>(
+        ptr: NonNull<u8>,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        unsafe { ptr.cast::<T>().as_ref() }.fmt(f)
+    }
+
+    pub fn new<T: Component>(comp_id: u32) -> Self {
         Self {
             name: Cow::Borrowed(core::any::type_name::<T>()),
             comp_id: ComponentId(comp_id),
             type_id: TypeId::of::<T>(),
             layout: Layout::new::<T>(),
             drop: needs_drop::<T>().then_some(Self::drop_ptr::<T>),
+            storage: T::STORAGE,
+            hooks: ComponentHooks::new::<T>(),
+            relation: None,
+            debug: None,
         }
     }
+
+    // Relation pairs share the type of their relation kind,
+    // but are registered as their own zero sized component per target entity.
+    pub(crate) fn new_relation_pair<R: Relation>(
+        comp_id: u32,
+        kind: ComponentId,
+        target: EntityKey,
+    ) -> Self {
+        Self {
+            name: Cow::Owned(format!(
+                "({}, {}v{})",
+                core::any::type_name::<R>(),
+                target.get_id(),
+                target.get_generation()
+            )),
+            comp_id: ComponentId(comp_id),
+            type_id: TypeId::of::<RelationKind<R>>(),
+            layout: Layout::new::<()>(),
+            drop: None,
+            storage: RelationKind::<R>::STORAGE,
+            hooks: ComponentHooks::default(),
+            relation: Some((kind, target)),
+            debug: None,
+        }
+    }
+
+    // Dynamic components share the same type,
+    // so they are stored inside of sparse sets, which are keyed by component id.
+    pub(crate) fn new_dynamic(
+        comp_id: u32,
+        name: String,
+        layout: Layout,
+        drop: Option<unsafe fn(*mut u8)>,
+    ) -> Self {
+        Self {
+            name: Cow::Owned(name),
+            comp_id: ComponentId(comp_id),
+            type_id: TypeId::of::<DynamicComponent>(),
+            layout,
+            drop,
+            storage: StorageTypes::SparseSet,
+            hooks: ComponentHooks::default(),
+            relation: None,
+            debug: None,
+        }
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.type_id == TypeId::of::<DynamicComponent>()
+    }
 }
 
+/// Storage layout used for a component type.
+///
+/// Table components are stored inside of the archetype tables,
+/// while sparse set components are stored per component type keyed by the entity.
+/// Adding or removing a sparse set component does not move the entity to another table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum StorageTypes {
     TableAoS,
     TableSoA,
@@ -105,6 +285,8 @@ mod test {
         utils::tuple_types::TupleTypesExt,
     };
 
+    use std::sync::{Arc, Mutex};
+
     use super::Component;
     struct Vec3 {
         x: f32,
@@ -195,7 +377,7 @@ mod test {
         mut particle_query: Query<(&mut Pos3, &Velocity), (With<Particle>, Without<Player>)>,
     ) {
         assert_eq!(2, player_query.iter().count());
-        for (p, v) in particle_query.iter() {
+        for (mut p, v) in particle_query.iter() {
             p.pos.x += v.acc.x;
             p.pos.y += v.acc.y;
             p.pos.z += v.acc.z;
@@ -222,6 +404,127 @@ mod test {
         world.init_and_run();
     }
 
+    #[derive(Default)]
+    struct HookLog {
+        added: usize,
+        removed: usize,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Handle(u32);
+    impl Component for Handle {
+        fn on_add() -> Option<for<'a> fn(world_data: &mut WorldData, entity: EntityKey)> {
+            Some(on_add_handle)
+        }
+        fn on_remove() -> Option<for<'a> fn(world_data: &mut WorldData, entity: EntityKey)> {
+            Some(on_remove_handle)
+        }
+    }
+
+    fn on_add_handle(world: &mut WorldData, entity: EntityKey) {
+        assert!(world.get_single_component::<Handle>(entity).is_some());
+        world.get_resource_mut::<HookLog>().unwrap().added += 1;
+    }
+
+    fn on_remove_handle(world: &mut WorldData, entity: EntityKey) {
+        // component is still accessible, before it gets dropped
+        assert!(world.get_single_component::<Handle>(entity).is_some());
+        world.get_resource_mut::<HookLog>().unwrap().removed += 1;
+    }
+
+    fn hook_counts(world: &mut WorldData) -> (usize, usize) {
+        let log = world.get_resource::<HookLog>().unwrap();
+        (log.added, log.removed)
+    }
+
+    #[test]
+    fn test_component_remove_hooks() {
+        let mut world: World = World::new();
+        let world = world.data.get_mut();
+        world.add_resource(HookLog::default());
+
+        let e1 = world.add_entity((Handle(1), Pos3::new(1., 0., 0.)));
+        assert_eq!((1, 0), hook_counts(world));
+
+        // overwriting a component removes the old and adds the new one
+        world.add_component_to_entity(e1, Handle(2), true);
+        assert_eq!((2, 1), hook_counts(world));
+        assert_eq!(Some(&Handle(2)), world.get_single_component::<Handle>(e1));
+        world.add_component_to_entity(e1, Handle(3), false);
+        assert_eq!((2, 1), hook_counts(world));
+
+        // archetype transfers keep the components of aos and soa tables
+        let e2 = world.add_entity((Pos3::new(2., 0., 0.), Velocity::new(3., 0., 0.)));
+        world.add_component_to_entity(e2, Handle(4), false);
+        assert_eq!((3, 1), hook_counts(world));
+        assert_eq!(2., world.get_single_component::<Pos3>(e2).unwrap().pos.x);
+        assert_eq!(
+            3.,
+            world.get_single_component::<Velocity>(e2).unwrap().acc.x
+        );
+
+        world.remove_component_from_entity::<Handle>(e2);
+        assert_eq!((3, 2), hook_counts(world));
+        world.remove_component_from_entity::<Handle>(e2);
+        assert_eq!((3, 2), hook_counts(world));
+        world.remove_component_from_entity::<Velocity>(e2);
+        assert_eq!(2., world.get_single_component::<Pos3>(e2).unwrap().pos.x);
+        assert_eq!(1., world.get_single_component::<Pos3>(e1).unwrap().pos.x);
+
+        world.remove_entity(e1);
+        assert_eq!((3, 3), hook_counts(world));
+        world.remove_entity(e2);
+        assert_eq!((3, 3), hook_counts(world));
+    }
+
+    #[test]
+    fn test_runtime_registered_hooks() {
+        let mut world: World = World::new();
+        world.add_resource(HookLog::default());
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let log = calls.clone();
+        world.register_on_add::<Handle>(move |world, entity| {
+            let handle = world.get_single_component::<Handle>(entity).unwrap().0;
+            log.lock().unwrap().push(format!("add1 {handle}"));
+        });
+        let log = calls.clone();
+        world.register_on_add::<Handle>(move |_world, _entity| {
+            log.lock().unwrap().push("add2".to_string());
+        });
+        let log = calls.clone();
+        world.register_on_replace::<Handle>(move |world, entity| {
+            let handle = world.get_single_component::<Handle>(entity).unwrap().0;
+            log.lock().unwrap().push(format!("replace {handle}"));
+        });
+        let log = calls.clone();
+        world.register_on_remove::<Handle>(move |_world, _entity| {
+            log.lock().unwrap().push("remove".to_string());
+        });
+
+        let entity = world.add_entity(Handle(1));
+        world
+            .data
+            .get_mut()
+            .add_component_to_entity(entity, Handle(2), true);
+        world.data.get_mut().remove_entity(entity);
+
+        assert_eq!(
+            vec![
+                "add1 1",
+                "add2",
+                "replace 1",
+                "remove",
+                "add1 2",
+                "add2",
+                "remove"
+            ],
+            *calls.lock().unwrap()
+        );
+        // static hooks of the component trait are still called
+        assert_eq!((2, 2), hook_counts(world.data.get_mut()));
+    }
+
     #[test]
     fn test_tuple_ext_methods() {
         let t = Pos3::new(0., 0., 0.);
@@ -0,0 +1,416 @@
+// dump.rs
+
+use std::{
+    any::{Any, TypeId},
+    fmt::{self, Display, Write},
+    ptr::NonNull,
+    str::FromStr,
+};
+
+use crate::{
+    ecs::{
+        component::{Component, DebugFn},
+        entity::EntityKey,
+        storages::entity_storage::EntityStorage,
+        world::WorldData,
+    },
+    utils::ecs_id::EcsId,
+};
+
+/// Error of a scene import, with the number of the line it occurred in, starting at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Scene error in line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+// Type erased functions of a component type, which can be imported from scenes.
+#[derive(Clone)]
+struct ComponentParser {
+    name: String,
+    type_id: TypeId,
+    parse: fn(&str) -> Result<Box<dyn Any>, String>,
+    // spawns a new entity, if no entity is supplied
+    insert: fn(&mut WorldData, Option<EntityKey>, Box<dyn Any>) -> EntityKey,
+}
+
+/// Component types, which can be imported from scenes, identified by their short type names.
+#[derive(Default)]
+pub(crate) struct SceneRegistry {
+    parsers: Vec<ComponentParser>,
+}
+
+impl SceneRegistry {
+    pub(crate) fn register_component<T: Component + FromStr>(&mut self)
+    where
+        T::Err: Display,
+    {
+        let name = short_type_name(std::any::type_name::<T>());
+        match self.parsers.iter().find(|p| p.name == name) {
+            Some(p) if p.type_id == TypeId::of::<T>() => {}
+            Some(_) => panic!("Another component is already registered with the name {name}."),
+            None => self.parsers.push(ComponentParser {
+                name,
+                type_id: TypeId::of::<T>(),
+                parse: parse_component::<T>,
+                insert: insert_component::<T>,
+            }),
+        }
+    }
+}
+
+fn parse_component<T: Component + FromStr>(value: &str) -> Result<Box<dyn Any>, String>
+where
+    T::Err: Display,
+{
+    match T::from_str(value) {
+        Ok(component) => Ok(Box::new(component)),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn insert_component<T: Component>(
+    world_data: &mut WorldData,
+    entity_key: Option<EntityKey>,
+    component: Box<dyn Any>,
+) -> EntityKey {
+    let component = *component
+        .downcast::<T>()
+        .expect("Parsed component should be of the registered type.");
+    match entity_key {
+        Some(entity_key) => {
+            world_data.add_component_to_entity(entity_key, component, true);
+            entity_key
+        }
+        None => world_data.add_entity(component),
+    }
+}
+
+/// Removes the module paths of all types inside of a type name,
+/// e.g. `my_game::Inventory<my_game::items::Sword>` becomes `Inventory<Sword>`.
+pub fn short_type_name(name: &str) -> String {
+    fn last_segment(path: &str) -> &str {
+        path.rsplit("::").next().unwrap_or(path)
+    }
+
+    let mut short = String::with_capacity(name.len());
+    let mut path_start = 0;
+    for (i, c) in name.char_indices() {
+        if !(c.is_alphanumeric() || c == '_' || c == ':') {
+            short.push_str(last_segment(&name[path_start..i]));
+            short.push(c);
+            path_start = i + c.len_utf8();
+        }
+    }
+    short.push_str(last_segment(&name[path_start..]));
+    short
+}
+
+struct DebugPtr {
+    ptr: NonNull<u8>,
+    debug: DebugFn,
+}
+
+impl fmt::Debug for DebugPtr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        unsafe { (self.debug)(self.ptr, f) }
+    }
+}
+
+// Every entity is written as a block of its components, ordered by their component ids:
+//
+// entity 3v0 (archetype: 2, row: 0) {
+//     Health: Health(10)
+//     # Cache
+//     # relation (Likes, 1v0)
+// }
+//
+// Components, which are not registered for dumps, and relations are written as comments,
+// so that the dump can be imported again.
+pub(crate) fn dump_world(entity_storage: &EntityStorage) -> String {
+    let mut text = String::new();
+    let mut comp_ids = Vec::new();
+    for entity_key in entity_storage.entities.keys() {
+        let entity = entity_storage
+            .entities
+            .get(entity_key)
+            .expect("Entity of a key should exist.");
+        let _ = writeln!(
+            text,
+            "entity {}v{} (archetype: {}, row: {}) {{",
+            entity_key.get_id(),
+            entity_key.get_generation(),
+            entity.archetype_id.id_usize(),
+            entity.row_id.id_usize()
+        );
+
+        comp_ids.clear();
+        entity_storage.get_entity_comp_ids(entity_key, &mut comp_ids);
+        let archetype = &entity_storage.archetypes[entity.archetype_id.id_usize()];
+        comp_ids.extend(
+            archetype
+                .relation_comp_ids
+                .iter()
+                .filter(|cid| entity_storage.components[cid.id_usize()].relation.is_some()),
+        );
+        comp_ids.sort();
+
+        for comp_id in comp_ids.iter() {
+            let cinfo = &entity_storage.components[comp_id.id_usize()];
+            let name = short_type_name(&cinfo.name);
+            let ptr = entity_storage.get_component_ptr(entity_key, *comp_id);
+            let _ = match (cinfo.debug, ptr) {
+                (Some(debug), Some(ptr)) => {
+                    writeln!(text, "    {name}: {:?}", DebugPtr { ptr, debug })
+                }
+                _ if cinfo.relation.is_some() => writeln!(text, "    # relation {name}"),
+                _ => writeln!(text, "    # {name}"),
+            };
+        }
+        text.push_str("}\n");
+    }
+    text
+}
+
+// Scenes use the same format as the world dumps. The keys, archetypes and rows
+// of the entity headers are ignored, so that hand written scenes can start entities with `entity {`.
+// Every component line consists of the short type name of the component and the text,
+// which is parsed with the FromStr implementation of the component type.
+// Empty lines and lines starting with # are skipped.
+//
+// The whole scene is parsed, before any entity is spawned.
+pub(crate) fn import_scene(
+    world_data: &mut WorldData,
+    text: &str,
+) -> Result<Vec<EntityKey>, SceneError> {
+    let parsers = world_data.scene_registry.parsers.clone();
+    let mut entities = Vec::new();
+    let mut components: Option<Vec<_>> = None;
+    let mut line_count = 0;
+    for (index, line) in text.lines().enumerate() {
+        line_count = index + 1;
+        let error = |message: String| SceneError {
+            line: index + 1,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some(entity_components) = components.as_mut() else {
+            if line.starts_with("entity") && line.ends_with('{') {
+                components = Some(Vec::new());
+                continue;
+            }
+            return Err(error(format!(
+                "Expected the start of an entity, found: {line}"
+            )));
+        };
+        if line == "}" {
+            // entities without components cannot exist,
+            // skipping them would shift the returned keys against the scene
+            if entity_components.is_empty() {
+                return Err(error("Entity contains no components.".to_string()));
+            }
+            entities.extend(components.take());
+            continue;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(error(format!("Expected a component, found: {line}")));
+        };
+        let name = name.trim();
+        let Some(parser) = parsers.iter().find(|p| p.name == name) else {
+            return Err(error(format!(
+                "Component {name} is not registered for scene imports."
+            )));
+        };
+        let component = (parser.parse)(value.trim())
+            .map_err(|msg| error(format!("Invalid value of component {name}: {msg}")))?;
+        entity_components.push((parser.insert, component));
+    }
+    if components.is_some() {
+        return Err(SceneError {
+            line: line_count,
+            message: "Entity is not closed.".to_string(),
+        });
+    }
+
+    let mut entity_keys = Vec::with_capacity(entities.len());
+    for components in entities {
+        let mut entity_key = None;
+        for (insert, component) in components {
+            entity_key = Some(insert(world_data, entity_key, component));
+        }
+        entity_keys.extend(entity_key);
+    }
+    Ok(entity_keys)
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use crate::ecs::{dump::short_type_name, prelude::*};
+
+    #[derive(Debug, PartialEq)]
+    #[allow(unused)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+    impl Component for Position {
+        const STORAGE: StorageTypes = StorageTypes::TableAoS;
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+    impl Component for Health {}
+    impl FromStr for Health {
+        type Err = String;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            s.strip_prefix("Health(")
+                .and_then(|s| s.strip_suffix(')'))
+                .and_then(|s| s.parse().ok())
+                .map(Health)
+                .ok_or(format!("Expected Health(<u32>), found: {s}"))
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Name(String);
+    impl Component for Name {
+        const STORAGE: StorageTypes = StorageTypes::SparseSet;
+    }
+    impl FromStr for Name {
+        type Err = String;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            s.strip_prefix("Name(\"")
+                .and_then(|s| s.strip_suffix("\")"))
+                .map(|s| Name(s.to_string()))
+                .ok_or(format!("Expected Name(\"<name>\"), found: {s}"))
+        }
+    }
+
+    struct Cache;
+    impl Component for Cache {}
+
+    struct Likes;
+    impl Relation for Likes {}
+
+    #[test]
+    fn test_short_type_name() {
+        assert_eq!("Health", short_type_name("ecs::ecs::dump::Health"));
+        assert_eq!(
+            "Inventory<Sword, u8>",
+            short_type_name("game::Inventory<game::items::Sword, u8>")
+        );
+        assert_eq!("(Likes, 1v0)", short_type_name("(game::Likes, 1v0)"));
+    }
+
+    #[test]
+    fn test_dump_world() {
+        let mut world = World::new();
+        world.register_debug_component::<Position>();
+        world.register_scene_component::<Health>();
+        world.register_scene_component::<Name>();
+        let hero = world.add_entity((
+            Position { x: 1., y: 2. },
+            Health(10),
+            Name("hero".to_string()),
+        ));
+        let tree = world.add_entity((Position { x: 5., y: 0.5 }, Cache));
+        world.add_relation::<Likes>(hero, tree);
+
+        let dump = world.dump_text();
+        let hero_start = dump.find("entity 0v0 (archetype: ").unwrap();
+        let tree_start = dump.find("entity 1v0 (archetype: ").unwrap();
+        let (hero_dump, tree_dump) = dump[hero_start..].split_at(tree_start - hero_start);
+        assert!(hero_dump.contains("row: 0) {\n"));
+        assert!(hero_dump.contains("    Position: Position { x: 1.0, y: 2.0 }\n"));
+        assert!(hero_dump.contains("    Health: Health(10)\n"));
+        assert!(hero_dump.contains("    Name: Name(\"hero\")\n"));
+        assert!(hero_dump.contains("    # relation (Likes, 1v0)\n"));
+        assert!(tree_dump.contains("    Position: Position { x: 5.0, y: 0.5 }\n"));
+        assert!(tree_dump.contains("    # Cache\n"));
+        assert!(tree_dump.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_import_scene() {
+        let mut world = World::new();
+        world.register_scene_component::<Health>();
+        world.register_scene_component::<Name>();
+        let scene = "
+            # hand written scene
+            entity {
+                Name: Name(\"orc\")
+                Health: Health(3)
+            }
+
+            entity {
+                Health: Health(7)
+            }
+        ";
+        let entities = world.import_scene(scene).unwrap();
+        assert_eq!(2, entities.len());
+        assert_eq!(
+            Some(&Name("orc".to_string())),
+            world.get_single_component::<Name>(entities[0])
+        );
+        assert_eq!(
+            Some(&Health(3)),
+            world.get_single_component::<Health>(entities[0])
+        );
+        assert_eq!(
+            Some(&Health(7)),
+            world.get_single_component::<Health>(entities[1])
+        );
+
+        // dumps of registered components can be imported again
+        let dump = world.dump_text();
+        let mut imported = World::new();
+        imported.register_scene_component::<Health>();
+        imported.register_scene_component::<Name>();
+        imported.import_scene(&dump).unwrap();
+        // archetypes are created in another order, while importing components one by one
+        let components = |dump: &str| -> Vec<String> {
+            dump.lines()
+                .map(|l| l.split(" (").next().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(components(&dump), components(&imported.dump_text()));
+    }
+
+    #[test]
+    fn test_invalid_scene() {
+        let mut world = World::new();
+        world.register_scene_component::<Health>();
+
+        let err = world
+            .import_scene("entity {\n    Health: Health(1)\n}\nentity {\n    Health: lots\n}")
+            .unwrap_err();
+        assert_eq!(5, err.line);
+        assert!(err.message.contains("Expected Health(<u32>)"));
+        let err = world.import_scene("entity {\n    Mana: 3\n}").unwrap_err();
+        assert_eq!(2, err.line);
+        let err = world
+            .import_scene("entity {\n    Health: Health(1)")
+            .unwrap_err();
+        assert_eq!(2, err.line);
+        let err = world
+            .import_scene("entity {\n    Health: Health(1)\n}\nentity {\n}")
+            .unwrap_err();
+        assert_eq!(5, err.line);
+        assert!(err.message.contains("no components"));
+        // no entity is spawned by an invalid scene
+        assert!(world.dump_text().is_empty());
+    }
+}
@@ -5,8 +5,7 @@ use std::collections::{HashMap, HashSet};
 use crate::{
     ecs::{
         component::{ArchetypeId, ComponentId},
-        prelude::EntityStorage,
-        query::{QueryParamMetaData, QueryState, RefKind, query_filter},
+        query::{QueryParamMetaData, QueryState, RefKind},
         resource::ResourceId,
         system::SystemId,
     },
@@ -32,6 +31,43 @@ pub enum EcsEdge {
     None,
 }
 
+impl EcsEdge {
+    // strength of the access, which is used to merge multiple edges between the same nodes
+    fn access_level(&self) -> u8 {
+        match self {
+            EcsEdge::None => 0,
+            EcsEdge::Shared => 1,
+            EcsEdge::Excl => 2,
+            EcsEdge::Owned => 3,
+        }
+    }
+
+    /// Returns true, if both accesses can't happen at the same time.
+    pub fn conflicts_with(self, other: EcsEdge) -> bool {
+        match (self, other) {
+            (EcsEdge::None, _) | (_, EcsEdge::None) => false,
+            (EcsEdge::Shared, EcsEdge::Shared) => false,
+            _ => true,
+        }
+    }
+
+    /// Returns the edge with the stronger access.
+    pub fn merge(self, other: EcsEdge) -> EcsEdge {
+        if other.access_level() > self.access_level() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+fn insert_merged_edge(edges: &mut EcsEdges, key: u32, edge: EcsEdge) {
+    let merged = edges
+        .get(&key)
+        .map_or(edge, |old_edge| old_edge.merge(edge));
+    edges.insert(key, merged);
+}
+
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Copy, Hash, Debug)]
 pub struct QueryId(u32);
 impl_ecs_id!(QueryId);
@@ -61,6 +97,8 @@ pub struct ResourceNode {
     #[allow(unused)]
     pub(crate) resource_id: ResourceId,
     pub(crate) system_edges: EcsEdges,
+    // nodes are kept, when their resource gets removed, because systems still refer to them
+    pub(crate) present: bool,
 }
 
 impl ResourceNode {
@@ -68,6 +106,7 @@ impl ResourceNode {
         Self {
             resource_id,
             system_edges: HashMap::new(),
+            present: false,
         }
     }
 }
@@ -105,7 +144,6 @@ impl ArchetypeNode {
 pub struct QueryNode {
     #[allow(unused)]
     pub(crate) query_id: QueryId,
-    #[allow(unused)]
     pub(crate) component_edges: EcsEdges,
     pub(crate) archetype_edges: EcsEdges,
 }
@@ -131,6 +169,12 @@ pub struct EcsDependencyGraph {
     pub component_keys: HashMap<ComponentId, u32>,
     pub archetype_keys: HashMap<ArchetypeId, u32>,
     pub query_keys: HashMap<QueryId, u32>,
+    // set, when a new archetype makes the queries of systems overlap,
+    // which are scheduled in the same parallel batch
+    pub(crate) schedule_outdated: bool,
+    // pairs of query keys of systems in the same parallel batch, whose accesses conflict,
+    // but which don't match the same archetype yet
+    pub(crate) parallel_query_conflicts: HashSet<(u32, u32)>,
 }
 
 impl EcsDependencyGraph {
@@ -146,6 +190,8 @@ impl EcsDependencyGraph {
             archetype_keys: HashMap::new(),
             component_keys: HashMap::new(),
             query_keys: HashMap::new(),
+            schedule_outdated: false,
+            parallel_query_conflicts: HashSet::new(),
         }
     }
 
@@ -158,30 +204,21 @@ impl EcsDependencyGraph {
         self.system_keys.insert(system_id, key);
         key
     }
-    ///NOTE: Do not insert the same resource id multiple times
-    /// into the resource edges of a system.
-    /// A system can only contain one of each resource as a system param.
-    /// Otherwise this method will panic.
+    /// Inserts a resource edge into a system.
+    /// Multiple params of the same resource type are merged into the strongest access,
+    /// conflicts between them are validated after the initialization of the system.
     pub fn insert_system_resource(
         &mut self,
         system_id: SystemId,
         resource: ResourceId,
         ecs_edge: EcsEdge,
     ) -> (u32, u32) {
-        let system_key = if let Some(key) = self.system_keys.get(&system_id) {
-            *key
-        } else {
-            self.insert_system(system_id)
-        };
+        let system_key = self.insert_system(system_id);
         let resource_key = self.insert_resource(resource);
-        let resource_id = resource_key as usize;
-        let res = &mut self.resources[resource_id];
-        res.system_edges.insert(system_key, ecs_edge);
+        let res = &mut self.resources[resource_key as usize];
+        insert_merged_edge(&mut res.system_edges, system_key, ecs_edge);
         let system_node = &mut self.systems[system_key as usize];
-        if let Some(_res_edge_key) = system_node.resource_edges.get(&resource_key) {
-            panic!("A system cannot have multiple params of the same resource type.")
-        }
-        let _ = system_node.resource_edges.insert(resource_key, ecs_edge);
+        insert_merged_edge(&mut system_node.resource_edges, resource_key, ecs_edge);
 
         (system_key, resource_key)
     }
@@ -194,6 +231,24 @@ impl EcsDependencyGraph {
         self.resource_keys.insert(resource_id, key);
         key
     }
+    /// Marks the resource as present in the world.
+    pub fn add_resource(&mut self, resource_id: ResourceId) -> u32 {
+        let key = self.insert_resource(resource_id);
+        self.resources[key as usize].present = true;
+        key
+    }
+    /// Marks the resource as removed from the world.
+    /// The node and its system edges are kept, so that the keys stay valid.
+    pub fn remove_resource(&mut self, resource_id: ResourceId) {
+        if let Some(key) = self.resource_keys.get(&resource_id) {
+            self.resources[*key as usize].present = false;
+        }
+    }
+    pub fn contains_resource(&self, resource_id: ResourceId) -> bool {
+        self.resource_keys
+            .get(&resource_id)
+            .is_some_and(|key| self.resources[*key as usize].present)
+    }
     pub fn insert_component(&mut self, component_id: ComponentId) -> u32 {
         if let Some(key) = self.component_keys.get(&component_id) {
             return *key;
@@ -222,9 +277,12 @@ impl EcsDependencyGraph {
         key
     }
 
+    /// Inserts a new archetype and adds it to all queries, which match it.
+    /// Marks the schedules as outdated, if the archetype is matched by multiple queries
+    /// of systems running in parallel, whose accesses to one of its components conflict.
     pub(crate) fn insert_archetype_components(
         &mut self,
-        query_stati: &mut Vec<QueryState>,
+        query_stati: &mut [QueryState],
         archetype_id: ArchetypeId,
         comp_ids: &[ComponentId],
     ) {
@@ -235,60 +293,58 @@ impl EcsDependencyGraph {
             arch.component_edges.insert(comp_key, EcsEdge::None);
         }
 
-        // update archetypes of query nodes
-        let arch: &mut ArchetypeNode = &mut self.archetypes[arch_key as usize];
-        let mut query_ids: HashSet<QueryId> = HashSet::new();
-        arch.component_edges.iter().for_each(|(c_row_id, _edge)| {
-            self.components[*c_row_id as usize]
-                .system_edges
-                .iter()
-                .for_each(|(s_row_id, _edge)| {
-                    self.systems[*s_row_id as usize]
-                        .query_edges
-                        .iter()
-                        .map(|(q_row_id, _edge)| self.queries[*q_row_id as usize].query_id.clone())
-                        .for_each(|qid| {
-                            query_ids.insert(qid);
-                        });
-                })
-        });
-        let arch_comp_ids_set: HashSet<ComponentId> = comp_ids.iter().map(|cid| *cid).collect();
-        let arch_comp_ids: SortedVec<ComponentId> = comp_ids
-            .iter()
-            .map(|cid| *cid)
-            .collect::<Vec<ComponentId>>()
-            .into();
-        for qid in query_ids.iter() {
-            let qnode = &mut self.queries[qid.id_usize()];
-            let query_comp_row_ids: HashSet<u32> = qnode
-                .component_edges
-                .keys()
-                .map(|c_row_id| *c_row_id)
-                .collect();
-            let query_state = &mut query_stati[qid.id_usize()];
-
-            if EntityStorage::is_subset_of(&query_state.query_param_meta_data, &arch_comp_ids) {
-                let not_filtered_out = query_filter::comp_ids_compatible_with_filter(
-                    &arch_comp_ids_set,
-                    &query_state.filter,
-                );
-                // need to take query filters into account
-                if not_filtered_out {
-                    println!("not filtered out");
-                    dbg!(&arch_comp_ids);
-                    dbg!(&query_comp_row_ids);
-                    dbg!(&query_state.filter);
-                    query_state.arch_ids.insert(archetype_id);
-                    qnode.archetype_edges.insert(arch_key, EcsEdge::None);
-                }
+        // every query is checked, because optional params, filters and sparse set components
+        // let queries match archetypes without any of their queried components
+        let arch_comp_ids: SortedVec<ComponentId> = comp_ids.to_vec().into();
+        let mut matching_query_keys = Vec::new();
+        for (qid, query_state) in query_stati.iter_mut().enumerate() {
+            query_state.archetypes_seen =
+                query_state.archetypes_seen.max(archetype_id.id_usize() + 1);
+            if !query_state.archetype_matches(&arch_comp_ids) {
+                continue;
             }
+            query_state.arch_ids.insert(archetype_id);
+            let query_key = self.insert_query(qid.into());
+            let qnode = &mut self.queries[query_key as usize];
+            qnode.archetype_edges.insert(arch_key, EcsEdge::None);
+            matching_query_keys.push(query_key);
+        }
+
+        // systems, whose queries did not overlap before, can be scheduled in parallel
+        let overlapping = matching_query_keys.iter().enumerate().any(|(n, q1)| {
+            matching_query_keys[n + 1..].iter().any(|q2| {
+                self.parallel_query_conflicts
+                    .contains(&(*q1.min(q2), *q1.max(q2)))
+            })
+        });
+        if overlapping {
+            self.schedule_outdated = true;
         }
     }
 
-    ///NOTE: Do not insert the same component ids multiple times
-    /// into the componenent edges of a system.
-    /// A system can currently only contain one of each component as a system param.
-    /// Otherwise this method will panic.
+    /// Returns true, if both queries access the same component and one of them exclusively.
+    pub(crate) fn queries_access_conflict(&self, query_key1: u32, query_key2: u32) -> bool {
+        let q1 = &self.queries[query_key1 as usize];
+        let q2 = &self.queries[query_key2 as usize];
+        q1.component_edges.iter().any(|(comp_key, edge1)| {
+            q2.component_edges
+                .get(comp_key)
+                .is_some_and(|edge2| edge1.conflicts_with(*edge2))
+        })
+    }
+
+    /// Returns true, if both queries can match entities of the same archetype.
+    pub(crate) fn queries_share_archetype(&self, query_key1: u32, query_key2: u32) -> bool {
+        let q1 = &self.queries[query_key1 as usize];
+        let q2 = &self.queries[query_key2 as usize];
+        q1.archetype_edges
+            .keys()
+            .any(|arch_key| q2.archetype_edges.contains_key(arch_key))
+    }
+
+    /// Inserts the component edges of a query into a system.
+    /// Multiple params of the same component type are merged into the strongest access,
+    /// conflicts between them are validated after the initialization of the system.
     pub fn insert_system_components(
         &mut self,
         system_id: SystemId,
@@ -302,12 +358,9 @@ impl EcsDependencyGraph {
                 RefKind::Shared => EcsEdge::Shared,
             };
             let comp: &mut ComponentNode = &mut self.components[comp_key as usize];
-            comp.system_edges.insert(system_key, edge);
+            insert_merged_edge(&mut comp.system_edges, system_key, edge);
             let system_node = &mut self.systems[system_key as usize];
-            if let Some(_comp_edge_key) = system_node.component_edges.get(&comp_key) {
-                panic!("A system cannot have multiple params of the same component type.")
-            }
-            let _ = system_node.component_edges.insert(comp_key, edge);
+            insert_merged_edge(&mut system_node.component_edges, comp_key, edge);
         }
     }
     pub fn insert_query_archetypes(&mut self, query_id: QueryId, archetype_ids: &[ArchetypeId]) {
@@ -318,6 +371,22 @@ impl EcsDependencyGraph {
             query.archetype_edges.insert(arch_key, EcsEdge::None);
         }
     }
+    pub fn insert_query_components(
+        &mut self,
+        query_id: QueryId,
+        meta_datas: &[QueryParamMetaData],
+    ) {
+        let query_key = self.insert_query(query_id);
+        for meta_data in meta_datas.iter() {
+            let comp_key = self.insert_component(meta_data.comp_id);
+            let edge = match meta_data.ref_kind {
+                RefKind::Exclusive => EcsEdge::Excl,
+                RefKind::Shared => EcsEdge::Shared,
+            };
+            let query = &mut self.queries[query_key as usize];
+            insert_merged_edge(&mut query.component_edges, comp_key, edge);
+        }
+    }
     pub fn insert_system_query(&mut self, system_id: SystemId, query_id: QueryId) {
         let system_key = self.insert_system(system_id);
         let query_key = self.insert_query(query_id);
@@ -334,7 +403,10 @@ impl Default for EcsDependencyGraph {
 
 #[cfg(test)]
 mod test {
-    use crate::ecs::prelude::*;
+    use crate::ecs::{
+        prelude::*,
+        scheduler::{SystemParamConflictKind, find_conflicts_in_systems_params},
+    };
 
     #[allow(unused)]
     struct Res1(usize);
@@ -388,14 +460,28 @@ mod test {
     }
 
     fn test_query_same_component_sets_with_excluding_filters_system(
-        mut comp1_2_query: Query<(&mut Comp1, &Comp2), With<Comp3>>,
-        mut comp1_3_query: Query<(&mut Comp1, &Comp3), With<Comp2>>,
+        mut comp1_2_query: Query<(&mut Comp1, &Comp2), Without<Comp3>>,
+        mut comp1_3_query: Query<(&mut Comp1, &Comp3), Without<Comp2>>,
     ) {
-        //TODO: this should be possible
-        assert_eq!(2, comp1_2_query.iter().count());
+        assert_eq!(1, comp1_2_query.iter().count());
         assert_eq!(2, comp1_3_query.iter().count());
     }
 
+    fn overlapping_filters_system(
+        _comp1_2_query: Query<(&mut Comp1, &Comp2), With<Comp3>>,
+        _comp1_3_query: Query<(&mut Comp1, &Comp3), With<Comp2>>,
+    ) {
+        panic!("This should not be reached by system execution.")
+    }
+
+    fn res_and_res_mut_system(_res1: Res<Res1>, _res1_mut: ResMut<Res1>) {
+        panic!("This should not be reached by system execution.")
+    }
+
+    fn multiple_commands_system(_commands1: Commands, _commands2: Commands) {
+        panic!("This should not be reached by system execution.")
+    }
+
     #[test]
     #[should_panic(expected = "A system cannot have multiple params of the same resource type.")]
     fn test_multiple_same_resource_types_in_sysparams() {
@@ -426,4 +512,46 @@ mod test {
         world.add_systems(test_query_same_component_sets_with_excluding_filters_system);
         world.init_and_run();
     }
+
+    #[test]
+    #[should_panic(expected = "A system cannot have multiple params of the same component type.")]
+    fn test_query_same_component_sets_with_overlapping_filters() {
+        let mut world = init_world();
+        world.add_systems(overlapping_filters_system);
+        world.init_and_run();
+    }
+
+    #[test]
+    #[should_panic(expected = "A system cannot have multiple Commands params.")]
+    fn test_multiple_commands_in_sysparams() {
+        let mut world = init_world();
+        world.add_systems(multiple_commands_system);
+        world.init_and_run();
+    }
+
+    #[test]
+    fn test_system_param_conflict_error() {
+        let mut world = init_world();
+        let res_system_id = world.add_systems(res_and_res_mut_system).pop().unwrap();
+        let query_system_id = world.add_systems(overlapping_filters_system).pop().unwrap();
+        world.systems.init_systems(world.data.get_mut());
+
+        let conflict =
+            find_conflicts_in_systems_params(world.data.get_mut(), &world.systems, res_system_id)
+                .unwrap_err();
+        assert_eq!(SystemParamConflictKind::Resource, conflict.kind);
+        assert!(conflict.system_name.contains("res_and_res_mut_system"));
+        assert_eq!(2, conflict.params.len());
+        assert!(conflict.params[0].starts_with("param 0: Res<"));
+        assert!(conflict.params[1].starts_with("param 1: ResMut<"));
+        assert!(conflict.conflicting_types[0].ends_with("Res1"));
+
+        let conflict =
+            find_conflicts_in_systems_params(world.data.get_mut(), &world.systems, query_system_id)
+                .unwrap_err();
+        assert_eq!(SystemParamConflictKind::Component, conflict.kind);
+        assert!(conflict.system_name.contains("overlapping_filters_system"));
+        assert_eq!(1, conflict.conflicting_types.len());
+        assert!(conflict.conflicting_types[0].ends_with("Comp1"));
+    }
 }
@@ -6,7 +6,7 @@ use std::{
 };
 
 use crate::{
-    ecs::component::Component,
+    ecs::{change_detection::Tick, component::Component},
     utils::{
         ecs_id::{EcsId, impl_ecs_id},
         tuple_iters::{TupleConstructorSource, TupleIterConstructor, TupleIterator},
@@ -15,6 +15,9 @@ use crate::{
 
 use super::component::ArchetypeId;
 
+pub mod hierarchy;
+pub mod relation;
+
 #[derive(Eq, PartialEq, Clone, Copy, Hash, Debug)]
 pub struct EntityId(u32);
 
@@ -165,6 +168,47 @@ impl Entities {
         }
     }
 
+    /// Returns the keys of all entities, ordered by their ids.
+    pub(crate) fn keys(&self) -> Vec<EntityKey> {
+        self.vec
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.entity.is_some())
+            .map(|(id, entry)| EntityKey::new(id as u32, entry.generation))
+            .collect()
+    }
+
+    /// Returns the current generation of every id, including the ids of removed entities.
+    pub(crate) fn generations(&self) -> Vec<u32> {
+        self.vec.iter().map(|entry| entry.generation).collect()
+    }
+
+    // Replaces all entries with empty ones of the supplied generations,
+    // entities are inserted afterwards with their reserved keys.
+    // The empty indices need to be rebuilt after inserting them.
+    pub(crate) fn reset_with_generations(&mut self, generations: &[u32]) {
+        self.vec = generations
+            .iter()
+            .map(|generation| Entry {
+                entity: None,
+                generation: *generation,
+            })
+            .collect();
+        self.rebuild_empty_indices();
+    }
+
+    pub(crate) fn rebuild_empty_indices(&mut self) {
+        self.empty_indices = self
+            .vec
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, entry)| entry.entity.is_none())
+            .map(|(id, _)| id as u32)
+            .collect();
+        self.reset_barriers();
+    }
+
     pub(crate) fn reset_barriers(&self) {
         self.empty_indices_barrier
             .store(0, atomic::Ordering::Relaxed);
@@ -213,7 +257,7 @@ impl<'vec> TupleIterator for EntityKeyIterUnsafe<'vec> {
 impl<S: TupleConstructorSource> TupleIterConstructor<S> for EntityKey {
     type Construct<'c> = EntityKeyIterUnsafe<'c>;
 
-    unsafe fn construct<'s>(mut source: NonNull<S>) -> Self::Construct<'s> {
+    unsafe fn construct<'s>(mut source: NonNull<S>, _this_run: Tick) -> Self::Construct<'s> {
         unsafe { source.as_mut().get_entity_key_iter() }
     }
 }
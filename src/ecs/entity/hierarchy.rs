@@ -0,0 +1,389 @@
+// hierarchy.rs
+
+use crate::ecs::{
+    commands::Command, component::Component, entity::EntityKey, query::Query,
+    query::query_filter::QueryFilter, world::WorldData,
+};
+
+/// Parent entity of an entity.
+/// The Children component of the parent entity is kept consistent by the hooks of this component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parent(EntityKey);
+
+impl Parent {
+    pub fn new(parent: EntityKey) -> Self {
+        Self(parent)
+    }
+
+    pub fn get(&self) -> EntityKey {
+        self.0
+    }
+}
+
+/// Child entities of an entity, in the order they were added.
+/// Only managed by the hooks of the Parent component,
+/// it is removed from the entity as soon as its last child leaves.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Children(Vec<EntityKey>);
+
+impl Children {
+    pub fn iter(&self) -> impl Iterator<Item = &EntityKey> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, entity: &EntityKey) -> bool {
+        self.0.contains(entity)
+    }
+}
+
+impl Component for Parent {
+    fn on_add() -> Option<for<'a> fn(world_data: &mut WorldData, entity: EntityKey)> {
+        Some(on_add_parent)
+    }
+    fn on_remove() -> Option<for<'a> fn(world_data: &mut WorldData, entity: EntityKey)> {
+        Some(on_remove_parent)
+    }
+}
+
+impl Component for Children {
+    fn on_remove() -> Option<for<'a> fn(world_data: &mut WorldData, entity: EntityKey)> {
+        Some(on_remove_children)
+    }
+}
+
+fn on_add_parent(world_data: &mut WorldData, child: EntityKey) {
+    let Some(parent) = world_data
+        .get_single_component::<Parent>(child)
+        .map(Parent::get)
+    else {
+        return;
+    };
+    match world_data.get_single_component_mut::<Children>(parent) {
+        Some(children) => {
+            if !children.contains(&child) {
+                children.0.push(child);
+            }
+        }
+        None => world_data.add_component_to_entity(parent, Children(vec![child]), false),
+    }
+}
+
+fn on_remove_parent(world_data: &mut WorldData, child: EntityKey) {
+    let Some(parent) = world_data
+        .get_single_component::<Parent>(child)
+        .map(Parent::get)
+    else {
+        return;
+    };
+    let Some(children) = world_data.get_single_component_mut::<Children>(parent) else {
+        return;
+    };
+    let len = children.len();
+    children.0.retain(|c| *c != child);
+    // only the child, which left last, removes the children component
+    if children.len() < len && children.is_empty() {
+        world_data.remove_component_from_entity::<Children>(parent);
+    }
+}
+
+fn on_remove_children(world_data: &mut WorldData, parent: EntityKey) {
+    // children are taken first, so that the remove hooks of their parent components
+    // don't modify the children component, while it is removed
+    let children = world_data
+        .get_single_component_mut::<Children>(parent)
+        .map(|children| std::mem::take(&mut children.0))
+        .unwrap_or_default();
+    for child in children {
+        world_data.remove_component_from_entity::<Parent>(child);
+    }
+}
+
+// Returns all descendants in breadth first order.
+pub(crate) fn descendants_of(
+    entity: EntityKey,
+    mut get_children: impl FnMut(EntityKey) -> Option<Vec<EntityKey>>,
+) -> Vec<EntityKey> {
+    let mut descendants = Vec::new();
+    let mut next = 0;
+    let mut cur = entity;
+    loop {
+        if let Some(children) = get_children(cur) {
+            descendants.extend(children);
+        }
+        if next == descendants.len() {
+            return descendants;
+        }
+        cur = descendants[next];
+        next += 1;
+    }
+}
+
+// Returns all ancestors, starting with the parent of the entity.
+pub(crate) fn ancestors_of(
+    entity: EntityKey,
+    mut get_parent: impl FnMut(EntityKey) -> Option<EntityKey>,
+) -> Vec<EntityKey> {
+    let mut ancestors = Vec::new();
+    let mut cur = entity;
+    while let Some(parent) = get_parent(cur) {
+        ancestors.push(parent);
+        cur = parent;
+    }
+    ancestors
+}
+
+impl<'w, 's, 'c, F: QueryFilter> Query<'w, 's, &'c Children, F> {
+    /// Returns all descendants of the entity in breadth first order.
+    pub fn descendants(&mut self, entity: EntityKey) -> Vec<EntityKey> {
+        descendants_of(entity, |e| {
+            self.get_entry(e)
+                .map(|children| children.iter().copied().collect())
+        })
+    }
+}
+
+impl<'w, 's, 'c, F: QueryFilter> Query<'w, 's, &'c Parent, F> {
+    /// Returns all ancestors of the entity, starting with its parent.
+    pub fn ancestors(&mut self, entity: EntityKey) -> Vec<EntityKey> {
+        ancestors_of(entity, |e| self.get_entry(e).map(Parent::get))
+    }
+}
+
+pub(crate) struct SetParentCommand {
+    pub(crate) child: EntityKey,
+    pub(crate) parent: EntityKey,
+}
+
+impl Command for SetParentCommand {
+    fn exec(self: Box<Self>, world_data: &mut WorldData) {
+        // the parent can be despawned by an earlier command of the same flush,
+        // a parent link to it would never be removed again
+        let entities = world_data.get_entities();
+        if entities.get(self.child).is_none() || entities.get(self.parent).is_none() {
+            println!("IGNORED: Parent or child entity of set_parent does not exist.");
+            return;
+        }
+        let ancestors = ancestors_of(self.parent, |e| {
+            world_data
+                .get_single_component::<Parent>(e)
+                .map(Parent::get)
+        });
+        if self.child == self.parent || ancestors.contains(&self.child) {
+            println!("IGNORED: An entity cannot be the parent of one of its ancestors or itself.");
+            return;
+        }
+        world_data.add_component_to_entity(self.child, Parent::new(self.parent), true);
+    }
+}
+
+pub(crate) struct DespawnRecursiveCommand {
+    pub(crate) entity_key: EntityKey,
+}
+
+impl Command for DespawnRecursiveCommand {
+    fn exec(self: Box<Self>, world_data: &mut WorldData) {
+        let descendants = descendants_of(self.entity_key, |e| {
+            world_data
+                .get_single_component::<Children>(e)
+                .map(|children| children.0.clone())
+        });
+        // deepest descendants are removed first,
+        // so that no entity gets orphaned before it is removed
+        for entity in descendants.into_iter().rev() {
+            world_data.remove_entity(entity);
+        }
+        world_data.remove_entity(self.entity_key);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ecs::{
+        entity::hierarchy::{Children, Parent},
+        prelude::*,
+        system::ResMut,
+    };
+
+    struct Player;
+    impl Component for Player {}
+
+    struct Particle(usize);
+    impl Component for Particle {}
+
+    #[derive(Default)]
+    struct Tree {
+        player: Option<EntityKey>,
+        particles: Vec<EntityKey>,
+        sparks: Vec<EntityKey>,
+        descendants: Vec<EntityKey>,
+        ancestors: Vec<EntityKey>,
+    }
+
+    fn spawn_tree(mut tree: ResMut<Tree>, mut commands: Commands) {
+        let player = commands.spawn(Player);
+        tree.player = Some(player);
+        for i in 0..3 {
+            let particle = commands.spawn(Particle(i));
+            commands.set_parent(particle, player);
+            tree.particles.push(particle);
+        }
+        let spark = commands.spawn(Particle(3));
+        commands.set_parent(spark, tree.particles[0]);
+        tree.sparks.push(spark);
+    }
+
+    fn walk_tree(
+        mut tree: ResMut<Tree>,
+        mut children_query: Query<&Children>,
+        mut parent_query: Query<&Parent>,
+    ) {
+        if let Some(player) = tree.player {
+            tree.descendants = children_query.descendants(player);
+            tree.ancestors = parent_query.ancestors(tree.sparks[0]);
+        }
+    }
+
+    fn reparent(tree: Res<Tree>, mut commands: Commands) {
+        commands.set_parent(tree.sparks[0], tree.particles[1]);
+        commands.remove_parent(tree.particles[2]);
+    }
+
+    fn despawn_player(tree: Res<Tree>, mut commands: Commands) {
+        commands.despawn_recursive(tree.player.unwrap());
+    }
+
+    fn children_of(world: &mut World, entity: EntityKey) -> Vec<EntityKey> {
+        world
+            .get_single_component::<Children>(entity)
+            .map(|children| children.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let mut world = World::new();
+        world.add_resource(Tree::default());
+        world.add_systems(spawn_tree.in_schedule(ScheduleLabel::Startup));
+        world.add_systems(walk_tree);
+        world.add_systems(reparent.in_schedule(ScheduleLabel::Custom("reparent")));
+        world.add_systems(despawn_player.in_schedule(ScheduleLabel::Custom("despawn")));
+        world.init_and_run();
+        world.run();
+
+        let tree = std::mem::take(world.get_resource_mut::<Tree>().unwrap());
+        let player = tree.player.unwrap();
+        let (particles, spark) = (tree.particles.clone(), tree.sparks[0]);
+        assert_eq!(children_of(&mut world, player), particles);
+        assert_eq!(children_of(&mut world, particles[0]), vec![spark]);
+        assert_eq!(
+            world.get_single_component::<Parent>(spark),
+            Some(&Parent::new(particles[0]))
+        );
+        let mut descendants = particles.clone();
+        descendants.push(spark);
+        assert_eq!(tree.descendants, descendants);
+        assert_eq!(tree.ancestors, vec![particles[0], player]);
+
+        world.add_resource(tree);
+        world.run_schedule(ScheduleLabel::Custom("reparent"));
+        // the last child leaving removes the children component
+        assert!(
+            world
+                .get_single_component::<Children>(particles[0])
+                .is_none()
+        );
+        assert_eq!(children_of(&mut world, particles[1]), vec![spark]);
+        assert_eq!(children_of(&mut world, player), particles[..2].to_vec());
+        assert!(world.get_single_component::<Parent>(particles[2]).is_none());
+
+        world.run_schedule(ScheduleLabel::Custom("despawn"));
+        assert!(world.get_single_component::<Player>(player).is_none());
+        for entity in [particles[0], particles[1], spark] {
+            assert!(world.get_single_component::<Particle>(entity).is_none());
+        }
+        // entities, which left the hierarchy, are not despawned
+        assert_eq!(
+            2,
+            world
+                .get_single_component::<Particle>(particles[2])
+                .unwrap()
+                .0
+        );
+    }
+
+    #[test]
+    fn test_despawned_parent_orphans_children() {
+        let mut world = World::new();
+        let parent = world.add_entity(Player);
+        let child = world.add_entity((Particle(0), Parent::new(parent)));
+        assert_eq!(children_of(&mut world, parent), vec![child]);
+
+        world.data.get_mut().remove_entity(parent);
+        assert!(world.get_single_component::<Parent>(child).is_none());
+        assert!(world.get_single_component::<Particle>(child).is_some());
+    }
+
+    fn set_parent_cycle(tree: Res<Tree>, mut commands: Commands) {
+        let player = tree.player.unwrap();
+        commands.set_parent(player, tree.sparks[0]);
+        commands.set_parent(player, player);
+        // commands after the ignored ones are still applied
+        commands.remove_parent(tree.particles[2]);
+    }
+
+    fn set_despawned_parent(tree: Res<Tree>, mut commands: Commands) {
+        commands.despawn(tree.particles[2]);
+        commands.set_parent(tree.sparks[0], tree.particles[2]);
+    }
+
+    #[test]
+    fn test_set_parent_cycle_ignored() {
+        let mut world = World::new();
+        world.add_resource(Tree::default());
+        world.add_systems(spawn_tree.in_schedule(ScheduleLabel::Startup));
+        world.add_systems(set_parent_cycle.in_schedule(ScheduleLabel::Custom("cycle")));
+        world.init_and_run();
+        world.run_schedule(ScheduleLabel::Custom("cycle"));
+
+        let tree = std::mem::take(world.get_resource_mut::<Tree>().unwrap());
+        let player = tree.player.unwrap();
+        assert!(world.get_single_component::<Parent>(player).is_none());
+        assert_eq!(children_of(&mut world, tree.particles[0]), tree.sparks);
+        assert_eq!(
+            children_of(&mut world, player),
+            tree.particles[..2].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_set_despawned_parent_ignored() {
+        let mut world = World::new();
+        world.add_resource(Tree::default());
+        world.add_systems(spawn_tree.in_schedule(ScheduleLabel::Startup));
+        world.add_systems(set_despawned_parent.in_schedule(ScheduleLabel::Custom("despawned")));
+        world.init_and_run();
+        world.run_schedule(ScheduleLabel::Custom("despawned"));
+
+        let tree = std::mem::take(world.get_resource_mut::<Tree>().unwrap());
+        let spark = tree.sparks[0];
+        // the spark keeps its previous parent
+        assert_eq!(
+            world.get_single_component::<Parent>(spark),
+            Some(&Parent::new(tree.particles[0]))
+        );
+        assert_eq!(children_of(&mut world, tree.particles[0]), vec![spark]);
+        assert!(
+            world
+                .get_single_component::<Children>(tree.particles[2])
+                .is_none()
+        );
+    }
+}
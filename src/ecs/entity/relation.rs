@@ -0,0 +1,303 @@
+// relation.rs
+
+use std::{any::TypeId, marker::PhantomData, ptr::NonNull};
+
+use crate::{
+    ecs::{
+        change_detection::Tick,
+        commands::Command,
+        component::{Component, ComponentId, StorageTypes},
+        entity::EntityKey,
+        query::{QueryParam, QueryParamMetaData, ReadOnlyQueryParam, RefKind},
+        storages::table_storage::TableStorage,
+        world::WorldData,
+    },
+    utils::tuple_iters::{TupleIterConstructor, TupleIterator},
+};
+
+/// Kind of a relation between two entities, e.g. `Likes` or `ChildOf`.
+///
+/// Every pair of a relation kind and a target entity is stored as its own archetype component,
+/// so an entity can relate to multiple targets with the same relation kind.
+/// Relations to a target are removed, when the target gets despawned.
+pub trait Relation: 'static + Sized {}
+
+// Tag component contained by every archetype with at least one pair of the relation kind,
+// used to find all archetypes matching a wildcard relation query.
+pub(crate) struct RelationKind<R: Relation>(PhantomData<R>);
+
+impl<R: Relation> Component for RelationKind<R> {}
+
+/// Query param matching all entities with at least one relation of kind R,
+/// the wildcard relation `(R, *)`.
+/// Yields the targets of all relations of kind R of the entity.
+pub struct Rel<R: Relation>(PhantomData<R>);
+
+impl<R: Relation> ReadOnlyQueryParam for Rel<R> {}
+
+impl<R: Relation> QueryParam for Rel<R> {
+    type QueryItem<'new> = Rel<R>;
+
+    fn type_ids_rec(vec: &mut Vec<TypeId>) {
+        vec.push(TypeId::of::<RelationKind<R>>());
+    }
+    fn comp_ids_rec(world_data: &mut WorldData, vec: &mut Vec<ComponentId>) {
+        vec.push(world_data.create_or_get_component::<RelationKind<R>>());
+    }
+    fn ref_kinds(vec: &mut Vec<RefKind>) {
+        vec.push(RefKind::Shared);
+    }
+    fn optional_param_rec(vec: &mut Vec<bool>) {
+        vec.push(false);
+    }
+    fn meta_data(world_data: &mut WorldData, vec: &mut Vec<QueryParamMetaData>) {
+        let comp_id = world_data.create_or_get_component::<RelationKind<R>>();
+        vec.push(QueryParamMetaData {
+            type_id: TypeId::of::<RelationKind<R>>(),
+            comp_id,
+            ref_kind: RefKind::Shared,
+            optional: false,
+            storage: StorageTypes::TableSoA,
+        });
+    }
+}
+
+impl<R: Relation> TupleIterConstructor<TableStorage> for Rel<R> {
+    type Construct<'c> = RelationTargetsIterUnsafe<'c>;
+
+    unsafe fn construct<'s>(source: NonNull<TableStorage>, _this_run: Tick) -> Self::Construct<'s> {
+        let table = unsafe { source.as_ref() };
+        let targets = table
+            .relation_targets
+            .get(&TypeId::of::<RelationKind<R>>())
+            .map(|targets| targets.as_slice())
+            .unwrap_or_default();
+        RelationTargetsIterUnsafe { targets }
+    }
+}
+
+// All entities of a table share the same relation targets.
+pub struct RelationTargetsIterUnsafe<'c> {
+    targets: &'c [EntityKey],
+}
+
+impl<'c> TupleIterator for RelationTargetsIterUnsafe<'c> {
+    type Item = &'c [EntityKey];
+    unsafe fn next(&mut self, _index: usize) -> Self::Item {
+        self.targets
+    }
+}
+
+pub(crate) struct AddRelationCommand<R: Relation> {
+    pub(crate) entity_key: EntityKey,
+    pub(crate) target: EntityKey,
+    pub(crate) _relation_marker: PhantomData<R>,
+}
+
+impl<R: Relation> Command for AddRelationCommand<R> {
+    fn exec(self: Box<Self>, world_data: &mut WorldData) {
+        world_data.add_relation::<R>(self.entity_key, self.target);
+    }
+}
+
+pub(crate) struct RemoveRelationCommand<R: Relation> {
+    pub(crate) entity_key: EntityKey,
+    pub(crate) target: EntityKey,
+    pub(crate) _relation_marker: PhantomData<R>,
+}
+
+impl<R: Relation> Command for RemoveRelationCommand<R> {
+    fn exec(self: Box<Self>, world_data: &mut WorldData) {
+        world_data.remove_relation::<R>(self.entity_key, self.target);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::ecs::{
+        entity::relation::{Rel, Relation},
+        prelude::*,
+        system::ResMut,
+    };
+
+    struct Likes;
+    impl Relation for Likes {}
+
+    struct Owns;
+    impl Relation for Owns {}
+
+    struct Name(&'static str);
+    impl Component for Name {}
+
+    struct Item;
+    impl Component for Item {}
+
+    #[derive(Default)]
+    struct LikesLog(HashMap<EntityKey, Vec<EntityKey>>);
+
+    struct Actors {
+        alice: EntityKey,
+        bob: EntityKey,
+        carol: EntityKey,
+    }
+
+    fn collect_likes(mut log: ResMut<LikesLog>, mut query: Query<(EntityKey, Rel<Likes>)>) {
+        log.0 = query
+            .iter()
+            .map(|(entity, targets)| {
+                let mut targets = targets.to_vec();
+                targets.sort_by_key(|target| target.get_id());
+                (entity, targets)
+            })
+            .collect();
+    }
+
+    fn change_likes(actors: Res<Actors>, mut commands: Commands) {
+        commands.remove_relation::<Likes>(actors.alice, actors.bob);
+        commands.add_relation::<Likes>(actors.carol, actors.bob);
+    }
+
+    #[test]
+    fn test_relation_query() {
+        let mut world = World::new();
+        let alice = world.add_entity(Name("alice"));
+        let bob = world.add_entity(Name("bob"));
+        let carol = world.add_entity(Name("carol"));
+        world.add_relation::<Likes>(alice, bob);
+        world.add_relation::<Likes>(alice, carol);
+        world.add_relation::<Likes>(bob, alice);
+        world.add_resource(Actors { alice, bob, carol });
+        world.add_resource(LikesLog::default());
+        world.add_systems(collect_likes);
+        world.add_systems(change_likes.in_schedule(ScheduleLabel::Custom("change")));
+        world.init_and_run();
+
+        let log = &world.get_resource::<LikesLog>().unwrap().0;
+        assert_eq!(2, log.len());
+        assert_eq!(Some(&vec![bob, carol]), log.get(&alice));
+        assert_eq!(Some(&vec![alice]), log.get(&bob));
+
+        // new archetypes created by commands are found by the already initialized query
+        world.run_schedule(ScheduleLabel::Custom("change"));
+        world.run();
+        let log = &world.get_resource::<LikesLog>().unwrap().0;
+        assert_eq!(3, log.len());
+        assert_eq!(Some(&vec![carol]), log.get(&alice));
+        assert_eq!(Some(&vec![bob]), log.get(&carol));
+        // table components are kept, when relations change
+        assert_eq!(
+            "alice",
+            world.get_single_component::<Name>(alice).unwrap().0
+        );
+    }
+
+    #[test]
+    fn test_relation_removal() {
+        let mut world = World::new();
+        let player = world.add_entity(Name("player"));
+        let sword = world.add_entity(Item);
+        let shield = world.add_entity(Item);
+        world.add_relation::<Owns>(player, sword);
+        world.add_relation::<Owns>(player, shield);
+        world.add_relation::<Likes>(player, sword);
+        assert_eq!(vec![sword, shield], {
+            let mut owned = world.get_relation_targets::<Owns>(player);
+            owned.sort_by_key(|target| target.get_id());
+            owned
+        });
+        assert_eq!(vec![sword], world.get_relation_targets::<Likes>(player));
+
+        world.remove_relation::<Owns>(player, sword);
+        world.remove_relation::<Owns>(player, shield);
+        assert!(world.get_relation_targets::<Owns>(player).is_empty());
+        assert!(world.get_entity_components::<Rel<Owns>>(player).is_none());
+        assert_eq!(
+            Some(&[sword][..]),
+            world.get_entity_components::<Rel<Likes>>(player)
+        );
+
+        // entities without any components left are removed
+        let fan = world.add_entity(Name("fan"));
+        world.add_relation::<Likes>(fan, player);
+        world
+            .data
+            .get_mut()
+            .remove_component_from_entity::<Name>(fan);
+        assert_eq!(vec![player], world.get_relation_targets::<Likes>(fan));
+        world.remove_relation::<Likes>(fan, player);
+        assert!(world.get_single_component::<Name>(fan).is_none());
+        assert!(world.data.get_mut().get_entities().get(fan).is_none());
+    }
+
+    #[test]
+    fn test_relation_target_despawn() {
+        let mut world = World::new();
+        let alice = world.add_entity(Name("alice"));
+        let bob = world.add_entity(Name("bob"));
+        let carol = world.add_entity(Name("carol"));
+        world.add_relation::<Likes>(alice, bob);
+        world.add_relation::<Likes>(alice, carol);
+        world.add_relation::<Owns>(carol, bob);
+        assert_eq!(
+            3,
+            world
+                .data
+                .get_mut()
+                .get_entity_storage_mut()
+                .relation_pair_compid_map
+                .len()
+        );
+
+        // pairs pointing to the despawned target are removed from all entities
+        world.data.get_mut().remove_entity(bob);
+        assert_eq!(vec![carol], world.get_relation_targets::<Likes>(alice));
+        assert!(world.get_relation_targets::<Owns>(carol).is_empty());
+        assert_eq!(
+            "carol",
+            world.get_single_component::<Name>(carol).unwrap().0
+        );
+        let entity_storage = world.data.get_mut().get_entity_storage_mut();
+        assert_eq!(1, entity_storage.relation_pair_compid_map.len());
+        // only carol is still a relation target
+        assert_eq!(
+            vec![carol],
+            entity_storage
+                .relation_target_pairs
+                .keys()
+                .copied()
+                .collect::<Vec<_>>()
+        );
+        let comp_count = entity_storage.components.len();
+        let arch_count = entity_storage.archetypes.len();
+
+        // the pair ids and archetypes of the despawned target are reused for new targets
+        let dave = world.add_entity(Name("dave"));
+        world.add_relation::<Likes>(alice, dave);
+        world.add_relation::<Owns>(carol, dave);
+        let entity_storage = world.data.get_mut().get_entity_storage_mut();
+        assert_eq!(comp_count, entity_storage.components.len());
+        assert_eq!(arch_count, entity_storage.archetypes.len());
+        assert_eq!(vec![dave, carol], {
+            let mut liked = world.get_relation_targets::<Likes>(alice);
+            liked.sort_by_key(|target| target.get_id());
+            liked
+        });
+        assert_eq!(vec![dave], world.get_relation_targets::<Owns>(carol));
+
+        // relations to despawned targets can't be added
+        world.data.get_mut().remove_entity(dave);
+        world.add_relation::<Likes>(alice, dave);
+        assert_eq!(vec![carol], world.get_relation_targets::<Likes>(alice));
+        assert_eq!(
+            1,
+            world
+                .data
+                .get_mut()
+                .get_entity_storage_mut()
+                .relation_pair_compid_map
+                .len()
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// error.rs
+
+use crate::ecs::{scheduler::SystemParamConflict, system::SystemId};
+
+/// Errors, which can be detected during the initialization of the systems of a world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EcsError {
+    /// The before and after constraints of the systems contain loops.
+    /// Every path starts and ends with the same system.
+    SystemConstraintLoop {
+        system_id_paths: Vec<Vec<SystemId>>,
+        system_name_paths: Vec<Vec<String>>,
+    },
+    /// The params of a single system conflict with each other.
+    SystemParamConflict(SystemParamConflict),
+    /// A system requests a resource, which was not added to the world.
+    MissingResource {
+        system_name: String,
+        resource_name: &'static str,
+    },
+}
+
+impl std::fmt::Display for EcsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EcsError::SystemConstraintLoop {
+                system_name_paths, ..
+            } => {
+                write!(f, "System scheduling loop detected!")?;
+                for path in system_name_paths {
+                    write!(f, "\n{}", path.join(" -> "))?;
+                }
+                Ok(())
+            }
+            EcsError::SystemParamConflict(conflict) => write!(f, "{conflict}"),
+            EcsError::MissingResource {
+                system_name,
+                resource_name,
+            } => write!(
+                f,
+                "Requested resource does not exist!\nsystem: {system_name}\nresource: {resource_name}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EcsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EcsError::SystemParamConflict(conflict) => Some(conflict),
+            _ => None,
+        }
+    }
+}
+
+impl From<SystemParamConflict> for EcsError {
+    fn from(conflict: SystemParamConflict) -> Self {
+        EcsError::SystemParamConflict(conflict)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ecs::{
+        error::EcsError,
+        prelude::*,
+        scheduler::SystemParamConflictKind,
+        system::{ResMut, SystemId},
+    };
+
+    struct Score(u32);
+    struct Settings(u32);
+
+    fn score_system(mut score: ResMut<Score>, settings: Option<Res<Settings>>) {
+        score.0 += settings.map_or(1, |s| s.0);
+    }
+
+    fn conflicting_system(_score: Res<Score>, _score_mut: ResMut<Score>) {}
+
+    fn first_system() {}
+    fn second_system() {}
+
+    #[test]
+    fn test_missing_resource_error() {
+        let mut world = World::new();
+        world.add_systems(score_system);
+        match world.try_init_systems() {
+            Err(EcsError::MissingResource { resource_name, .. }) => {
+                assert_eq!(std::any::type_name::<Score>(), resource_name)
+            }
+            other => panic!("expected missing resource error, got {other:?}"),
+        }
+
+        // optional resources are not required
+        let mut world = World::new();
+        world.add_resource(Score(0));
+        world.add_systems(score_system);
+        assert_eq!(Ok(()), world.try_init_systems());
+    }
+
+    #[test]
+    fn test_system_param_conflict_error() {
+        let mut world = World::new();
+        world.add_resource(Score(0));
+        world.add_systems(conflicting_system);
+        match world.try_init_systems() {
+            Err(EcsError::SystemParamConflict(conflict)) => {
+                assert_eq!(SystemParamConflictKind::Resource, conflict.kind)
+            }
+            other => panic!("expected param conflict error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_system_constraint_loop_error() {
+        let mut world = World::new();
+        world.add_systems((first_system, second_system).chain());
+        world.add_systems(second_system.before(first_system));
+        let err = world.try_init_systems().unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("System scheduling loop detected!")
+        );
+        let EcsError::SystemConstraintLoop {
+            system_id_paths, ..
+        } = err
+        else {
+            panic!("expected system constraint loop error, got {err:?}");
+        };
+        let first: SystemId = 0usize.into();
+        let second: SystemId = 1usize.into();
+        assert!(system_id_paths.contains(&vec![first, second, first]));
+    }
+}
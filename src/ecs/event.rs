@@ -0,0 +1,339 @@
+// event.rs
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{
+    ecs::{
+        change_detection::SystemTicks,
+        ecs_dependency_graph::EcsEdge,
+        resource::ResourceId,
+        system::{RefType, SystemId, SystemParam, SystemParamId},
+        world::WorldData,
+    },
+    utils::ecs_id::{EcsId, impl_ecs_id},
+};
+
+/// Types, which can be sent as events.
+/// Writers and readers of the events can run on any thread of the world.
+pub trait Event: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Event for T {}
+
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Copy, Hash, Debug)]
+pub struct EventReaderId(u32);
+impl_ecs_id!(EventReaderId);
+
+/// Double buffered storage for events of type E.
+/// Events stay readable during the run in which they were sent and the following run,
+/// so every reader sees them once, independent of the execution order of the systems.
+/// The buffers are swapped once per run of the world.
+pub struct Events<E> {
+    // events sent during the previous run
+    previous: Vec<E>,
+    // events sent during the current run
+    current: Vec<E>,
+    // id of the first event in the previous buffer
+    previous_start: usize,
+    // every event reader has its own cursor, which contains the id of the next unread event.
+    // The cursors are atomic, because readers only get shared access to the events.
+    reader_cursors: Vec<AtomicUsize>,
+}
+
+impl<E> Events<E> {
+    pub fn new() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            previous_start: 0,
+            reader_cursors: Vec::new(),
+        }
+    }
+
+    pub fn send(&mut self, event: E) {
+        self.current.push(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        self.current.extend(events);
+    }
+
+    /// Drops the events of the previous run and
+    /// moves the events of the current run into the previous buffer.
+    pub fn update(&mut self) {
+        self.previous_start = self.current_start();
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    /// Drops all stored events. Unread events are lost for every reader.
+    pub fn clear(&mut self) {
+        self.previous_start = self.event_count();
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    /// Number of all stored events of the previous and the current run.
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn register_reader(&mut self) -> EventReaderId {
+        let reader_id: EventReaderId = self.reader_cursors.len().into();
+        self.reader_cursors
+            .push(AtomicUsize::new(self.previous_start));
+        reader_id
+    }
+
+    fn current_start(&self) -> usize {
+        self.previous_start + self.previous.len()
+    }
+
+    // total count of events sent since the creation, which is also the id of the next event
+    fn event_count(&self) -> usize {
+        self.current_start() + self.current.len()
+    }
+
+    // Events that were already dropped are skipped.
+    fn unread_start(&self, reader_id: EventReaderId) -> usize {
+        self.reader_cursors[reader_id.id_usize()]
+            .load(Ordering::Relaxed)
+            .max(self.previous_start)
+    }
+
+    fn unread_len(&self, reader_id: EventReaderId) -> usize {
+        self.event_count() - self.unread_start(reader_id)
+    }
+
+    fn read(&self, reader_id: EventReaderId) -> impl Iterator<Item = &E> {
+        let start = self.unread_start(reader_id);
+        self.reader_cursors[reader_id.id_usize()].store(self.event_count(), Ordering::Relaxed);
+        let previous_skip = (start - self.previous_start).min(self.previous.len());
+        let current_skip = start.saturating_sub(self.current_start());
+        self.previous[previous_skip..]
+            .iter()
+            .chain(self.current[current_skip..].iter())
+    }
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// System param to send events of type E.
+/// Systems with a writer get exclusive access to the events.
+pub struct EventWriter<'w, E> {
+    events: &'w mut Events<E>,
+}
+
+impl<'w, E> EventWriter<'w, E> {
+    pub fn send(&mut self, event: E) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        self.events.send_batch(events);
+    }
+}
+
+/// System param to read events of type E.
+/// Every reader keeps track of the events it has already read,
+/// so each event is only returned once per reader.
+pub struct EventReader<'w, E> {
+    events: &'w Events<E>,
+    reader_id: EventReaderId,
+}
+
+impl<'w, E> EventReader<'w, E> {
+    /// Returns all events, which were not read by this reader yet
+    /// and marks them as read.
+    pub fn read(&mut self) -> impl Iterator<Item = &E> {
+        self.events.read(self.reader_id)
+    }
+
+    /// Number of unread events.
+    pub fn len(&self) -> usize {
+        self.events.unread_len(self.reader_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Marks all events as read.
+    pub fn clear(&mut self) {
+        let _ = self.events.read(self.reader_id);
+    }
+}
+
+impl<'w, E: Event> SystemParam for EventWriter<'w, E> {
+    type Item<'new> = EventWriter<'new, E>;
+
+    unsafe fn retrieve<'r>(
+        system_param_index: &mut usize,
+        _system_param_ids: &[SystemParamId],
+        world_data: *mut WorldData,
+        _system_ticks: SystemTicks,
+    ) -> Self::Item<'r> {
+        *system_param_index += 1;
+        unsafe {
+            EventWriter {
+                events: (*world_data)
+                    .resources
+                    .get_mut()
+                    .expect("Requested events do not exist!"),
+            }
+        }
+    }
+
+    fn create_system_param_data(
+        system_id: SystemId,
+        system_param_ids: &mut Vec<SystemParamId>,
+        world_data: &mut WorldData,
+    ) {
+        let resource_id = world_data.add_event::<E>();
+        world_data.get_depend_graph_mut().insert_system_resource(
+            system_id,
+            resource_id,
+            EcsEdge::Excl,
+        );
+        system_param_ids.push(SystemParamId::Resource(resource_id, RefType::Exclusive));
+    }
+}
+
+impl<'w, E: Event> SystemParam for EventReader<'w, E> {
+    type Item<'new> = EventReader<'new, E>;
+
+    unsafe fn retrieve<'r>(
+        system_param_index: &mut usize,
+        system_param_ids: &[SystemParamId],
+        world_data: *mut WorldData,
+        _system_ticks: SystemTicks,
+    ) -> Self::Item<'r> {
+        let sys_prm_id = &system_param_ids[*system_param_index];
+        *system_param_index += 1;
+        if let SystemParamId::EventReader(_, reader_id) = sys_prm_id {
+            unsafe {
+                return EventReader {
+                    events: (*world_data)
+                        .resources
+                        .get()
+                        .expect("Requested events do not exist!"),
+                    reader_id: *reader_id,
+                };
+            }
+        }
+        panic!("System param id should be an event reader id.");
+    }
+
+    fn create_system_param_data(
+        system_id: SystemId,
+        system_param_ids: &mut Vec<SystemParamId>,
+        world_data: &mut WorldData,
+    ) {
+        let resource_id = world_data.add_event::<E>();
+        let reader_id = world_data
+            .resources
+            .get_mut::<Events<E>>()
+            .expect("Events should have been added to the world.")
+            .register_reader();
+        world_data.get_depend_graph_mut().insert_system_resource(
+            system_id,
+            resource_id,
+            EcsEdge::Shared,
+        );
+        system_param_ids.push(SystemParamId::EventReader(resource_id, reader_id));
+    }
+}
+
+pub(crate) fn update_events<E: Event>(world_data: &mut WorldData) {
+    if let Some(events) = world_data.resources.get_mut::<Events<E>>() {
+        events.update();
+    }
+}
+
+pub(crate) fn events_resource_id<E: Event>() -> ResourceId {
+    ResourceId::of::<Events<E>>()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ecs::{
+        event::{EventReader, EventWriter, Events},
+        prelude::*,
+        system::ResMut,
+    };
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Damage(u32);
+
+    #[derive(Default)]
+    struct Received {
+        frame: u32,
+        before_writer: Vec<Vec<u32>>,
+        after_writer: Vec<Vec<u32>>,
+    }
+
+    fn reader_before_writer(mut received: ResMut<Received>, mut reader: EventReader<Damage>) {
+        let damages = reader.read().map(|d| d.0).collect();
+        received.before_writer.push(damages);
+    }
+
+    fn writer(received: ResMut<Received>, mut writer: EventWriter<Damage>) {
+        // only send events during the first two frames
+        if received.frame < 2 {
+            writer.send(Damage(received.frame * 10));
+            writer.send_batch([Damage(received.frame * 10 + 1)]);
+        }
+    }
+
+    fn reader_after_writer(mut received: ResMut<Received>, mut reader: EventReader<Damage>) {
+        let damages = reader.read().map(|d| d.0).collect();
+        assert!(reader.is_empty());
+        received.after_writer.push(damages);
+        received.frame += 1;
+    }
+
+    #[test]
+    fn test_event_readers_and_writers() {
+        let mut world = World::new();
+        world.add_resource(Received::default());
+        world.add_systems((reader_before_writer, writer, reader_after_writer).chain());
+        world.init_and_run();
+        world.run();
+        world.run();
+        world.run();
+
+        let received = world.get_resource::<Received>().unwrap();
+        // readers running before the writer receive the events one run later
+        assert_eq!(
+            vec![vec![], vec![0, 1], vec![10, 11], vec![]],
+            received.before_writer
+        );
+        assert_eq!(
+            vec![vec![0, 1], vec![10, 11], vec![], vec![]],
+            received.after_writer
+        );
+    }
+
+    #[test]
+    fn test_events_are_dropped_after_two_updates() {
+        let mut events = Events::new();
+        events.send(Damage(1));
+        let reader_id = events.register_reader();
+        events.update();
+        events.send(Damage(2));
+        assert_eq!(2, events.len());
+        events.update();
+        // first event was dropped before it was read
+        assert_eq!(vec![&Damage(2)], events.read(reader_id).collect::<Vec<_>>());
+        assert_eq!(0, events.unread_len(reader_id));
+        events.update();
+        assert!(events.is_empty());
+    }
+}
@@ -1,14 +1,31 @@
 // prelude.rs
 
 pub use crate::ecs::{
+    change_detection::Mut,
     commands::Commands,
     component::{Component, StorageTypes},
-    entity::EntityKey,
+    dump::SceneError,
+    entity::{
+        EntityKey,
+        hierarchy::{Children, Parent},
+        relation::{Rel, Relation},
+    },
+    error::EcsError,
+    event::{Event, EventReader, EventWriter, Events},
     query::{
-        Query,
-        query_filter::{Or, With, Without},
+        Query, QueryState, TypedQueryState,
+        dynamic_query::{DynamicQuery, DynamicQueryBuilder, QueryBuilder},
+        query_filter::{Added, Changed, Or, With, Without},
     },
+    reflect::{FieldValue, Reflect},
+    resource::FromWorld,
+    schedule::ScheduleLabel,
+    snapshot::{SerializableComponent, SerializableResource},
     storages::entity_storage::EntityStorage,
-    system::{Res, ResMut, builder::IntoSystemConfig},
-    world::World,
+    system::{
+        Local, NonSend, NonSendMut, Res, ResMut, ResOwned,
+        builder::{IntoSystemConfig, IntoSystemInstance},
+        pipe::{In, IntoPipeSystem},
+    },
+    world::{World, WorldData},
 };
@@ -5,16 +5,19 @@ use std::{
     collections::{HashSet, hash_set},
     hash::Hash,
     marker::PhantomData,
+    ops::Range,
 };
 
 use crate::{
     all_tuples,
     ecs::{
+        change_detection::{SystemTicks, Tick},
         ecs_dependency_graph::QueryId,
         entity::EntityKey,
         query::query_filter::{FilterElem, QueryFilter},
+        storages::entity_storage::EntityStorage,
         storages::table_storage::TableStorageTupleIter,
-        system::{SystemId, SystemParamId},
+        system::{ReadOnlySystemParam, SystemId, SystemParamId},
     },
     utils::{
         ecs_id::EcsId,
@@ -24,12 +27,13 @@ use crate::{
 };
 
 use super::{
-    component::{ArchetypeId, Component, ComponentId},
+    component::{ArchetypeId, Component, ComponentId, StorageTypes},
     storages::table_storage::TableStorage,
     system::SystemParam,
-    world::WorldData,
+    world::{World, WorldData},
 };
 
+pub mod dynamic_query;
 pub mod query_filter;
 
 type QueryDataType = TableStorage;
@@ -37,6 +41,7 @@ type QueryDataType = TableStorage;
 pub struct Query<'w, 's, P: QueryParam, F: QueryFilter = ()> {
     world: *mut WorldData,
     state: &'s QueryState,
+    system_ticks: SystemTicks,
     _param_marker: PhantomData<fn() -> P>,
     _filter_marker: PhantomData<fn() -> F>,
     _world_lt_marker: PhantomData<&'w WorldData>,
@@ -45,17 +50,190 @@ pub struct Query<'w, 's, P: QueryParam, F: QueryFilter = ()> {
 unsafe impl<'w, 's, P: QueryParam, F: QueryFilter> Send for Query<'w, 's, P, F> {}
 unsafe impl<'w, 's, P: QueryParam, F: QueryFilter> Sync for Query<'w, 's, P, F> {}
 
+/// Queried components, filter and matched archetypes of a query.
 #[derive(Debug)]
-pub(crate) struct QueryState {
+pub struct QueryState {
     pub(crate) query_param_meta_data: SortedVec<QueryParamMetaData>,
+    // queried component ids in the order of the query params
+    pub(crate) param_comp_ids: Vec<ComponentId>,
     pub(crate) arch_ids: HashSet<ArchetypeId>,
+    // number of archetypes, which were already checked for matches
+    pub(crate) archetypes_seen: usize,
     pub(crate) filter: Vec<FilterElem>,
+    // filter references sparse set components, which are not part of archetypes,
+    // so the filter needs to be checked for every entity during iteration
+    pub(crate) sparse_filter: bool,
+    // filter contains Added or Changed filters,
+    // which also need to be checked for every entity during iteration
+    pub(crate) tick_filter: bool,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
-pub(crate) struct QueryStateKey {
-    comp_ids: SortedVec<ComponentId>,
-    filter: Vec<FilterElem>,
+impl QueryState {
+    // Matches all existing archetypes against the queried components and the filter.
+    pub(crate) fn new(
+        world_data: &WorldData,
+        query_param_meta_data: SortedVec<QueryParamMetaData>,
+        param_comp_ids: Vec<ComponentId>,
+        filter: Vec<FilterElem>,
+    ) -> Self {
+        let mut filter_comp_ids = Vec::new();
+        query_filter::filter_comp_ids(&filter, &mut filter_comp_ids);
+        let sparse_filter = filter_comp_ids.iter().any(|cid| {
+            world_data.get_components()[cid.id_usize()].storage == StorageTypes::SparseSet
+        });
+        let tick_filter = query_filter::contains_tick_filter(&filter);
+
+        // remove archetypes that do not match the filter,
+        // filters with sparse set components are checked during iteration
+        let arch_ids = world_data
+            .find_fitting_archetypes(&query_param_meta_data)
+            .into_iter()
+            .filter(|aid| {
+                if sparse_filter {
+                    return true;
+                }
+                let arch = &world_data.get_archetypes()[aid.id_usize()];
+                let comp_ids_set: HashSet<ComponentId> = arch.all_comp_ids().copied().collect();
+                query_filter::comp_ids_compatible_with_filter(&comp_ids_set, &filter)
+            })
+            .collect();
+
+        Self {
+            query_param_meta_data,
+            param_comp_ids,
+            arch_ids,
+            archetypes_seen: world_data.get_archetypes().len(),
+            filter,
+            sparse_filter,
+            tick_filter,
+        }
+    }
+
+    pub(crate) fn from_params<P: QueryParam, F: QueryFilter>(world_data: &mut WorldData) -> Self {
+        let mut query_prm_meta_data = world_data
+            .get_cache_mut()
+            .query_param_meta_data_vec_cache
+            .take_cached();
+        P::meta_data(world_data, &mut query_prm_meta_data);
+        let param_comp_ids = query_prm_meta_data
+            .iter()
+            .map(|qpmd| qpmd.comp_id)
+            .collect();
+        let query_prm_meta_data: SortedVec<QueryParamMetaData> = query_prm_meta_data.into();
+
+        let mut filter = Vec::new();
+        F::get_and_filters(world_data, &mut filter);
+
+        QueryState::new(world_data, query_prm_meta_data, param_comp_ids, filter)
+    }
+
+    // Adds the archetypes created since the last update, which match the query.
+    pub(crate) fn update_archetypes(&mut self, world_data: &WorldData) {
+        let archetypes = world_data.get_archetypes();
+        for archetype in archetypes[self.archetypes_seen..].iter() {
+            let arch_comp_ids: SortedVec<ComponentId> =
+                archetype.all_comp_ids().copied().collect::<Vec<_>>().into();
+            if self.archetype_matches(&arch_comp_ids) {
+                self.arch_ids.insert(archetype.archetype_id);
+            }
+        }
+        self.archetypes_seen = archetypes.len();
+    }
+
+    // Components accessed multiple times by the query params, at least once exclusively.
+    pub(crate) fn conflicting_comp_ids(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.query_param_meta_data
+            .get_vec()
+            .windows(2)
+            .filter(|pair| {
+                pair[0].comp_id == pair[1].comp_id
+                    && (pair[0].ref_kind == RefKind::Exclusive
+                        || pair[1].ref_kind == RefKind::Exclusive)
+            })
+            .map(|pair| pair[0].comp_id)
+    }
+
+    // Components, which every entity matched by the query owns.
+    fn required_comp_ids(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.query_param_meta_data
+            .iter()
+            .filter(|qpmd| !qpmd.optional)
+            .map(|qpmd| qpmd.comp_id)
+            .chain(query_filter::required_comp_ids(&self.filter))
+    }
+
+    // Two queries can never match the same entity,
+    // if one of them requires a component, which the other one excludes.
+    pub(crate) fn is_disjoint_with(&self, other: &QueryState) -> bool {
+        fn excludes_required(q1: &QueryState, q2: &QueryState) -> bool {
+            let excluded: HashSet<ComponentId> =
+                query_filter::excluded_comp_ids(&q2.filter).collect();
+            q1.required_comp_ids().any(|cid| excluded.contains(&cid))
+        }
+        excludes_required(self, other) || excludes_required(other, self)
+    }
+
+    pub(crate) fn archetype_matches(&self, arch_comp_ids: &SortedVec<ComponentId>) -> bool {
+        EntityStorage::is_subset_of(&self.query_param_meta_data, arch_comp_ids)
+            && (self.sparse_filter
+                || query_filter::filter_matches(
+                    &|cid| arch_comp_ids.get_vec().binary_search(cid).is_ok(),
+                    &self.filter,
+                ))
+    }
+}
+
+/// State of a typed query, which is used outside of systems, created by `World::query`.
+/// Archetypes created since the last iteration are matched before iterating again.
+/// Added and Changed filters match changes since the previous iteration of this state.
+pub struct TypedQueryState<P: QueryParam, F: QueryFilter = ()> {
+    state: QueryState,
+    last_run: Tick,
+    _marker: PhantomData<fn() -> (P, F)>,
+}
+
+impl<P: QueryParam, F: QueryFilter> TypedQueryState<P, F> {
+    // Panics, if the params access a component multiple times and at least once mutably.
+    pub(crate) fn new(world_data: &mut WorldData) -> Self {
+        let state = QueryState::from_params::<P, F>(world_data);
+        let conflicting_types: Vec<&str> = state
+            .conflicting_comp_ids()
+            .map(|comp_id| &*world_data.get_components()[comp_id.id_usize()].name)
+            .collect();
+        assert!(
+            conflicting_types.is_empty(),
+            "Query accesses the components {:?} mutably and additionally in another param.",
+            conflicting_types
+        );
+        Self {
+            state,
+            last_run: Tick::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a query over the world, which can be used like a query system param.
+    pub fn query<'w>(&'w mut self, world: &'w mut World) -> Query<'w, 'w, P, F> {
+        let world_data = world.data.get_mut();
+        self.state.update_archetypes(world_data);
+        let this_run = world_data.increment_change_tick();
+        let system_ticks = SystemTicks {
+            last_run: self.last_run,
+            this_run,
+        };
+        self.last_run = this_run;
+        Query::new(world_data, &self.state, system_ticks)
+    }
+
+    /// Iterates the entities matching the query.
+    pub fn iter<'w>(&'w mut self, world: &'w mut World) -> QueryIter<'w, 'w, P, F> {
+        self.query(world).into_iter()
+    }
+
+    /// Returns the untyped state of the query.
+    pub fn state(&self) -> &QueryState {
+        &self.state
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -68,10 +246,12 @@ impl<'w, 's, P: QueryParam, F: QueryFilter> Query<'w, 's, P, F> {
     pub(crate) fn new(
         world: /*TODO: should be: &'w*/ *mut WorldData,
         state: &'s QueryState,
+        system_ticks: SystemTicks,
     ) -> Self {
         Self {
             world,
             state,
+            system_ticks,
             _param_marker: Default::default(),
             _filter_marker: Default::default(),
             _world_lt_marker: Default::default(),
@@ -79,8 +259,8 @@ impl<'w, 's, P: QueryParam, F: QueryFilter> Query<'w, 's, P, F> {
     }
 
     ///TODO: add documentation and examples
-    pub fn iter(&mut self) -> QueryIter<'_, '_, P, F> {
-        QueryIter::new(self)
+    pub fn iter(&mut self) -> QueryIter<'_, 's, P, F> {
+        QueryIter::new(Query::new(self.world, self.state, self.system_ticks))
     }
 
     pub fn get_entry(
@@ -90,6 +270,70 @@ impl<'w, 's, P: QueryParam, F: QueryFilter> Query<'w, 's, P, F> {
         unsafe { (&mut *self.world).get_entity_components::<P>(entity_key) }
     }
 
+    /// Calls f for every item of the query in parallel.
+    /// The rows of every matched table are split into chunks of at most batch_size rows,
+    /// which are distributed onto the thread pool of the world.
+    /// Parallel iterations nested inside of f run on the calling thread.
+    /// A panic of f is resumed on the caller, after all chunks finished.
+    pub fn par_for_each<'a>(
+        &'a mut self,
+        batch_size: usize,
+        f: impl Fn(<<P as TupleIterConstructor<QueryDataType>>::Construct<'a> as TupleIterator>::Item)
+        + Send
+        + Sync,
+    ) {
+        assert!(batch_size > 0, "Batch size needs to be greater than zero.");
+        let query: &'a Query<'a, 's, P, F> = self;
+        let mut chunks = Vec::new();
+        for arch_id in query.state.arch_ids.iter() {
+            let len = unsafe { (&*query.world).get_tables()[arch_id].entities.len() };
+            for start in (0..len).step_by(batch_size) {
+                chunks.push(QueryChunk {
+                    arch_id: *arch_id,
+                    iter: unsafe { query.get_arch_chunk_iter(*arch_id, start..start + batch_size) },
+                });
+            }
+        }
+        let f = &f;
+        let world_data = unsafe { &*query.world };
+        world_data.query_thread_pool.scope(|s| {
+            for chunk in chunks {
+                s.spawn(move || chunk.for_each(query, f));
+            }
+        });
+    }
+
+    // Checks the query filter against the archetype, sparse set components
+    // and change ticks of the entity in the row of the table.
+    fn row_matches_filter(&self, arch_id: ArchetypeId, row_index: usize) -> bool {
+        let world_data = unsafe { &*self.world };
+        let table = &world_data.get_tables()[&arch_id];
+        let entity_key = table.entities[row_index];
+        let archetype = &world_data.get_archetypes()[arch_id.id_usize()];
+        let sparse_sets = world_data.get_sparse_sets();
+        let components = world_data.get_components();
+        query_filter::entity_matches_filter(
+            &|cid| archetype.contains_comp(cid) || sparse_sets.contains(cid, entity_key),
+            &|cid| table.get_change_ticks(&components[cid.id_usize()], row_index),
+            self.system_ticks,
+            &self.state.filter,
+        )
+    }
+
+    unsafe fn get_arch_chunk_iter(
+        &self,
+        arch_id: ArchetypeId,
+        rows: Range<usize>,
+    ) -> TableStorageTupleIter<<P as TupleIterConstructor<QueryDataType>>::Construct<'w>> {
+        unsafe {
+            (&mut *self.world)
+                .get_tables_mut()
+                .get_mut(&arch_id)
+                .expect("Table with archetype id could not be found.")
+                .tuple_iter_rows::<P>(rows, self.system_ticks.this_run)
+        }
+    }
+
     #[inline(never)]
     #[cold]
     unsafe fn get_arch_query_iter(
@@ -101,32 +345,77 @@ impl<'w, 's, P: QueryParam, F: QueryFilter> Query<'w, 's, P, F> {
                 .get_tables_mut()
                 .get_mut(&arch_id)
                 .expect("Table with archetype id could not be found.")
-                .tuple_iter::<P>()
+                .tuple_iter::<P>(self.system_ticks.this_run)
+        }
+    }
+}
+
+// Rows of a single table, which are iterated by one thread of a parallel iteration.
+struct QueryChunk<T: TupleIterator> {
+    arch_id: ArchetypeId,
+    iter: TableStorageTupleIter<T>,
+}
+
+// Chunks of the same table never contain the same rows.
+unsafe impl<T: TupleIterator> Send for QueryChunk<T> {}
+unsafe impl<T: TupleIterator> Sync for QueryChunk<T> {}
+
+impl<T: TupleIterator> QueryChunk<T> {
+    fn for_each<P: QueryParam, F: QueryFilter>(
+        mut self,
+        query: &Query<'_, '_, P, F>,
+        f: &impl Fn(T::Item),
+    ) {
+        let check_rows = query.state.sparse_filter || query.state.tick_filter;
+        while let Some(elem) = self.iter.next() {
+            if !check_rows || query.row_matches_filter(self.arch_id, self.iter.last_row_index()) {
+                f(elem);
+            }
         }
     }
 }
 
 pub struct QueryIter<'w, 's, T: QueryParam, F: QueryFilter> {
-    query: &'w Query<'w, 's, T, F>,
+    query: Query<'w, 's, T, F>,
     cur_arch_query: Option<TableStorageTupleIter<T::Construct<'w>>>,
+    cur_arch_id: Option<ArchetypeId>,
     cur_arch_index: hash_set::Iter<'s, ArchetypeId>,
 }
 
 impl<'w, 's, T: QueryParam, F: QueryFilter> QueryIter<'w, 's, T, F> {
-    pub fn new(query: &'w Query<'w, 's, T, F>) -> Self {
+    pub fn new(query: Query<'w, 's, T, F>) -> Self {
         let mut arch_query = None;
+        let mut cur_arch_id = None;
         let mut arch_ids_iter = query.state.arch_ids.iter();
         if query.state.arch_ids.len() > 0 {
             let arch_id = <_ as Iterator>::next(&mut arch_ids_iter).unwrap();
             arch_query = Some(unsafe { query.get_arch_query_iter(*arch_id) });
+            cur_arch_id = Some(*arch_id);
         }
 
         Self {
             query,
             cur_arch_query: arch_query,
+            cur_arch_id,
             cur_arch_index: arch_ids_iter,
         }
     }
+
+    fn row_matches_filter(&self, row_index: usize) -> bool {
+        let arch_id = self
+            .cur_arch_id
+            .expect("Current archetype id should be set.");
+        self.query.row_matches_filter(arch_id, row_index)
+    }
+}
+
+impl<'w, 's, P: QueryParam, F: QueryFilter> IntoIterator for Query<'w, 's, P, F> {
+    type Item = <QueryIter<'w, 's, P, F> as Iterator>::Item;
+    type IntoIter = QueryIter<'w, 's, P, F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        QueryIter::new(self)
+    }
 }
 
 impl<'w, 's, T: QueryParam, F: QueryFilter> Iterator for QueryIter<'w, 's, T, F> {
@@ -135,12 +424,21 @@ impl<'w, 's, T: QueryParam, F: QueryFilter> Iterator for QueryIter<'w, 's, T, F>
         loop {
             if let Some(cur_query) = &mut self.cur_arch_query {
                 match <_ as Iterator>::next(cur_query) {
-                    Some(elem) => return Some(elem),
+                    Some(elem) => {
+                        if !self.query.state.sparse_filter && !self.query.state.tick_filter {
+                            return Some(elem);
+                        }
+                        let row_index = cur_query.last_row_index();
+                        if self.row_matches_filter(row_index) {
+                            return Some(elem);
+                        }
+                    }
                     None => {
                         if let Some(next_arch_id) = <_ as Iterator>::next(&mut self.cur_arch_index)
                         {
                             self.cur_arch_query =
                                 Some(unsafe { self.query.get_arch_query_iter(*next_arch_id) });
+                            self.cur_arch_id = Some(*next_arch_id);
                         } else {
                             return None;
                         }
@@ -153,12 +451,15 @@ impl<'w, 's, T: QueryParam, F: QueryFilter> Iterator for QueryIter<'w, 's, T, F>
     }
 }
 
+impl<'w, 's, P: ReadOnlyQueryParam, F: QueryFilter> ReadOnlySystemParam for Query<'w, 's, P, F> {}
+
 impl<'w, 's, P: QueryParam, F: QueryFilter> SystemParam for Query<'w, 's, P, F> {
     type Item<'new> = Query<'new, 's, P, F>;
     unsafe fn retrieve<'r>(
         system_param_index: &mut usize,
         system_param_ids: &[SystemParamId],
         world_data: *mut WorldData,
+        system_ticks: SystemTicks,
     ) -> Self::Item<'r> {
         let world_data_mut = unsafe { world_data.as_mut().unwrap() };
         let sys_prm_id = &system_param_ids[*system_param_index];
@@ -167,7 +468,7 @@ impl<'w, 's, P: QueryParam, F: QueryFilter> SystemParam for Query<'w, 's, P, F>
             *system_param_index += 1;
             //TODO: wrap WorldData reference in some temporary unsafe access type that is Sync and
             //Send
-            return Query::new(world_data, qs);
+            return Query::new(world_data, qs, system_ticks);
         }
         panic!(
             "SystemParamId=<{}> is not a QueryId! param_ids: {:?}",
@@ -180,43 +481,8 @@ impl<'w, 's, P: QueryParam, F: QueryFilter> SystemParam for Query<'w, 's, P, F>
         system_param_ids: &mut Vec<SystemParamId>,
         world_data: &mut WorldData,
     ) {
-        let mut comp_ids = world_data.get_cache_mut().compid_vec_cache.take_cached();
-        P::comp_ids_rec(world_data, &mut comp_ids);
-        let comp_ids: SortedVec<ComponentId> = comp_ids.into();
-
-        let mut query_prm_meta_data = world_data
-            .get_cache_mut()
-            .query_param_meta_data_vec_cache
-            .take_cached();
-        P::meta_data(world_data, &mut query_prm_meta_data);
-        let query_prm_meta_data: SortedVec<QueryParamMetaData> = query_prm_meta_data.into();
-
-        let mut filter = Vec::new();
-        F::get_and_filters(world_data, &mut filter);
-
-        let query_state_key = QueryStateKey { comp_ids, filter };
-
-        let arch_ids = world_data.find_fitting_archetypes(&query_prm_meta_data);
-
-        // remove archetypes that do not match the filter
-        let arch_ids: Vec<ArchetypeId> = arch_ids
-            .iter()
-            .filter(|aid| {
-                let arch = &world_data.get_archetypes()[aid.0 as usize];
-                let comp_ids_set: HashSet<ComponentId> = HashSet::from_iter(
-                    arch.soa_comp_ids
-                        .iter()
-                        .chain(arch.aos_comp_ids.iter())
-                        .map(|cid| *cid),
-                );
-                let not_filtered_out = query_filter::comp_ids_compatible_with_filter(
-                    &comp_ids_set,
-                    &query_state_key.filter,
-                );
-                not_filtered_out
-            })
-            .cloned()
-            .collect();
+        let query_data = QueryState::from_params::<P, F>(world_data);
+        let arch_ids: Vec<ArchetypeId> = query_data.arch_ids.iter().copied().collect();
 
         let next_query_id = world_data.get_query_data().len().into();
         system_param_ids.push(SystemParamId::Query(next_query_id));
@@ -225,22 +491,13 @@ impl<'w, 's, P: QueryParam, F: QueryFilter> SystemParam for Query<'w, 's, P, F>
         // systems <- add queries <- add components and filtered archetypes
 
         let depend_graph = &mut world_data.get_depend_graph_mut();
-        depend_graph.insert_system_components(system_id, &query_prm_meta_data.get_vec());
+        depend_graph
+            .insert_system_components(system_id, query_data.query_param_meta_data.get_vec());
+        depend_graph
+            .insert_query_components(next_query_id, query_data.query_param_meta_data.get_vec());
         depend_graph.insert_query_archetypes(next_query_id, &arch_ids);
         depend_graph.insert_system_query(system_id, next_query_id);
 
-        let QueryStateKey {
-            comp_ids: _comp_ids,
-            filter,
-        } = query_state_key;
-
-        let arch_ids: HashSet<ArchetypeId> = arch_ids.iter().map(|aid| aid.clone()).collect();
-        let query_data = QueryState {
-            query_param_meta_data: query_prm_meta_data,
-            arch_ids: arch_ids,
-            filter: filter,
-        };
-
         world_data.get_query_data_mut().push(query_data);
     }
 }
@@ -251,6 +508,7 @@ pub struct QueryParamMetaData {
     pub comp_id: ComponentId,
     pub ref_kind: RefKind,
     pub optional: bool,
+    pub storage: StorageTypes,
 }
 impl Hash for QueryParamMetaData {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
@@ -278,6 +536,13 @@ pub trait QueryParam: TupleIterConstructor<QueryDataType> {
     fn meta_data(world_data: &mut WorldData, vec: &mut Vec<QueryParamMetaData>);
 }
 
+/// Marker for query params, which only access components immutably.
+pub trait ReadOnlyQueryParam: QueryParam {}
+
+impl<T: Component> ReadOnlyQueryParam for &T {}
+impl<T: Component> ReadOnlyQueryParam for Option<&T> {}
+impl ReadOnlyQueryParam for EntityKey {}
+
 impl<T: Component> QueryParam for &T {
     type QueryItem<'new> = &'new T;
 
@@ -301,6 +566,7 @@ impl<T: Component> QueryParam for &T {
             comp_id,
             ref_kind: RefKind::Shared,
             optional: false,
+            storage: T::STORAGE,
         });
     }
 }
@@ -327,6 +593,7 @@ impl<T: Component> QueryParam for &mut T {
             comp_id,
             ref_kind: RefKind::Exclusive,
             optional: false,
+            storage: T::STORAGE,
         });
     }
 }
@@ -352,6 +619,7 @@ impl<'p, T: Component> QueryParam for Option<&T> {
             comp_id,
             ref_kind: RefKind::Shared,
             optional: true,
+            storage: T::STORAGE,
         });
     }
 }
@@ -377,6 +645,7 @@ impl<'p, T: Component> QueryParam for Option<&mut T> {
             comp_id,
             ref_kind: RefKind::Exclusive,
             optional: true,
+            storage: T::STORAGE,
         });
     }
 }
@@ -432,13 +701,34 @@ all_tuples!(
     T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16
 );
 
+macro_rules! impl_read_only_query_param_tuples {
+    ($($t:ident), *) => {
+        impl<$($t : ReadOnlyQueryParam), *> ReadOnlyQueryParam for ($($t),*,){}
+    };
+}
+
+#[rustfmt::skip]
+all_tuples!(
+    impl_read_only_query_param_tuples,
+    T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16
+);
+
 #[cfg(test)]
 mod test {
-    use std::usize;
+    use std::{
+        sync::{
+            Mutex,
+            atomic::{AtomicUsize, Ordering},
+        },
+        thread::ThreadId,
+        usize,
+    };
 
     use crate::ecs::{
-        component::Component,
-        query::query_filter::{Or, With, Without},
+        component::{Component, StorageTypes},
+        entity::EntityKey,
+        query::query_filter::{Changed, Or, With, Without},
+        schedule::ScheduleLabel,
         system::{Res, ResMut},
         world::World,
     };
@@ -469,7 +759,7 @@ mod test {
         assert!(*prm.value == 2345678_i32);
         assert!(*prm2.value == 33330000);
 
-        for (comp1, comp2) in query.iter() {
+        for (comp1, mut comp2) in query.iter() {
             println!("comp1: {}; {}", comp1.0, comp1.1);
             println!("comp2: {}; {}", comp2.0, comp2.1);
             comp2.0 = 2;
@@ -484,7 +774,7 @@ mod test {
             assert_eq!(comp1.0, 12);
             assert_eq!(comp1.1, 34);
         }
-        assert_eq!(query.iter().count(), 4);
+        assert_eq!(query.iter().count(), 5);
     }
 
     fn test_system3(mut query: Query<(&Comp1, &Marker1), Without<Marker2>>) {
@@ -575,4 +865,160 @@ mod test {
             Some(&TestSystem6Ran(true))
         );
     }
+
+    struct Velocity(usize);
+    impl Component for Velocity {
+        const STORAGE: StorageTypes = StorageTypes::TableAoS;
+    }
+
+    struct Position(usize);
+    impl Component for Position {}
+
+    struct Frozen;
+    impl Component for Frozen {}
+
+    #[derive(Default)]
+    struct MovedCount(AtomicUsize);
+
+    fn par_move_system(
+        moved: Res<MovedCount>,
+        mut query: Query<(&Velocity, &mut Position), Without<Frozen>>,
+    ) {
+        query.par_for_each(64, |(vel, mut pos)| {
+            pos.0 += vel.0;
+            moved.0.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    #[test]
+    fn test_par_for_each() {
+        let mut world = World::new();
+        world.add_resource(MovedCount::default());
+        world.add_systems(par_move_system);
+        let moving: Vec<_> = (0..1000)
+            .map(|i| world.add_entity((Velocity(i), Position(1))))
+            .collect();
+        let frozen: Vec<_> = (0..100)
+            .map(|i| world.add_entity((Velocity(i), Position(1), Frozen)))
+            .collect();
+
+        world.init_and_run();
+        world.run();
+
+        let moved = world.get_resource::<MovedCount>().unwrap();
+        assert_eq!(2000, moved.0.load(Ordering::Relaxed));
+        for (i, key) in moving.into_iter().enumerate() {
+            let pos = world.get_single_component::<Position>(key).unwrap();
+            assert_eq!(1 + 2 * i, pos.0);
+        }
+        for key in frozen {
+            assert_eq!(1, world.get_single_component::<Position>(key).unwrap().0);
+        }
+    }
+
+    #[derive(Default)]
+    struct IterationThreads(Mutex<Vec<ThreadId>>);
+
+    fn par_moving_system(
+        threads: Res<IterationThreads>,
+        mut query: Query<&Position, Without<Frozen>>,
+    ) {
+        query.par_for_each(64, |_pos| {
+            threads.0.lock().unwrap().push(std::thread::current().id())
+        });
+    }
+
+    fn par_frozen_system(
+        threads: Res<IterationThreads>,
+        mut query: Query<&Position, With<Frozen>>,
+    ) {
+        query.par_for_each(64, |_pos| {
+            threads.0.lock().unwrap().push(std::thread::current().id())
+        });
+    }
+
+    #[test]
+    fn test_par_for_each_in_parallel_systems() {
+        let mut world = World::with_thread_count(2);
+        world.add_resource(IterationThreads::default());
+        let system_ids = world.add_systems((par_moving_system, par_frozen_system));
+        world.add_entity((Velocity(1), Position(1)));
+        world.add_entity((Velocity(1), Position(1), Frozen));
+
+        world.init_and_run();
+
+        // both systems run in the same batch
+        let schedule = &world.scheduler.schedule[&ScheduleLabel::Update];
+        assert!(
+            schedule
+                .iter()
+                .flatten()
+                .any(|batch| system_ids.iter().all(|id| batch.contains(id)))
+        );
+        // the chunks of both parallel iterations run on the shared thread pool of the world
+        let threads = world
+            .get_resource::<IterationThreads>()
+            .unwrap()
+            .0
+            .lock()
+            .unwrap()
+            .clone();
+        assert_eq!(2, threads.len());
+        let query_thread_pool = &world.data.get_mut().query_thread_pool;
+        assert!(
+            threads
+                .iter()
+                .all(|thread_id| query_thread_pool.is_pool_thread(*thread_id))
+        );
+    }
+
+    fn par_panic_system(mut query: Query<&Position>) {
+        query.par_for_each(1, |pos| assert_eq!(0, pos.0, "par_for_each chunk panicked"));
+    }
+
+    #[test]
+    #[should_panic(expected = "par_for_each chunk panicked")]
+    fn test_par_for_each_panic() {
+        let mut world = World::new();
+        world.add_systems(par_panic_system);
+        world.add_entity(Position(0));
+        world.add_entity(Position(1));
+        world.init_and_run();
+    }
+
+    #[test]
+    fn test_world_query() {
+        let mut world = World::new();
+        let moving = world.add_entity((Velocity(2), Position(1)));
+        world.add_entity((Velocity(3), Position(1), Frozen));
+
+        let mut move_query = world.query::<(&Velocity, &mut Position), Without<Frozen>>();
+        let mut changed_query = world.query::<EntityKey, Changed<Position>>();
+        assert_eq!(2, changed_query.iter(&mut world).count());
+        assert_eq!(0, changed_query.iter(&mut world).count());
+
+        for (vel, mut pos) in move_query.iter(&mut world) {
+            pos.0 += vel.0;
+        }
+        assert_eq!(3, world.get_single_component::<Position>(moving).unwrap().0);
+        let changed: Vec<_> = changed_query.iter(&mut world).collect();
+        assert_eq!(vec![moving], changed);
+
+        // archetypes created after the query state are matched on the next iteration
+        let marked = world.add_entity((Velocity(4), Position(1), Marker1()));
+        for (vel, mut pos) in move_query.iter(&mut world) {
+            pos.0 += vel.0;
+        }
+        assert_eq!(5, world.get_single_component::<Position>(moving).unwrap().0);
+        assert_eq!(5, world.get_single_component::<Position>(marked).unwrap().0);
+        assert_eq!(2, move_query.state().arch_ids.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "Query accesses the components")]
+    fn test_world_query_access_conflict() {
+        let mut world = World::new();
+        world.add_entity(Position(1));
+        world.query::<(&mut Position, &mut Position), ()>();
+    }
 }
@@ -0,0 +1,458 @@
+// dynamic_query.rs
+
+use std::ptr::NonNull;
+
+use crate::{
+    ecs::{
+        component::{ComponentId, StorageTypes},
+        entity::EntityKey,
+        query::{QueryParamMetaData, QueryState, RefKind, query_filter::FilterElem},
+        storages::entity_storage::EntityStorage,
+        world::World,
+    },
+    utils::{ecs_id::EcsId, sorted_vec::SortedVec},
+};
+
+use super::query_filter;
+
+/// Builder of a query over component ids instead of component types,
+/// used to query dynamic components, which are registered at runtime.
+#[derive(Debug, Clone, Default)]
+pub struct DynamicQueryBuilder {
+    with: Vec<ComponentId>,
+    without: Vec<ComponentId>,
+}
+
+impl DynamicQueryBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Matched entities need to own the component,
+    /// a pointer to it is yielded in the order the components were added to the builder.
+    pub fn with(mut self, comp_id: ComponentId) -> Self {
+        self.with.push(comp_id);
+        self
+    }
+
+    /// Matched entities must not own the component.
+    pub fn without(mut self, comp_id: ComponentId) -> Self {
+        self.without.push(comp_id);
+        self
+    }
+
+    pub fn build(self) -> DynamicQuery {
+        DynamicQuery {
+            with: self.with,
+            without: self.without,
+        }
+    }
+}
+
+/// Query over component ids, which yields every matching entity together with
+/// untyped pointers to its components. Relations can't be queried, because they don't store any data.
+#[derive(Debug, Clone)]
+pub struct DynamicQuery {
+    with: Vec<ComponentId>,
+    without: Vec<ComponentId>,
+}
+
+impl DynamicQuery {
+    // Archetypes are filtered by their table components,
+    // sparse set components are checked per entity of the matching tables.
+    pub(crate) fn for_each(
+        &self,
+        entity_storage: &EntityStorage,
+        mut func: impl FnMut(EntityKey, &[NonNull<u8>]),
+    ) {
+        let is_sparse = |comp_id: &&ComponentId| {
+            entity_storage
+                .components
+                .get(comp_id.id_usize())
+                .is_some_and(|cinfo| cinfo.storage == StorageTypes::SparseSet)
+        };
+        let mut ptrs = Vec::with_capacity(self.with.len());
+        for archetype in entity_storage.archetypes.iter() {
+            let matches = self
+                .with
+                .iter()
+                .filter(|cid| !is_sparse(cid))
+                .all(|cid| archetype.contains_comp(cid))
+                && !self
+                    .without
+                    .iter()
+                    .filter(|cid| !is_sparse(cid))
+                    .any(|cid| archetype.contains_comp(cid));
+            if !matches {
+                continue;
+            }
+            let Some(table) = entity_storage.tables.get(&archetype.archetype_id) else {
+                continue;
+            };
+            for entity_key in table.entities.iter().copied() {
+                let excluded = self
+                    .without
+                    .iter()
+                    .filter(is_sparse)
+                    .any(|cid| entity_storage.sparse_sets.contains(cid, entity_key));
+                if excluded {
+                    continue;
+                }
+                ptrs.clear();
+                ptrs.extend(
+                    self.with
+                        .iter()
+                        .map_while(|cid| entity_storage.get_component_ptr(entity_key, *cid)),
+                );
+                if ptrs.len() == self.with.len() {
+                    func(entity_key, &ptrs);
+                }
+            }
+        }
+    }
+}
+
+/// Builder of a query over component ids instead of component types,
+/// which supports the same params and filters as typed queries,
+/// e.g. for components looked up by name or dynamic components registered at runtime.
+#[derive(Debug, Clone, Default)]
+pub struct QueryBuilder {
+    params: Vec<(ComponentId, RefKind, bool)>,
+    filter: Vec<FilterElem>,
+}
+
+impl QueryBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(self, comp_id: ComponentId) -> Self {
+        self.param(comp_id, RefKind::Shared, false)
+    }
+
+    pub fn write(self, comp_id: ComponentId) -> Self {
+        self.param(comp_id, RefKind::Exclusive, false)
+    }
+
+    /// Also matches entities, which don't own the component.
+    pub fn read_optional(self, comp_id: ComponentId) -> Self {
+        self.param(comp_id, RefKind::Shared, true)
+    }
+
+    /// Also matches entities, which don't own the component.
+    pub fn write_optional(self, comp_id: ComponentId) -> Self {
+        self.param(comp_id, RefKind::Exclusive, true)
+    }
+
+    pub fn with(mut self, comp_id: ComponentId) -> Self {
+        self.filter.push(FilterElem::With(comp_id));
+        self
+    }
+
+    pub fn without(mut self, comp_id: ComponentId) -> Self {
+        self.filter.push(FilterElem::Without(comp_id));
+        self
+    }
+
+    fn param(mut self, comp_id: ComponentId, ref_kind: RefKind, optional: bool) -> Self {
+        self.params.push((comp_id, ref_kind, optional));
+        self
+    }
+
+    /// Creates the query state with all archetypes of the world matching the query.
+    /// Panics, if a component is not registered in the world or queried multiple times.
+    pub fn build(self, world: &mut World) -> QueryState {
+        let world_data = world.data.get_mut();
+        let components = world_data.get_components();
+        let mut meta_data = Vec::with_capacity(self.params.len());
+        for (comp_id, ref_kind, optional) in self.params.iter().copied() {
+            let cinfo = components
+                .get(comp_id.id_usize())
+                .expect("Queried component should be registered in the world.");
+            meta_data.push(QueryParamMetaData {
+                type_id: cinfo.type_id,
+                comp_id,
+                ref_kind,
+                optional,
+                storage: cinfo.storage,
+            });
+        }
+        let meta_data: SortedVec<QueryParamMetaData> = meta_data.into();
+        if !meta_data.get_vec().is_empty()
+            && let Some(dup) = meta_data.check_duplicates()
+        {
+            panic!(
+                "Component {} is queried multiple times.",
+                components[dup.comp_id.id_usize()].name
+            );
+        }
+        let param_comp_ids = self.params.iter().map(|(cid, _, _)| *cid).collect();
+        QueryState::new(world_data, meta_data, param_comp_ids, self.filter)
+    }
+}
+
+impl QueryState {
+    /// Calls the function with every entity matched by the query and untyped pointers
+    /// to its queried components, in the order the components were added to the query.
+    /// Optional components, which the entity does not own, are None.
+    /// Components queried for writing are marked as changed.
+    /// Archetypes created since the last iteration are matched before iterating.
+    pub fn for_each_dyn(
+        &mut self,
+        world: &mut World,
+        mut func: impl FnMut(EntityKey, &[Option<NonNull<u8>>]),
+    ) {
+        let world_data = world.data.get_mut();
+        self.update_archetypes(world_data);
+        let entity_storage = world_data.get_entity_storage_mut();
+        let params: Vec<&QueryParamMetaData> = self
+            .param_comp_ids
+            .iter()
+            .filter_map(|cid| {
+                self.query_param_meta_data
+                    .iter()
+                    .find(|qpmd| qpmd.comp_id == *cid)
+            })
+            .collect();
+        let mut ptrs = Vec::with_capacity(params.len());
+        for arch_id in self.arch_ids.iter() {
+            let Some(len) = entity_storage.tables.get(arch_id).map(|t| t.entities.len()) else {
+                continue;
+            };
+            for row in 0..len {
+                let entity_key = entity_storage.tables[arch_id].entities[row];
+                // filters with sparse set components are checked per entity
+                if self.sparse_filter {
+                    let archetype = &entity_storage.archetypes[arch_id.id_usize()];
+                    let sparse_sets = &entity_storage.sparse_sets;
+                    let has = |cid: &ComponentId| {
+                        archetype.contains_comp(cid) || sparse_sets.contains(cid, entity_key)
+                    };
+                    if !query_filter::filter_matches(&has, &self.filter) {
+                        continue;
+                    }
+                }
+                ptrs.clear();
+                ptrs.extend(
+                    params
+                        .iter()
+                        .map(|qpmd| entity_storage.get_component_ptr(entity_key, qpmd.comp_id)),
+                );
+                // sparse set components are not part of archetypes
+                // and need to be checked for every entity
+                let owns_required = params
+                    .iter()
+                    .zip(ptrs.iter())
+                    .all(|(qpmd, ptr)| qpmd.optional || ptr.is_some());
+                if !owns_required {
+                    continue;
+                }
+                params
+                    .iter()
+                    .zip(ptrs.iter())
+                    .filter(|(qpmd, ptr)| qpmd.ref_kind == RefKind::Exclusive && ptr.is_some())
+                    .for_each(|(qpmd, _)| {
+                        entity_storage.set_component_changed(entity_key, qpmd.comp_id)
+                    });
+                func(entity_key, &ptrs);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        alloc::Layout,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::ecs::prelude::*;
+
+    struct Position(f32);
+    impl Component for Position {}
+
+    struct Name(&'static str);
+    impl Component for Name {}
+
+    struct Hidden;
+    impl Component for Hidden {
+        const STORAGE: StorageTypes = StorageTypes::SparseSet;
+    }
+
+    struct Tag;
+    impl Component for Tag {}
+
+    static HANDLE_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe fn drop_handle(_ptr: *mut u8) {
+        HANDLE_DROPS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_dynamic_query() {
+        let mut world = World::new();
+        let velocity =
+            world.register_dynamic_component("Velocity", Layout::new::<[f32; 2]>(), None);
+        let frozen = world.register_dynamic_component("Frozen", Layout::new::<()>(), None);
+        assert_eq!(
+            velocity,
+            world.register_dynamic_component("Velocity", Layout::new::<[f32; 2]>(), None)
+        );
+        assert_eq!(Some(frozen), world.get_dynamic_component_id("Frozen"));
+        let position = world.get_component_id::<Position>();
+
+        let moving = world.add_entity(Position(0.));
+        let stuck = world.add_entity(Position(5.));
+        let _still = world.add_entity(Position(9.));
+        let bytes: Vec<u8> = [1f32, 2.].iter().flat_map(|f| f.to_ne_bytes()).collect();
+        unsafe {
+            world.add_component_dyn(moving, velocity, &bytes);
+            world.add_component_dyn(stuck, velocity, &bytes);
+            world.add_component_dyn(stuck, frozen, &[]);
+        }
+
+        let query = DynamicQueryBuilder::new()
+            .with(position)
+            .with(velocity)
+            .without(frozen)
+            .build();
+        let mut matched = Vec::new();
+        world.for_each_dynamic(&query, |entity, ptrs| {
+            let pos = unsafe { ptrs[0].cast::<Position>().as_mut() };
+            let vel = unsafe { ptrs[1].cast::<[f32; 2]>().read_unaligned() };
+            pos.0 += vel[0] + vel[1];
+            matched.push(entity);
+        });
+        assert_eq!(vec![moving], matched);
+        assert_eq!(
+            3.,
+            world.get_single_component::<Position>(moving).unwrap().0
+        );
+        assert_eq!(5., world.get_single_component::<Position>(stuck).unwrap().0);
+
+        world.remove_component_dyn(stuck, frozen);
+        let mut matched = Vec::new();
+        world.for_each_dynamic(&query, |entity, _ptrs| matched.push(entity));
+        matched.sort_by_key(|entity| entity.get_id());
+        assert_eq!(vec![moving, stuck], matched);
+    }
+
+    #[test]
+    fn test_query_builder_dynamic_components() {
+        let mut world = World::new();
+        let velocity =
+            world.register_dynamic_component("Velocity", Layout::new::<[f32; 2]>(), None);
+        let frozen = world.register_dynamic_component("Frozen", Layout::new::<()>(), None);
+        assert_eq!(
+            velocity,
+            world.register_dynamic_component("Velocity", Layout::new::<[f32; 2]>(), None)
+        );
+        assert_eq!(Some(frozen), world.get_dynamic_component_id("Frozen"));
+        let position = world.get_component_id::<Position>();
+
+        let moving = world.add_entity(Position(0.));
+        let stuck = world.add_entity(Position(5.));
+        let _still = world.add_entity(Position(9.));
+        let bytes: Vec<u8> = [1f32, 2.].iter().flat_map(|f| f.to_ne_bytes()).collect();
+        unsafe {
+            world.add_component_dyn(moving, velocity, &bytes);
+            world.add_component_dyn(stuck, velocity, &bytes);
+            world.add_component_dyn(stuck, frozen, &[]);
+        }
+
+        let mut query = QueryBuilder::new()
+            .write(position)
+            .read(velocity)
+            .without(frozen)
+            .build(&mut world);
+        let mut matched = Vec::new();
+        query.for_each_dyn(&mut world, |entity, ptrs| {
+            let pos = unsafe { ptrs[0].unwrap().cast::<Position>().as_mut() };
+            let vel = unsafe { ptrs[1].unwrap().cast::<[f32; 2]>().read_unaligned() };
+            pos.0 += vel[0] + vel[1];
+            matched.push(entity);
+        });
+        assert_eq!(vec![moving], matched);
+        assert_eq!(
+            3.,
+            world.get_single_component::<Position>(moving).unwrap().0
+        );
+        assert_eq!(5., world.get_single_component::<Position>(stuck).unwrap().0);
+
+        world.remove_component_dyn(stuck, frozen);
+        let mut matched = Vec::new();
+        query.for_each_dyn(&mut world, |entity, _ptrs| matched.push(entity));
+        matched.sort_by_key(|entity| entity.get_id());
+        assert_eq!(vec![moving, stuck], matched);
+    }
+
+    fn collect_rows(
+        query: &mut QueryState,
+        world: &mut World,
+    ) -> Vec<(EntityKey, f32, Option<&'static str>)> {
+        let mut rows = Vec::new();
+        query.for_each_dyn(world, |entity, ptrs| {
+            let pos = unsafe { ptrs[0].unwrap().cast::<Position>().as_ref().0 };
+            let name = ptrs[1].map(|ptr| unsafe { ptr.cast::<Name>().as_ref().0 });
+            rows.push((entity, pos, name));
+        });
+        rows.sort_by_key(|(entity, _, _)| entity.get_id());
+        rows
+    }
+
+    #[test]
+    fn test_query_builder_filters() {
+        let mut world = World::new();
+        let alice = world.add_entity((Position(1.), Name("alice")));
+        let bob = world.add_entity(Position(2.));
+        world.add_entity((Position(3.), Name("hidden"), Hidden));
+        let position = world.get_component_id::<Position>();
+        let name = world.get_component_id::<Name>();
+        let hidden = world.get_component_id::<Hidden>();
+
+        let mut query = QueryBuilder::new()
+            .read(position)
+            .read_optional(name)
+            .without(hidden)
+            .build(&mut world);
+        assert_eq!(
+            vec![(alice, 1., Some("alice")), (bob, 2., None)],
+            collect_rows(&mut query, &mut world)
+        );
+
+        // archetypes created after the query was built are matched on the next iteration
+        let carol = world.add_entity((Position(4.), Tag));
+        assert_eq!(
+            vec![
+                (alice, 1., Some("alice")),
+                (bob, 2., None),
+                (carol, 4., None)
+            ],
+            collect_rows(&mut query, &mut world)
+        );
+    }
+
+    #[test]
+    fn test_dynamic_component_drop() {
+        let mut world = World::new();
+        let handle =
+            world.register_dynamic_component("Handle", Layout::new::<u64>(), Some(drop_handle));
+        let entity = world.add_entity(Position(0.));
+        unsafe {
+            world.add_component_dyn(entity, handle, &1u64.to_ne_bytes());
+            // replacing the component drops the old one
+            world.add_component_dyn(entity, handle, &2u64.to_ne_bytes());
+        }
+        assert_eq!(1, HANDLE_DROPS.load(Ordering::SeqCst));
+        let query = DynamicQueryBuilder::new().with(handle).build();
+        let mut values = Vec::new();
+        world.for_each_dynamic(&query, |_entity, ptrs| {
+            values.push(unsafe { ptrs[0].cast::<u64>().read_unaligned() })
+        });
+        assert_eq!(vec![2], values);
+
+        world.data.get_mut().remove_entity(entity);
+        assert_eq!(2, HANDLE_DROPS.load(Ordering::SeqCst));
+    }
+}
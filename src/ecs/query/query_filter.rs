@@ -5,6 +5,7 @@ use std::{collections::HashSet, marker::PhantomData};
 use crate::{
     all_tuples,
     ecs::{
+        change_detection::{ComponentTicks, SystemTicks},
         component::{Component, ComponentId},
         world::WorldData,
     },
@@ -14,29 +15,100 @@ pub(crate) fn comp_ids_compatible_with_filter(
     comp_ids: &HashSet<ComponentId>,
     filter: &[FilterElem],
 ) -> bool {
+    filter_matches(&|cid| comp_ids.contains(cid), filter)
+}
+
+// Checks the filter against a component lookup,
+// so that sparse set components of single entities can be taken into account.
+// Added and Changed filters only check if the component is contained,
+// their ticks need to be checked separately for every entity.
+pub(crate) fn filter_matches(has: &impl Fn(&ComponentId) -> bool, filter: &[FilterElem]) -> bool {
+    filter_matches_by(
+        &|filter_elem| match filter_elem {
+            FilterElem::With(id) | FilterElem::Added(id) | FilterElem::Changed(id) => has(id),
+            FilterElem::Without(id) => !has(id),
+            FilterElem::Or(_) => unreachable!("Or filters are handled by filter_matches_by."),
+        },
+        filter,
+    )
+}
+
+// Checks the filter against the components of a single entity,
+// ticks returns the change ticks of a component, if the entity owns it.
+pub(crate) fn entity_matches_filter(
+    has: &impl Fn(&ComponentId) -> bool,
+    ticks: &impl Fn(&ComponentId) -> Option<ComponentTicks>,
+    system_ticks: SystemTicks,
+    filter: &[FilterElem],
+) -> bool {
+    let SystemTicks { last_run, this_run } = system_ticks;
+    filter_matches_by(
+        &|filter_elem| match filter_elem {
+            FilterElem::With(id) => has(id),
+            FilterElem::Without(id) => !has(id),
+            FilterElem::Added(id) => ticks(id).is_some_and(|t| t.is_added(last_run, this_run)),
+            FilterElem::Changed(id) => ticks(id).is_some_and(|t| t.is_changed(last_run, this_run)),
+            FilterElem::Or(_) => unreachable!("Or filters are handled by filter_matches_by."),
+        },
+        filter,
+    )
+}
+
+fn filter_matches_by(leaf_matches: &impl Fn(&FilterElem) -> bool, filter: &[FilterElem]) -> bool {
     for el in filter.iter() {
-        if !handle_filter_elem(comp_ids, el) {
+        let matches = match el {
+            FilterElem::Or(or_elems) => or_elems
+                .iter()
+                .any(|or_el| filter_matches_by(leaf_matches, or_el)),
+            _ => leaf_matches(el),
+        };
+        if !matches {
             return false;
         }
     }
     true
 }
 
-fn handle_filter_elem(comp_ids: &HashSet<ComponentId>, filter_elem: &FilterElem) -> bool {
-    match filter_elem {
-        FilterElem::With(id) => comp_ids.contains(id),
-        FilterElem::Without(id) => !comp_ids.contains(id),
-        FilterElem::Or(or_elems) => handle_or_elems(comp_ids, or_elems),
+pub(crate) fn filter_comp_ids(filter: &[FilterElem], vec: &mut Vec<ComponentId>) {
+    for el in filter.iter() {
+        match el {
+            FilterElem::With(id)
+            | FilterElem::Without(id)
+            | FilterElem::Added(id)
+            | FilterElem::Changed(id) => vec.push(*id),
+            FilterElem::Or(or_elems) => or_elems
+                .iter()
+                .for_each(|or_el| filter_comp_ids(or_el, vec)),
+        }
     }
 }
 
-fn handle_or_elems(comp_ids: &HashSet<ComponentId>, or_elems: &[Vec<FilterElem>]) -> bool {
-    for or_el in or_elems.iter() {
-        if comp_ids_compatible_with_filter(comp_ids, &or_el) {
-            return true;
-        }
-    }
-    false
+// Components, which an entity needs to own to match the filter.
+// Components inside of Or filters are not taken into account.
+pub(crate) fn required_comp_ids(filter: &[FilterElem]) -> impl Iterator<Item = ComponentId> + '_ {
+    filter.iter().filter_map(|el| match el {
+        FilterElem::With(id) | FilterElem::Added(id) | FilterElem::Changed(id) => Some(*id),
+        _ => None,
+    })
+}
+
+// Components, which an entity is not allowed to own to match the filter.
+// Components inside of Or filters are not taken into account.
+pub(crate) fn excluded_comp_ids(filter: &[FilterElem]) -> impl Iterator<Item = ComponentId> + '_ {
+    filter.iter().filter_map(|el| match el {
+        FilterElem::Without(id) => Some(*id),
+        _ => None,
+    })
+}
+
+// Returns true, if the filter contains Added or Changed filters,
+// which need to be checked for every entity during iteration.
+pub(crate) fn contains_tick_filter(filter: &[FilterElem]) -> bool {
+    filter.iter().any(|el| match el {
+        FilterElem::Added(_) | FilterElem::Changed(_) => true,
+        FilterElem::Or(or_elems) => or_elems.iter().any(|or_el| contains_tick_filter(or_el)),
+        _ => false,
+    })
 }
 
 pub trait QueryFilter: Send + Sync {
@@ -50,6 +122,16 @@ pub struct With<T: Component> {
 pub struct Without<T: Component> {
     _marker: PhantomData<fn() -> T>,
 }
+/// Only matches entities, whose component of type T was added
+/// since the last run of the system.
+pub struct Added<T: Component> {
+    _marker: PhantomData<fn() -> T>,
+}
+/// Only matches entities, whose component of type T was added or mutably accessed
+/// since the last run of the system.
+pub struct Changed<T: Component> {
+    _marker: PhantomData<fn() -> T>,
+}
 pub struct Or<F: QueryFilter> {
     _marker: PhantomData<F>,
 }
@@ -58,6 +140,8 @@ pub struct Or<F: QueryFilter> {
 pub enum FilterElem {
     With(ComponentId),
     Without(ComponentId),
+    Added(ComponentId),
+    Changed(ComponentId),
     Or(Vec<Vec<FilterElem>>),
 }
 
@@ -81,6 +165,22 @@ impl<T: Component> QueryFilter for Without<T> {
         filter_elems.push(vec![FilterElem::Without(es.create_or_get_component::<T>())]);
     }
 }
+impl<T: Component> QueryFilter for Added<T> {
+    fn get_and_filters(es: &mut WorldData, filter_elems: &mut Vec<FilterElem>) {
+        filter_elems.push(FilterElem::Added(es.create_or_get_component::<T>()));
+    }
+    fn get_or_filters(es: &mut WorldData, filter_elems: &mut Vec<Vec<FilterElem>>) {
+        filter_elems.push(vec![FilterElem::Added(es.create_or_get_component::<T>())]);
+    }
+}
+impl<T: Component> QueryFilter for Changed<T> {
+    fn get_and_filters(es: &mut WorldData, filter_elems: &mut Vec<FilterElem>) {
+        filter_elems.push(FilterElem::Changed(es.create_or_get_component::<T>()));
+    }
+    fn get_or_filters(es: &mut WorldData, filter_elems: &mut Vec<Vec<FilterElem>>) {
+        filter_elems.push(vec![FilterElem::Changed(es.create_or_get_component::<T>())]);
+    }
+}
 impl<F: QueryFilter> QueryFilter for Or<F> {
     fn get_and_filters(es: &mut WorldData, filter_elems: &mut Vec<FilterElem>) {
         let mut or_inner_elems = Vec::new();
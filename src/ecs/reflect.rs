@@ -0,0 +1,540 @@
+// reflect.rs
+
+use std::{
+    alloc::Layout,
+    any::{TypeId, type_name},
+    fmt::{self, Display},
+    marker::PhantomData,
+    mem::size_of,
+    ptr::NonNull,
+};
+
+use crate::ecs::{
+    component::{ComponentInfo, Map},
+    entity::EntityKey,
+};
+
+/// Component or resource type, whose fields can be read and written without knowing the type.
+///
+/// Usually implemented with the `impl_reflect!` macro:
+/// `impl_reflect!(Position { x, y });` or `impl_reflect!(Health { 0 });`
+pub trait Reflect: 'static + Sized {
+    fn fields() -> Vec<FieldInfo>;
+}
+
+/// Implements Reflect for a struct by the names of its reflected fields.
+/// Fields, which are not listed, can't be accessed through reflection.
+#[macro_export]
+macro_rules! impl_reflect {
+    ($t:ty { $($field:tt),* $(,)? }) => {
+        impl $crate::ecs::reflect::Reflect for $t {
+            fn fields() -> Vec<$crate::ecs::reflect::FieldInfo> {
+                vec![$(
+                    $crate::ecs::reflect::FieldInfo::new(
+                        stringify!($field),
+                        std::mem::offset_of!($t, $field),
+                        $crate::ecs::reflect::FieldType::of_field(|value: &$t| &value.$field),
+                    )
+                ),*]
+            }
+        }
+    };
+}
+
+// Generates the field types and values of all primitive types,
+// which can be read and written through reflection.
+macro_rules! impl_field_types {
+    ($($variant:ident: $t:ty), *) => {
+        /// Type of a reflected field.
+        /// Fields of other types are opaque, they can't be read or written.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum FieldType {
+            $($variant,)*
+            Opaque(&'static str),
+        }
+
+        /// Value of a reflected field.
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub enum FieldValue {
+            $($variant($t),)*
+        }
+
+        impl FieldType {
+            pub fn of<T: 'static>() -> Self {
+                let type_id = TypeId::of::<T>();
+                $(if type_id == TypeId::of::<$t>() {
+                    return FieldType::$variant;
+                })*
+                FieldType::Opaque(type_name::<T>())
+            }
+
+            /// Returns the size of the field, None for opaque fields.
+            pub fn size(&self) -> Option<usize> {
+                match self {
+                    $(FieldType::$variant => Some(size_of::<$t>()),)*
+                    FieldType::Opaque(_) => None,
+                }
+            }
+        }
+
+        impl FieldValue {
+            pub fn field_type(&self) -> FieldType {
+                match self {
+                    $(FieldValue::$variant(_) => FieldType::$variant,)*
+                }
+            }
+
+            // #SAFETY: The pointer needs to point to a valid value of the field type.
+            unsafe fn read(ptr: NonNull<u8>, field_type: FieldType) -> Option<Self> {
+                match field_type {
+                    $(FieldType::$variant => Some(FieldValue::$variant(unsafe {
+                        ptr.cast::<$t>().read_unaligned()
+                    })),)*
+                    FieldType::Opaque(_) => None,
+                }
+            }
+
+            // #SAFETY: The pointer needs to point to a value of the same field type,
+            // which is overwritten without being dropped.
+            unsafe fn write(self, ptr: NonNull<u8>) {
+                match self {
+                    $(FieldValue::$variant(value) => unsafe {
+                        ptr.cast::<$t>().write_unaligned(value)
+                    },)*
+                }
+            }
+        }
+    };
+}
+
+impl_field_types!(
+    Bool: bool,
+    U8: u8,
+    U16: u16,
+    U32: u32,
+    U64: u64,
+    Usize: usize,
+    I8: i8,
+    I16: i16,
+    I32: i32,
+    I64: i64,
+    Isize: isize,
+    F32: f32,
+    F64: f64,
+    EntityKey: EntityKey
+);
+
+impl FieldType {
+    /// Returns the type of the field returned by the accessor, used by `impl_reflect!`.
+    pub fn of_field<S, F: 'static>(_accessor: fn(&S) -> &F) -> Self {
+        Self::of::<F>()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    // offset of the field from the start of its struct
+    pub offset: usize,
+    pub field_type: FieldType,
+}
+
+impl FieldInfo {
+    pub fn new(name: &'static str, offset: usize, field_type: FieldType) -> Self {
+        Self {
+            name,
+            offset,
+            field_type,
+        }
+    }
+}
+
+/// Errors of reading or writing fields through reflection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReflectError {
+    UnknownField(String),
+    /// Fields of types, which are not primitive, can't be accessed.
+    OpaqueField(String),
+    TypeMismatch {
+        field: String,
+        expected: FieldType,
+        found: FieldType,
+    },
+    /// The byte slice is too short to contain the field.
+    OutOfBounds(String),
+    /// The bytes of the field are not a valid value of its type.
+    InvalidValue(String),
+}
+
+impl Display for ReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReflectError::UnknownField(field) => write!(f, "Unknown field {field}."),
+            ReflectError::OpaqueField(field) => {
+                write!(f, "Field {field} is opaque and can't be accessed.")
+            }
+            ReflectError::TypeMismatch {
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Field {field} is of type {expected:?}, but a value of type {found:?} was supplied."
+            ),
+            ReflectError::OutOfBounds(field) => {
+                write!(f, "Field {field} is outside of the supplied bytes.")
+            }
+            ReflectError::InvalidValue(field) => {
+                write!(
+                    f,
+                    "Bytes of field {field} are not a valid value of its type."
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReflectError {}
+
+/// Reflected fields of a component or resource type.
+#[derive(Debug, Clone)]
+pub struct TypeInfo {
+    pub name: &'static str,
+    pub type_id: TypeId,
+    pub layout: Layout,
+    pub fields: Vec<FieldInfo>,
+}
+
+impl TypeInfo {
+    pub fn of<T: Reflect>() -> Self {
+        Self {
+            name: type_name::<T>(),
+            type_id: TypeId::of::<T>(),
+            layout: Layout::new::<T>(),
+            fields: T::fields(),
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldInfo> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Reads a field from the bytes of a value of this type.
+    pub fn read_field(&self, bytes: &[u8], name: &str) -> Result<FieldValue, ReflectError> {
+        let field = self.accessible_field(bytes.len(), name)?;
+        let field_bytes = &bytes[field.offset..];
+        let valid = match field.field_type {
+            FieldType::Bool => field_bytes[0] <= 1,
+            _ => true,
+        };
+        if !valid {
+            return Err(ReflectError::InvalidValue(name.to_string()));
+        }
+        unsafe {
+            FieldValue::read(NonNull::from(field_bytes).cast(), field.field_type)
+                .ok_or_else(|| ReflectError::OpaqueField(name.to_string()))
+        }
+    }
+
+    /// Writes a field into the bytes of a value of this type.
+    pub fn write_field(
+        &self,
+        bytes: &mut [u8],
+        name: &str,
+        value: FieldValue,
+    ) -> Result<(), ReflectError> {
+        let field = self.accessible_field(bytes.len(), name)?;
+        Self::check_type(field, value)?;
+        unsafe { value.write(NonNull::from(&mut bytes[field.offset..]).cast()) };
+        Ok(())
+    }
+
+    fn accessible_field(&self, len: usize, name: &str) -> Result<&FieldInfo, ReflectError> {
+        let field = self
+            .field(name)
+            .ok_or_else(|| ReflectError::UnknownField(name.to_string()))?;
+        let size = field
+            .field_type
+            .size()
+            .ok_or_else(|| ReflectError::OpaqueField(name.to_string()))?;
+        if field.offset + size > len {
+            return Err(ReflectError::OutOfBounds(name.to_string()));
+        }
+        Ok(field)
+    }
+
+    fn check_type(field: &FieldInfo, value: FieldValue) -> Result<(), ReflectError> {
+        if let FieldType::Opaque(_) = field.field_type {
+            return Err(ReflectError::OpaqueField(field.name.to_string()));
+        }
+        if field.field_type != value.field_type() {
+            return Err(ReflectError::TypeMismatch {
+                field: field.name.to_string(),
+                expected: field.field_type,
+                found: value.field_type(),
+            });
+        }
+        Ok(())
+    }
+
+    // #SAFETY: The pointer needs to point to a valid value of this type.
+    unsafe fn read_field_ptr(
+        &self,
+        ptr: NonNull<u8>,
+        name: &str,
+    ) -> Result<FieldValue, ReflectError> {
+        let field = self
+            .field(name)
+            .ok_or_else(|| ReflectError::UnknownField(name.to_string()))?;
+        unsafe { FieldValue::read(ptr.byte_add(field.offset), field.field_type) }
+            .ok_or_else(|| ReflectError::OpaqueField(name.to_string()))
+    }
+}
+
+/// Registry of the reflected component and resource types of a world.
+#[derive(Default)]
+pub struct TypeRegistry {
+    types: Map<TypeId, TypeInfo>,
+}
+
+impl TypeRegistry {
+    pub fn register<T: Reflect>(&mut self) {
+        self.types
+            .entry(TypeId::of::<T>())
+            .or_insert_with(TypeInfo::of::<T>);
+    }
+
+    pub fn get(&self, type_id: &TypeId) -> Option<&TypeInfo> {
+        self.types.get(type_id)
+    }
+
+    /// Returns the reflected fields of the component,
+    /// which can be used to access the bytes of the component in its table.
+    pub fn get_component(&self, cinfo: &ComponentInfo) -> Option<&TypeInfo> {
+        self.types.get(&cinfo.type_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TypeInfo> {
+        self.types.values()
+    }
+}
+
+/// Reference to a component or resource of a reflected type.
+pub struct DynRef<'w> {
+    ptr: NonNull<u8>,
+    type_info: &'w TypeInfo,
+    _marker: PhantomData<&'w u8>,
+}
+
+impl<'w> DynRef<'w> {
+    // #SAFETY: The pointer needs to point to a valid value of the type,
+    // which is not mutably accessed during the lifetime.
+    pub(crate) unsafe fn new(ptr: NonNull<u8>, type_info: &'w TypeInfo) -> Self {
+        Self {
+            ptr,
+            type_info,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn type_info(&self) -> &'w TypeInfo {
+        self.type_info
+    }
+
+    pub fn field(&self, name: &str) -> Result<FieldValue, ReflectError> {
+        unsafe { self.type_info.read_field_ptr(self.ptr, name) }
+    }
+}
+
+/// Mutable reference to a component or resource of a reflected type.
+pub struct DynMut<'w> {
+    ptr: NonNull<u8>,
+    type_info: &'w TypeInfo,
+    _marker: PhantomData<&'w mut u8>,
+}
+
+impl<'w> DynMut<'w> {
+    // #SAFETY: The pointer needs to point to a valid value of the type,
+    // which is not accessed otherwise during the lifetime.
+    pub(crate) unsafe fn new(ptr: NonNull<u8>, type_info: &'w TypeInfo) -> Self {
+        Self {
+            ptr,
+            type_info,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn type_info(&self) -> &'w TypeInfo {
+        self.type_info
+    }
+
+    pub fn field(&self, name: &str) -> Result<FieldValue, ReflectError> {
+        unsafe { self.type_info.read_field_ptr(self.ptr, name) }
+    }
+
+    pub fn set_field(&mut self, name: &str, value: FieldValue) -> Result<(), ReflectError> {
+        let field = self
+            .type_info
+            .field(name)
+            .ok_or_else(|| ReflectError::UnknownField(name.to_string()))?;
+        TypeInfo::check_type(field, value)?;
+        unsafe { value.write(self.ptr.byte_add(field.offset)) };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ecs::{
+        prelude::*,
+        reflect::{FieldType, FieldValue, ReflectError, TypeInfo},
+        resource::ResourceId,
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+    impl Component for Position {}
+    crate::impl_reflect!(Position { x, y });
+
+    #[derive(Debug, PartialEq)]
+    struct Stats {
+        level: u8,
+        alive: bool,
+        target: EntityKey,
+        title: String,
+    }
+    impl Component for Stats {
+        const STORAGE: StorageTypes = StorageTypes::TableAoS;
+    }
+    crate::impl_reflect!(Stats {
+        level,
+        alive,
+        target,
+        title
+    });
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+    impl Component for Health {
+        const STORAGE: StorageTypes = StorageTypes::SparseSet;
+    }
+    crate::impl_reflect!(Health { 0 });
+
+    struct Gravity(f64);
+    crate::impl_reflect!(Gravity { 0 });
+
+    #[test]
+    fn test_component_dyn() {
+        let mut world = World::new();
+        world.register_reflect_component::<Position>();
+        world.register_reflect_component::<Stats>();
+        world.register_reflect_component::<Health>();
+        let target = world.add_entity(Position { x: 0., y: 0. });
+        let entity = world.add_entity((
+            Position { x: 1., y: 2. },
+            Stats {
+                level: 3,
+                alive: true,
+                target,
+                title: "knight".to_string(),
+            },
+            Health(40),
+        ));
+
+        let position = world.get_component_id::<Position>();
+        let stats = world.get_component_id::<Stats>();
+        let health = world.get_component_id::<Health>();
+
+        let pos = world.get_component_dyn(entity, position).unwrap();
+        assert_eq!(Ok(FieldValue::F32(2.)), pos.field("y"));
+        assert_eq!(
+            Err(ReflectError::UnknownField("z".to_string())),
+            pos.field("z")
+        );
+        let stats_ref = world.get_component_dyn(entity, stats).unwrap();
+        assert_eq!(Ok(FieldValue::U8(3)), stats_ref.field("level"));
+        assert_eq!(Ok(FieldValue::Bool(true)), stats_ref.field("alive"));
+        assert_eq!(Ok(FieldValue::EntityKey(target)), stats_ref.field("target"));
+        assert_eq!(
+            Err(ReflectError::OpaqueField("title".to_string())),
+            stats_ref.field("title")
+        );
+        assert_eq!(
+            Ok(FieldValue::U32(40)),
+            world.get_component_dyn(entity, health).unwrap().field("0")
+        );
+
+        let mut stats_mut = world.get_component_dyn_mut(entity, stats).unwrap();
+        stats_mut.set_field("level", FieldValue::U8(4)).unwrap();
+        assert!(matches!(
+            stats_mut.set_field("level", FieldValue::U32(5)),
+            Err(ReflectError::TypeMismatch { .. })
+        ));
+        world
+            .get_component_dyn_mut(entity, health)
+            .unwrap()
+            .set_field("0", FieldValue::U32(35))
+            .unwrap();
+        assert_eq!(
+            4,
+            world.get_single_component::<Stats>(entity).unwrap().level
+        );
+        assert_eq!(
+            "knight",
+            world.get_single_component::<Stats>(entity).unwrap().title
+        );
+        assert_eq!(
+            Some(&Health(35)),
+            world.get_single_component::<Health>(entity)
+        );
+
+        // components, which are not owned by the entity, are not returned
+        assert!(world.get_component_dyn(target, stats).is_none());
+    }
+
+    #[test]
+    fn test_resource_dyn() {
+        let mut world = World::new();
+        world.register_reflect_resource::<Gravity>();
+        world.add_resource(Gravity(9.81));
+        let gravity = ResourceId::of::<Gravity>();
+        assert_eq!(
+            Ok(FieldValue::F64(9.81)),
+            world.get_resource_dyn(gravity).unwrap().field("0")
+        );
+        world
+            .get_resource_dyn_mut(gravity)
+            .unwrap()
+            .set_field("0", FieldValue::F64(1.62))
+            .unwrap();
+        assert_eq!(1.62, world.get_resource::<Gravity>().unwrap().0);
+    }
+
+    #[test]
+    fn test_type_info_bytes() {
+        let type_info = TypeInfo::of::<Position>();
+        assert_eq!(FieldType::F32, type_info.field("x").unwrap().field_type);
+        let mut bytes = vec![0; type_info.layout.size()];
+        type_info
+            .write_field(&mut bytes, "y", FieldValue::F32(4.5))
+            .unwrap();
+        assert_eq!(Ok(FieldValue::F32(4.5)), type_info.read_field(&bytes, "y"));
+        assert_eq!(Ok(FieldValue::F32(0.)), type_info.read_field(&bytes, "x"));
+        assert_eq!(
+            Err(ReflectError::OutOfBounds("y".to_string())),
+            type_info.read_field(&bytes[..4], "y")
+        );
+
+        let type_info = TypeInfo::of::<Stats>();
+        let alive = type_info.field("alive").unwrap().offset;
+        let mut bytes = vec![0; type_info.layout.size()];
+        bytes[alive] = 2;
+        assert_eq!(
+            Err(ReflectError::InvalidValue("alive".to_string())),
+            type_info.read_field(&bytes, "alive")
+        );
+    }
+}
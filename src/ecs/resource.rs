@@ -1,13 +1,66 @@
 // resource.rs
 
-use std::any::TypeId;
+use std::{
+    any::TypeId,
+    hash::{Hash, Hasher},
+};
 
-#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Copy, Hash, Debug)]
-pub struct ResourceId(TypeId);
+use crate::ecs::world::WorldData;
+
+#[derive(Clone, Copy, Debug, Eq)]
+pub struct ResourceId {
+    type_id: TypeId,
+    // only used for readable error messages
+    name: &'static str,
+}
 
 impl ResourceId {
-    pub fn new(type_id: TypeId) -> Self {
-        Self(type_id)
+    pub fn of<T: 'static>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            name: std::any::type_name::<T>(),
+        }
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl Hash for ResourceId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.type_id.hash(state);
+    }
+}
+impl PartialEq for ResourceId {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_id.eq(&other.type_id)
+    }
+}
+impl PartialOrd for ResourceId {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for ResourceId {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.type_id.cmp(&other.type_id)
+    }
+}
+
+/// Creates a resource from the data of the world, e.g. from other resources.
+/// Every type implementing Default can be created from the world.
+pub trait FromWorld {
+    fn from_world(world_data: &mut WorldData) -> Self;
+}
+
+impl<T: Default> FromWorld for T {
+    fn from_world(_world_data: &mut WorldData) -> Self {
+        T::default()
     }
 }
 
@@ -0,0 +1,74 @@
+// schedule.rs
+
+/// Label of a schedule, which systems can be added to.
+/// Every schedule has its own precomputed execution batches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum ScheduleLabel {
+    /// Runs only once, during the first run of the world.
+    Startup,
+    /// Runs during every run of the world.
+    #[default]
+    Update,
+    /// Runs during every run of the world after the update schedule.
+    Cleanup,
+    /// Only runs when it is explicitly executed with `World::run_schedule`.
+    Custom(&'static str),
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ecs::{prelude::*, system::ResMut};
+
+    #[derive(Default)]
+    struct Log(Vec<&'static str>);
+
+    fn startup_system(mut log: ResMut<Log>) {
+        log.0.push("startup");
+    }
+
+    fn update_system(mut log: ResMut<Log>) {
+        log.0.push("update");
+    }
+
+    fn late_update_system(mut log: ResMut<Log>) {
+        log.0.push("late_update");
+    }
+
+    fn cleanup_system(mut log: ResMut<Log>) {
+        log.0.push("cleanup");
+    }
+
+    fn custom_system(mut log: ResMut<Log>) {
+        log.0.push("custom");
+    }
+
+    #[test]
+    fn test_schedule_labels() {
+        let mut world = World::new();
+        world.add_resource(Log::default());
+        world.add_systems(cleanup_system.in_schedule(ScheduleLabel::Cleanup));
+        // late_update_system is added to the update schedule through the constraint
+        world.add_systems(update_system.before(late_update_system));
+        world.add_systems(startup_system.in_schedule(ScheduleLabel::Startup));
+        world.add_systems(custom_system.in_schedule(ScheduleLabel::Custom("custom")));
+
+        world.init_and_run();
+        world.run();
+        world.run_schedule(ScheduleLabel::Custom("custom"));
+
+        let log = world.get_resource::<Log>().unwrap();
+        assert_eq!(
+            vec![
+                "startup",
+                "update",
+                "late_update",
+                "cleanup",
+                "update",
+                "late_update",
+                "cleanup",
+                "custom"
+            ],
+            log.0
+        );
+    }
+}
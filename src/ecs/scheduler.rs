@@ -3,124 +3,50 @@
 use std::{
     cell::UnsafeCell,
     collections::{HashMap, HashSet},
+    sync::Arc,
 };
 
 use crate::{
     ecs::{
+        component::ComponentId,
         ecs_dependency_graph::{EcsDependencyGraph, EcsEdge},
-        system::{System, SystemId, SystemParamId, Systems},
+        error::EcsError,
+        query::{QueryState, RefKind},
+        resource::ResourceId,
+        schedule::ScheduleLabel,
+        system::{
+            Constraint, RefType, System, SystemId, SystemParamId, Systems, requires_world_thread,
+            takes_resources,
+        },
         world::{SharedWorldData, WorldData},
     },
     utils::{ecs_id::EcsId, scoped_threadpool::ScopedThreadPool},
 };
 
 pub(crate) trait Scheduler {
-    fn init_schedule(&mut self, graph: &mut EcsDependencyGraph, systems: &Systems);
-    fn execute(&mut self, systems: &mut Systems, world_data: &mut UnsafeCell<WorldData>);
-}
-
-pub(crate) struct SingleThreadScheduler {
-    pub(crate) schedule: Vec<HashSet<SystemId>>,
-}
-
-impl SingleThreadScheduler {
-    pub(crate) fn new() -> Self {
-        Self {
-            schedule: Vec::new(),
-        }
-    }
-
-    fn execute_dynamically_planned(
-        schedule: &Vec<SystemId>,
+    fn init_schedule(
+        &mut self,
+        graph: &mut EcsDependencyGraph,
+        systems: &Systems,
+    ) -> Result<(), EcsError>;
+    fn execute(
+        &mut self,
+        schedule: &ScheduleLabel,
         systems: &mut Systems,
         world_data: &mut UnsafeCell<WorldData>,
-    ) {
-        let mut to_run_systems = schedule.clone();
-        let mut finished_systems: HashSet<SystemId> = HashSet::new();
-        let mut cur_sys_ind: usize = 0;
-
-        let max_not_exec_system_count = to_run_systems.len() * 2;
-        let mut not_exec_system_count = 0;
-
-        while to_run_systems.len() > 0 {
-            let sysid = to_run_systems[cur_sys_ind];
-            let run_system: bool = if let Some(constraint) = systems.constraints.get(&sysid) {
-                constraint.after.is_subset(&finished_systems)
-            } else {
-                true
-            };
-
-            if run_system {
-                systems.run_system(sysid, world_data.get_mut());
-                // execute commands after each sequential system run immediately
-                // so that the next running system has the most up to date world state
-                world_data.get_mut().execute_commands();
-                let removed_sysid = to_run_systems.remove(cur_sys_ind);
-                finished_systems.insert(removed_sysid);
-                if to_run_systems.len() >= cur_sys_ind && cur_sys_ind > 0 {
-                    cur_sys_ind = 0;
-                }
-                not_exec_system_count = 0;
-            } else {
-                cur_sys_ind += 1;
-                if cur_sys_ind >= to_run_systems.len() {
-                    cur_sys_ind = 0;
-                }
-                not_exec_system_count += 1;
-            }
-            if not_exec_system_count >= max_not_exec_system_count {
-                panic!("System scheduling loop detected!")
-            }
-        }
-    }
-}
-
-impl Scheduler for SingleThreadScheduler {
-    fn init_schedule(&mut self, _graph: &mut EcsDependencyGraph, systems: &Systems) {
-        if let Err(paths) = find_loops_for_all_system_constraints(systems) {
-            print_system_id_paths(systems, &paths);
-            panic!("System scheduling loop detected!")
-        }
-        self.schedule = build_constraint_based_schedule(systems);
-    }
-
-    fn execute(&mut self, systems: &mut Systems, world_data: &mut UnsafeCell<WorldData>) {
-        fn run_sys<'a>(
-            system: &mut dyn System,
-            sys_par_data: &Vec<SystemParamId>,
-            world_data: &SharedWorldData<'a>,
-        ) {
-            system.run(sys_par_data, world_data.0.get());
-        }
-
-        for batch in self.schedule.iter() {
-            for sys_id in batch.iter() {
-                let world_data = SharedWorldData(&*world_data);
-                let world_data = &world_data;
-                let sys_par_data = &systems.system_param_data;
-                let system = &mut systems.system_vec[sys_id.id_usize()];
-                run_sys(
-                    system.as_mut(),
-                    &sys_par_data.get(&sys_id).unwrap(),
-                    &world_data,
-                );
-            }
-
-            // execute commands after all systems of one batch have run
-            world_data.get_mut().execute_commands();
-        }
-    }
+    );
 }
 
 pub(crate) struct ParallelScheduler {
-    schedule: Vec<Vec<HashSet<SystemId>>>,
+    // shared with the running schedule, which only copies it, if it gets rebuilt during the run
+    pub(crate) schedule: HashMap<ScheduleLabel, Arc<Vec<Vec<HashSet<SystemId>>>>>,
     thread_pool: ScopedThreadPool,
 }
 
 impl ParallelScheduler {
     pub(crate) fn new(thread_count: usize) -> Self {
         Self {
-            schedule: Vec::new(),
+            schedule: HashMap::new(),
             thread_pool: ScopedThreadPool::new(thread_count),
         }
     }
@@ -145,21 +71,79 @@ impl ParallelScheduler {
         }
     }
 
+    // schedule systems in parallel according to their mutable and immutable Systemparams
+    fn build_schedules(&mut self, graph: &mut EcsDependencyGraph, systems: &Systems) {
+        self.schedule.clear();
+        for (label, system_ids) in systems.schedules.iter() {
+            let schd = Self::build_parallel_based_schedule(graph, systems, system_ids);
+            self.schedule.insert(*label, schd.into());
+        }
+        graph.schedule_outdated = false;
+        Self::update_parallel_query_conflicts(graph, &self.schedule);
+    }
+
+    // Queries of systems in the same parallel batch can conflict, as long as they don't match
+    // the same archetype. These pairs are stored, so that only a new archetype matched by both
+    // queries outdates the schedules.
+    fn update_parallel_query_conflicts(
+        graph: &mut EcsDependencyGraph,
+        schedules: &HashMap<ScheduleLabel, Arc<Vec<Vec<HashSet<SystemId>>>>>,
+    ) {
+        let mut parallel_query_conflicts = HashSet::new();
+        for parallel_batch in schedules.values().flat_map(|schd| schd.iter().flatten()) {
+            let query_keys: Vec<(SystemId, u32)> = parallel_batch
+                .iter()
+                .filter_map(|sys_id| graph.system_keys.get(sys_id).map(|key| (*sys_id, *key)))
+                .flat_map(|(sys_id, key)| {
+                    graph.systems[key as usize]
+                        .query_edges
+                        .keys()
+                        .map(move |query_key| (sys_id, *query_key))
+                })
+                .collect();
+            for (n, (sys_id1, query_key1)) in query_keys.iter().enumerate() {
+                for (sys_id2, query_key2) in query_keys[n + 1..].iter() {
+                    if sys_id1 != sys_id2 && graph.queries_access_conflict(*query_key1, *query_key2)
+                    {
+                        parallel_query_conflicts
+                            .insert((*query_key1.min(query_key2), *query_key1.max(query_key2)));
+                    }
+                }
+            }
+        }
+        graph.parallel_query_conflicts = parallel_query_conflicts;
+    }
+
     fn build_parallel_based_schedule(
         graph: &mut EcsDependencyGraph,
         systems: &Systems,
+        schedule_system_ids: &HashSet<SystemId>,
     ) -> Vec<Vec<HashSet<SystemId>>> {
-        let parallel_schedule = build_constraint_based_schedule(systems)
+        let parallel_schedule = build_constraint_based_schedule(systems, schedule_system_ids)
             .into_iter()
-            .map(|batch| Self::create_parallizable_systems_schedule(graph, batch))
+            .map(|batch| Self::create_parallizable_systems_schedule(graph, systems, batch))
             .collect();
         parallel_schedule
     }
 
     fn create_parallizable_systems_schedule(
         graph: &mut EcsDependencyGraph,
+        systems: &Systems,
         mut systems_to_check: HashSet<SystemId>,
     ) -> Vec<HashSet<SystemId>> {
+        // exclusive systems and systems, which take resources out of the world, get their own batch
+        let exclusive_systems: Vec<SystemId> = systems_to_check
+            .iter()
+            .copied()
+            .filter(|sys_id| {
+                systems.get_system(*sys_id).is_exclusive()
+                    || takes_resources(systems.get_sys_param_data(sys_id))
+            })
+            .collect();
+        exclusive_systems.iter().for_each(|sys_id| {
+            systems_to_check.remove(sys_id);
+        });
+
         let conflicting_systems_map: HashMap<SystemId, HashSet<SystemId>> = systems_to_check
             .iter()
             .map(|sys_id| (*sys_id, Self::find_conflicting_systems(graph, *sys_id)))
@@ -187,14 +171,21 @@ impl ParallelScheduler {
             });
             schedule.push(parallel_systems);
         }
+        schedule.extend(
+            exclusive_systems
+                .into_iter()
+                .map(|sys_id| HashSet::from([sys_id])),
+        );
         schedule
     }
 
     /// Finding conflicting systems:
-    /// - find excl and shared res/comps sets for every system
+    /// - find excl and shared res sets for every system
     /// - per system find all to res or comps connected systems
-    /// - check if other systems access same res/comps mutably
+    /// - check if other systems access same res mutably
     /// or one mut and the other immutable
+    /// - check if queries of other systems access same comps mutably
+    /// or one mut and the other immutable and can match the same archetype
     /// - create set of conflicting systems for one system
     fn find_conflicting_systems(
         graph: &mut EcsDependencyGraph,
@@ -205,7 +196,7 @@ impl ParallelScheduler {
         let sys_res: &HashMap<u32, EcsEdge> = &system_node.resource_edges;
         let (res_excl, res_shared) = Self::create_excl_shared_sets(sys_res);
         let sys_comps: &HashMap<u32, EcsEdge> = &system_node.component_edges;
-        let (comp_excl, comp_shared) = Self::create_excl_shared_sets(sys_comps);
+        let sys_queries: Vec<u32> = system_node.query_edges.keys().copied().collect();
 
         let mut conn_sys_ids: HashSet<SystemId> = HashSet::new();
         // find systems connected through components in query
@@ -227,22 +218,17 @@ impl ParallelScheduler {
             let system_node = &graph.systems[system_row_id];
             let sys_res: &HashMap<u32, EcsEdge> = &system_node.resource_edges;
             let (res_excl2, res_shared2) = Self::create_excl_shared_sets(sys_res);
-            let sys_comps: &HashMap<u32, EcsEdge> = &system_node.component_edges;
-            let (comp_excl2, comp_shared2) = Self::create_excl_shared_sets(sys_comps);
 
-            for comp_id in comp_excl.iter() {
-                if comp_excl2.contains(comp_id) {
-                    conflict_systems.insert(*sys_id);
-                    continue 'systems_loop;
-                } else if comp_shared2.contains(comp_id) {
-                    conflict_systems.insert(*sys_id);
-                    continue 'systems_loop;
-                }
-            }
-            for comp_id in comp_shared.iter() {
-                if comp_excl2.contains(comp_id) {
-                    conflict_systems.insert(*sys_id);
-                    continue 'systems_loop;
+            // component accesses of queries only conflict,
+            // if the queries can match entities of the same archetype
+            for query_key in sys_queries.iter() {
+                for query_key2 in system_node.query_edges.keys() {
+                    if graph.queries_access_conflict(*query_key, *query_key2)
+                        && graph.queries_share_archetype(*query_key, *query_key2)
+                    {
+                        conflict_systems.insert(*sys_id);
+                        continue 'systems_loop;
+                    }
                 }
             }
 
@@ -280,18 +266,25 @@ impl ParallelScheduler {
 }
 
 impl Scheduler for ParallelScheduler {
-    fn init_schedule(&mut self, graph: &mut EcsDependencyGraph, systems: &Systems) {
-        if let Err(paths) = find_loops_for_all_system_constraints(systems) {
-            print_system_id_paths(systems, &paths);
-            panic!("System scheduling loop detected!")
-        }
+    fn init_schedule(
+        &mut self,
+        graph: &mut EcsDependencyGraph,
+        systems: &Systems,
+    ) -> Result<(), EcsError> {
+        find_loops_for_all_system_constraints(systems)?;
 
-        // schedule systems in parallel according to their mutable and immutable Systemparams
-        let schd = Self::build_parallel_based_schedule(graph, systems);
-        Self::print_schedule(systems, &schd);
-        self.schedule = schd;
+        self.build_schedules(graph, systems);
+        for schd in self.schedule.values() {
+            Self::print_schedule(systems, schd);
+        }
+        Ok(())
     }
-    fn execute(&mut self, systems: &mut Systems, world_data: &mut UnsafeCell<WorldData>) {
+    fn execute(
+        &mut self,
+        schedule: &ScheduleLabel,
+        systems: &mut Systems,
+        world_data: &mut UnsafeCell<WorldData>,
+    ) {
         fn run_sys<'a>(
             system: &mut dyn System,
             sys_par_data: &Vec<SystemParamId>,
@@ -300,15 +293,63 @@ impl Scheduler for ParallelScheduler {
             system.run(sys_par_data, world_data.0.get());
         }
 
-        let sys_par_data = &systems.system_param_data;
-
-        for batch in self.schedule.iter() {
-            for batch in batch.iter() {
+        // new archetypes, which were created since the last run, can make queries overlap
+        if world_data.get_mut().get_depend_graph().schedule_outdated {
+            self.build_schedules(world_data.get_mut().get_depend_graph_mut(), systems);
+        }
+        let Some(schedule) = self.schedule.get(schedule) else {
+            return;
+        };
+        let mut schedule = Arc::clone(schedule);
+        let mut condition_results = HashMap::new();
+
+        for batch_index in 0..schedule.len() {
+            let mut parallel_index = 0;
+            while parallel_index < schedule[batch_index].len() {
+                // run conditions are evaluated before the systems of a batch get dispatched
+                let batch: HashSet<SystemId> = schedule[batch_index][parallel_index]
+                    .iter()
+                    .copied()
+                    .filter(|sys_id| {
+                        required_resources_present(
+                            world_data.get_mut(),
+                            systems.get_sys_param_data(sys_id),
+                        ) && systems.conditions_met(
+                            *sys_id,
+                            world_data.get(),
+                            &mut condition_results,
+                        )
+                    })
+                    .collect();
+                parallel_index += 1;
+                let exclusive_sys_id = batch
+                    .iter()
+                    .copied()
+                    .find(|sys_id| systems.get_system(*sys_id).is_exclusive());
+                if let Some(sys_id) = exclusive_sys_id {
+                    // exclusive systems see the commands of the systems before
+                    // and their own changes are visible to the systems after
+                    world_data.get_mut().execute_commands();
+                    systems.run_system(sys_id, world_data.get_mut());
+                    // entities, which were added directly, are not covered by the barriers
+                    world_data.get_mut().get_entities().reset_barriers();
+                    world_data.get_mut().execute_commands();
+                    self.update_outdated_schedule(
+                        world_data,
+                        systems,
+                        &mut schedule,
+                        batch_index,
+                        parallel_index,
+                    );
+                    continue;
+                }
+                let sys_par_data = &systems.system_param_data;
                 {
                     let world_data = SharedWorldData(&*world_data);
                     let world_data = &world_data;
                     //std::thread::scope(|s| {
                     self.thread_pool.scope(|s| {
+                        let mut world_thread_systems = Vec::new();
                         for (i, sys) in systems
                             .system_vec
                             .iter_mut()
@@ -316,49 +357,321 @@ impl Scheduler for ParallelScheduler {
                             .filter(|(i, _s)| batch.contains(&SystemId::from(i)))
                         {
                             let sys_id = SystemId::from(i);
+                            let sys_par_data = sys_par_data.get(&sys_id).unwrap();
+                            // systems with non send resources stay on the thread, which owns the world
+                            if requires_world_thread(sys_par_data) {
+                                world_thread_systems.push((sys, sys_par_data));
+                                continue;
+                            }
                             s.spawn(move || {
-                                run_sys(
-                                    sys.as_mut(),
-                                    &sys_par_data.get(&sys_id).unwrap(),
-                                    &world_data,
-                                );
+                                run_sys(sys.as_mut(), sys_par_data, &world_data);
                             });
                         }
+                        // the scope closure runs on the calling thread, parallel to the spawned systems
+                        for (sys, sys_par_data) in world_thread_systems {
+                            run_sys(sys.as_mut(), sys_par_data, world_data);
+                        }
                     });
                 }
             }
             // execute commands after all systems of one batch have run
             world_data.get_mut().execute_commands();
+            self.update_outdated_schedule(world_data, systems, &mut schedule, batch_index + 1, 0);
         }
     }
 }
 
-fn find_conflicts_in_systems_params(
-    graph: &EcsDependencyGraph,
+impl ParallelScheduler {
+    // Commands and exclusive systems can create archetypes, which make queries overlap.
+    // The remaining systems of the current run are scheduled again in that case,
+    // but keep their constraint batches, so that no system runs twice or gets skipped.
+    fn update_outdated_schedule(
+        &mut self,
+        world_data: &mut UnsafeCell<WorldData>,
+        systems: &Systems,
+        schedule: &mut Arc<Vec<Vec<HashSet<SystemId>>>>,
+        batch_index: usize,
+        parallel_index: usize,
+    ) {
+        if !world_data.get_mut().get_depend_graph().schedule_outdated {
+            return;
+        }
+        let graph = world_data.get_mut().get_depend_graph_mut();
+        self.build_schedules(graph, systems);
+        let remaining_schedule = &mut Arc::make_mut(schedule)[batch_index..];
+        for (i, batch) in remaining_schedule.iter_mut().enumerate() {
+            let first_index = if i == 0 { parallel_index } else { 0 };
+            let batch_systems: HashSet<SystemId> = batch.drain(first_index..).flatten().collect();
+            batch.extend(Self::create_parallizable_systems_schedule(
+                graph,
+                systems,
+                batch_systems,
+            ));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemParamConflictKind {
+    Resource,
+    Component,
+    Commands,
+}
+
+/// Conflicting params of a single system, which were found during the initialization.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemParamConflict {
+    pub kind: SystemParamConflictKind,
+    pub system_name: String,
+    /// Descriptions of the conflicting params together with their position in the system.
+    pub params: Vec<String>,
+    /// Names of the types, which are accessed by the conflicting params.
+    pub conflicting_types: Vec<String>,
+}
+
+impl std::fmt::Display for SystemParamConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.kind {
+            SystemParamConflictKind::Resource => {
+                "A system cannot have multiple params of the same resource type."
+            }
+            SystemParamConflictKind::Component => {
+                "A system cannot have multiple params of the same component type."
+            }
+            SystemParamConflictKind::Commands => "A system cannot have multiple Commands params.",
+        };
+        write!(
+            f,
+            "{reason}\nsystem: {}\nparams: {}\nconflicting types: {}",
+            self.system_name,
+            self.params.join(", "),
+            self.conflicting_types.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for SystemParamConflict {}
+
+// Systems are skipped, while one of their required resources is missing,
+// e.g. after it was consumed by a ResOwned param.
+fn required_resources_present(world_data: &WorldData, system_param_ids: &[SystemParamId]) -> bool {
+    let depend_graph = world_data.get_depend_graph();
+    system_param_ids.iter().all(|sys_prm_id| match sys_prm_id {
+        SystemParamId::Resource(resource_id, _)
+        | SystemParamId::NonSendResource(resource_id, _) => {
+            depend_graph.contains_resource(*resource_id)
+        }
+        _ => true,
+    })
+}
+
+/// Validates the params of an initialized system. Conflicts are:
+/// - multiple params of the same resource type
+/// - multiple Commands params
+/// - multiple accesses to the same component inside of a query,
+///   if one of them is exclusive
+/// - multiple queries accessing the same component, if one of them is exclusive
+///   and the queries can match the same entities
+pub(crate) fn find_conflicts_in_systems_params(
+    world_data: &WorldData,
     systems: &Systems,
     system_id: SystemId,
-) -> Result<(), String> {
-    //TODO:
-    // conflicts between resource params can be found during conneting of multiple params of same resource type
-    // edges to a system inside of dependency graph
-    let system_row_id = graph
-        .system_keys
-        .get(&system_id)
-        .expect("System should exist in graph at this point.");
-    let system_node = &graph.systems[*system_row_id as usize];
-    //system_node.//resource_edges.
-
-    // find conflicts between components in query params: multiple of same component
+) -> Result<(), SystemParamConflict> {
+    let system_param_ids = systems.get_sys_param_data(&system_id);
+    let conflict = |kind, params: &[usize], conflicting_types: Vec<String>| SystemParamConflict {
+        kind,
+        system_name: systems.get_system(system_id).system_name().to_string(),
+        params: params
+            .iter()
+            .map(|i| describe_system_param(world_data, *i, &system_param_ids[*i]))
+            .collect(),
+        conflicting_types,
+    };
+
+    let mut resource_params: HashMap<ResourceId, usize> = HashMap::new();
+    let mut commands_param: Option<usize> = None;
+    let mut query_params: Vec<(usize, &QueryState)> = Vec::new();
+    for (i, sys_prm_id) in system_param_ids.iter().enumerate() {
+        match sys_prm_id {
+            SystemParamId::Resource(resource_id, _)
+            | SystemParamId::OptionalResource(resource_id, _)
+            | SystemParamId::NonSendResource(resource_id, _)
+            | SystemParamId::EventReader(resource_id, _) => {
+                if let Some(other) = resource_params.insert(*resource_id, i) {
+                    return Err(conflict(
+                        SystemParamConflictKind::Resource,
+                        &[other, i],
+                        vec![resource_id.name().to_string()],
+                    ));
+                }
+            }
+            SystemParamId::Commands => {
+                if let Some(other) = commands_param.replace(i) {
+                    return Err(conflict(
+                        SystemParamConflictKind::Commands,
+                        &[other, i],
+                        Vec::new(),
+                    ));
+                }
+            }
+            SystemParamId::Query(qid) => {
+                let query_state = &world_data.get_query_data()[qid.id_usize()];
+                let conflicting_types: Vec<String> = query_state
+                    .conflicting_comp_ids()
+                    .map(|comp_id| component_name(world_data, comp_id))
+                    .collect();
+                if !conflicting_types.is_empty() {
+                    return Err(conflict(
+                        SystemParamConflictKind::Component,
+                        &[i],
+                        conflicting_types,
+                    ));
+                }
+                query_params.push((i, query_state));
+            }
+            SystemParamId::Local(..) | SystemParamId::NotRelevant => {}
+        }
+    }
+
+    for (n, (i1, q1)) in query_params.iter().enumerate() {
+        for (i2, q2) in query_params.iter().skip(n + 1) {
+            if q1.is_disjoint_with(q2) {
+                continue;
+            }
+            let conflicting_types: Vec<String> = q1
+                .query_param_meta_data
+                .iter()
+                .filter(|qpmd1| {
+                    q2.query_param_meta_data.iter().any(|qpmd2| {
+                        qpmd1.comp_id == qpmd2.comp_id
+                            && (qpmd1.ref_kind == RefKind::Exclusive
+                                || qpmd2.ref_kind == RefKind::Exclusive)
+                    })
+                })
+                .map(|qpmd| component_name(world_data, qpmd.comp_id))
+                .collect();
+            if !conflicting_types.is_empty() {
+                return Err(conflict(
+                    SystemParamConflictKind::Component,
+                    &[*i1, *i2],
+                    conflicting_types,
+                ));
+            }
+        }
+    }
     Ok(())
 }
 
-fn build_constraint_based_schedule(systems: &Systems) -> Vec<HashSet<SystemId>> {
+/// Checks, that all resources, which are required by the params of an initialized system,
+/// were added to the world. Optional resource params are not taken into account.
+pub(crate) fn find_missing_resources_of_system(
+    world_data: &WorldData,
+    systems: &Systems,
+    system_id: SystemId,
+) -> Result<(), EcsError> {
+    for sys_prm_id in systems.get_sys_param_data(&system_id) {
+        let (resource_id, resources) = match sys_prm_id {
+            SystemParamId::Resource(resource_id, _) => (resource_id, &world_data.resources),
+            SystemParamId::NonSendResource(resource_id, _) => {
+                (resource_id, &world_data.non_send_resources)
+            }
+            _ => continue,
+        };
+        if !resources.contains(&resource_id.type_id()) {
+            return Err(EcsError::MissingResource {
+                system_name: systems.get_system(system_id).system_name().to_string(),
+                resource_name: resource_id.name(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn component_name(world_data: &WorldData, comp_id: ComponentId) -> String {
+    world_data.get_components()[comp_id.id_usize()]
+        .name
+        .to_string()
+}
+
+fn describe_system_param(
+    world_data: &WorldData,
+    index: usize,
+    sys_prm_id: &SystemParamId,
+) -> String {
+    let description = match sys_prm_id {
+        SystemParamId::Resource(resource_id, RefType::Shared) => {
+            format!("Res<{}>", resource_id.name())
+        }
+        SystemParamId::Resource(resource_id, RefType::Exclusive) => {
+            format!("ResMut<{}>", resource_id.name())
+        }
+        SystemParamId::Resource(resource_id, RefType::Owned) => {
+            format!("ResOwned<{}>", resource_id.name())
+        }
+        SystemParamId::OptionalResource(resource_id, RefType::Shared) => {
+            format!("Option<Res<{}>>", resource_id.name())
+        }
+        SystemParamId::OptionalResource(resource_id, _) => {
+            format!("Option<ResMut<{}>>", resource_id.name())
+        }
+        SystemParamId::NonSendResource(resource_id, RefType::Shared) => {
+            format!("NonSend<{}>", resource_id.name())
+        }
+        SystemParamId::NonSendResource(resource_id, _) => {
+            format!("NonSendMut<{}>", resource_id.name())
+        }
+        SystemParamId::EventReader(resource_id, _) => {
+            format!("EventReader of {}", resource_id.name())
+        }
+        SystemParamId::Query(qid) => {
+            let query_state = &world_data.get_query_data()[qid.id_usize()];
+            let params: Vec<String> = query_state
+                .query_param_meta_data
+                .iter()
+                .map(|qpmd| {
+                    let name = component_name(world_data, qpmd.comp_id);
+                    match qpmd.ref_kind {
+                        RefKind::Shared => format!("&{name}"),
+                        RefKind::Exclusive => format!("&mut {name}"),
+                    }
+                })
+                .collect();
+            format!("Query<({})>", params.join(", "))
+        }
+        SystemParamId::Local(..) => "Local".to_string(),
+        SystemParamId::Commands => "Commands".to_string(),
+        SystemParamId::NotRelevant => "unknown".to_string(),
+    };
+    format!("param {index}: {description}")
+}
+
+/// Builds the constraint batches for the systems of a single schedule.
+/// Constraints to systems of other schedules are ignored.
+fn build_constraint_based_schedule(
+    systems: &Systems,
+    schedule_system_ids: &HashSet<SystemId>,
+) -> Vec<HashSet<SystemId>> {
     let mut schd: Vec<HashSet<SystemId>> = Vec::new();
+    let constraints: HashMap<SystemId, Constraint> = schedule_system_ids
+        .iter()
+        .map(|s| {
+            let mut constraint = Constraint::new(*s);
+            if let Some(c) = systems.get_constraint(s) {
+                constraint.after = c.after.intersection(schedule_system_ids).copied().collect();
+                constraint.before = c
+                    .before
+                    .intersection(schedule_system_ids)
+                    .copied()
+                    .collect();
+            }
+            (*s, constraint)
+        })
+        .collect();
 
     //find constraint roots and unconstraint systems
     let mut roots: HashSet<SystemId> = HashSet::new();
     let mut unconstrained: HashSet<SystemId> = HashSet::new();
-    for (s, c) in &systems.constraints {
+    for (s, c) in &constraints {
         if c.after.is_empty() && !c.before.is_empty() {
             roots.insert(*s);
         } else if c.after.is_empty() && c.before.is_empty() {
@@ -369,7 +682,7 @@ fn build_constraint_based_schedule(systems: &Systems) -> Vec<HashSet<SystemId>>
     schd.push(roots);
     let mut next_batch: HashSet<SystemId> = HashSet::new();
     for s in schd[0].iter() {
-        if let Some(constraint) = systems.get_constraint(s) {
+        if let Some(constraint) = constraints.get(s) {
             next_batch.extend(constraint.before.iter());
         }
     }
@@ -380,7 +693,7 @@ fn build_constraint_based_schedule(systems: &Systems) -> Vec<HashSet<SystemId>>
     loop {
         let mut next_batch: HashSet<SystemId> = HashSet::new();
         for s1 in schd[batch_index].iter() {
-            if let Some(s1_c) = systems.get_constraint(s1) {
+            if let Some(s1_c) = constraints.get(s1) {
                 for s2 in schd[batch_index].iter() {
                     if s1 != s2 {
                         if s1_c.before.contains(s2) {
@@ -392,7 +705,7 @@ fn build_constraint_based_schedule(systems: &Systems) -> Vec<HashSet<SystemId>>
         }
 
         for s in schd[batch_index].iter() {
-            if let Some(constraint) = systems.get_constraint(s) {
+            if let Some(constraint) = constraints.get(s) {
                 next_batch.extend(constraint.before.iter());
             }
         }
@@ -433,18 +746,29 @@ fn build_constraint_based_schedule(systems: &Systems) -> Vec<HashSet<SystemId>>
     schd
 }
 
-fn find_loops_for_all_system_constraints(systems: &Systems) -> Result<(), Vec<Vec<SystemId>>> {
-    let mut result = Ok(());
+fn find_loops_for_all_system_constraints(systems: &Systems) -> Result<(), EcsError> {
+    let mut system_id_paths = Vec::new();
     for s in 0..systems.system_vec.len() {
         let mut sys_id_path = Vec::new();
         if find_loops_in_system_constraints_rec(systems, &mut sys_id_path, s.into()) {
-            match result {
-                Ok(()) => result = Err(vec![sys_id_path]),
-                Err(ref mut sys_id_paths) => sys_id_paths.push(sys_id_path),
-            }
+            system_id_paths.push(sys_id_path);
         }
     }
-    result
+    if system_id_paths.is_empty() {
+        return Ok(());
+    }
+    let system_name_paths = system_id_paths
+        .iter()
+        .map(|path| {
+            path.iter()
+                .map(|s| systems.get_system(*s).system_name().to_string())
+                .collect()
+        })
+        .collect();
+    Err(EcsError::SystemConstraintLoop {
+        system_id_paths,
+        system_name_paths,
+    })
 }
 
 fn find_loops_in_system_constraints_rec(
@@ -468,23 +792,16 @@ fn find_loops_in_system_constraints_rec(
     false
 }
 
-fn print_system_id_paths(systems: &Systems, system_id_paths: &[Vec<SystemId>]) {
-    println!("Loops in system constraints were detected:");
-    for path in system_id_paths {
-        let path_str: String = path
-            .iter()
-            .map(|s| systems.get_system(*s).system_name())
-            .collect::<Vec<&str>>()
-            .join(" -> ");
-        println!("{path_str}");
-    }
-}
-
 #[cfg(test)]
 mod test {
+    use std::sync::Arc;
+
     use crate::ecs::{
+        component::Component,
+        query::{Query, query_filter::With},
+        schedule::ScheduleLabel,
         scheduler::ParallelScheduler,
-        system::{Res, ResMut},
+        system::{Res, ResMut, builder::IntoSystemConfig},
         world::World,
     };
 
@@ -535,9 +852,8 @@ mod test {
 
         world.init_systems();
         world.run();
-        ParallelScheduler::print_schedule(&world.systems, &world.scheduler.schedule);
-
-        let scheduler = &world.scheduler.schedule;
+        let scheduler = &world.scheduler.schedule[&ScheduleLabel::Update];
+        ParallelScheduler::print_schedule(&world.systems, scheduler);
 
         let set_with_sysid1 = scheduler
             .iter()
@@ -562,4 +878,68 @@ mod test {
 
         assert!(!set_with_sysid5.contains(&sysid4));
     }
+
+    struct Pos(usize);
+    impl Component for Pos {}
+    struct MarkerA;
+    impl Component for MarkerA {}
+    struct MarkerB;
+    impl Component for MarkerB {}
+
+    fn write_pos_a_system(mut query: Query<&mut Pos, With<MarkerA>>) {
+        for mut pos in query.iter() {
+            pos.0 += 1;
+        }
+    }
+
+    fn read_pos_b_system(mut query: Query<&Pos, With<MarkerB>>) {
+        for pos in query.iter() {
+            assert!(pos.0 < usize::MAX);
+        }
+    }
+
+    #[test]
+    fn test_schedule_rebuilt_for_overlapping_archetype() {
+        let mut world = World::new();
+        world.add_entity((Pos(0), MarkerA));
+        world.add_entity((Pos(0), MarkerB));
+        let write_sys_id = world.add_systems(write_pos_a_system).pop().unwrap();
+        let read_sys_id = world.add_systems(read_pos_b_system).pop().unwrap();
+        world.init_systems();
+
+        let same_batch = |world: &World| {
+            world.scheduler.schedule[&ScheduleLabel::Update]
+                .iter()
+                .flatten()
+                .any(|batch| batch.contains(&write_sys_id) && batch.contains(&read_sys_id))
+        };
+        // the queries can't match the same entities yet
+        assert!(same_batch(&world));
+
+        world.add_entity((Pos(0), MarkerA, MarkerB));
+        world.run();
+        assert!(!same_batch(&world));
+    }
+
+    #[test]
+    fn test_schedule_kept_for_unrelated_archetype() {
+        let mut world = World::new();
+        world.add_entity((Pos(0), MarkerA));
+        world.add_entity((Pos(0), MarkerB));
+        world.add_systems((write_pos_a_system, read_pos_b_system).chain());
+        world.init_and_run();
+        let mut pos_query = world.query::<&Pos, ()>();
+        let schedule = Arc::clone(&world.scheduler.schedule[&ScheduleLabel::Update]);
+
+        // matched by both system queries, which never run in parallel,
+        // and by a query state outside of the systems
+        world.add_entity((Pos(0), MarkerA, MarkerB));
+        assert_eq!(3, pos_query.iter(&mut world).count());
+        assert!(!world.data.get_mut().get_depend_graph().schedule_outdated);
+        world.run();
+        assert!(Arc::ptr_eq(
+            &schedule,
+            &world.scheduler.schedule[&ScheduleLabel::Update]
+        ));
+    }
 }
@@ -0,0 +1,523 @@
+// snapshot.rs
+
+use std::{
+    any::{Any, TypeId},
+    io::{self, Read, Write},
+};
+
+use crate::ecs::{component::Component, entity::EntityKey, world::WorldData};
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"ECSS";
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Component, which can be written to and read from world snapshots.
+///
+/// Components are identified inside of snapshots by their name instead of their TypeId,
+/// so the name must stay the same between builds, that read and write a snapshot.
+pub trait SerializableComponent: Component {
+    const NAME: &'static str;
+    fn serialize(&self, writer: &mut dyn Write) -> io::Result<()>;
+    fn deserialize(reader: &mut dyn Read) -> io::Result<Self>;
+}
+
+/// Resource, which can be written to and read from world snapshots.
+pub trait SerializableResource: 'static + Sized {
+    const NAME: &'static str;
+    fn serialize(&self, writer: &mut dyn Write) -> io::Result<()>;
+    fn deserialize(reader: &mut dyn Read) -> io::Result<Self>;
+}
+
+pub fn write_u32(writer: &mut dyn Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn read_u32(reader: &mut dyn Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn write_str(writer: &mut dyn Write, value: &str) -> io::Result<()> {
+    write_len(writer, value.len())?;
+    writer.write_all(value.as_bytes())
+}
+
+pub fn read_string(reader: &mut dyn Read) -> io::Result<String> {
+    let bytes = read_bytes(reader)?;
+    String::from_utf8(bytes).map_err(|err| invalid_data(err.to_string()))
+}
+
+fn write_len(writer: &mut dyn Write, len: usize) -> io::Result<()> {
+    let len: u32 = len
+        .try_into()
+        .map_err(|_| invalid_data("Length is above u32 max!".to_string()))?;
+    write_u32(writer, len)
+}
+
+fn read_bytes(reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+    let len = read_u32(reader)? as usize;
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Type erased functions of a serializable component type.
+#[derive(Clone, Copy)]
+struct ComponentSerializer {
+    name: &'static str,
+    type_id: TypeId,
+    // returns false, if the entity does not own the component
+    serialize: fn(&mut WorldData, EntityKey, &mut dyn Write) -> io::Result<bool>,
+    deserialize: fn(&mut dyn Read) -> io::Result<Box<dyn Any>>,
+    insert: fn(&mut WorldData, EntityKey, Box<dyn Any>),
+}
+
+// Type erased functions of a serializable resource type.
+#[derive(Clone, Copy)]
+struct ResourceSerializer {
+    name: &'static str,
+    type_id: TypeId,
+    // returns false, if the resource does not exist
+    serialize: fn(&WorldData, &mut dyn Write) -> io::Result<bool>,
+    deserialize: fn(&mut dyn Read) -> io::Result<Box<dyn Any>>,
+    insert: fn(&mut WorldData, Box<dyn Any>),
+}
+
+/// Serializable component and resource types of a world, in their order of registration.
+#[derive(Default)]
+pub(crate) struct SnapshotRegistry {
+    components: Vec<ComponentSerializer>,
+    resources: Vec<ResourceSerializer>,
+}
+
+impl SnapshotRegistry {
+    // Registering the same type twice is ignored,
+    // while two types with the same name could not be told apart inside of snapshots.
+    pub(crate) fn register_component<T: SerializableComponent>(&mut self) {
+        match self.components.iter().find(|s| s.name == T::NAME) {
+            Some(s) if s.type_id == TypeId::of::<T>() => {}
+            Some(_) => panic!(
+                "Another component is already registered with the name {}.",
+                T::NAME
+            ),
+            None => self.components.push(ComponentSerializer {
+                name: T::NAME,
+                type_id: TypeId::of::<T>(),
+                serialize: serialize_component::<T>,
+                deserialize: deserialize_component::<T>,
+                insert: insert_component::<T>,
+            }),
+        }
+    }
+
+    pub(crate) fn register_resource<T: SerializableResource>(&mut self) {
+        match self.resources.iter().find(|s| s.name == T::NAME) {
+            Some(s) if s.type_id == TypeId::of::<T>() => {}
+            Some(_) => panic!(
+                "Another resource is already registered with the name {}.",
+                T::NAME
+            ),
+            None => self.resources.push(ResourceSerializer {
+                name: T::NAME,
+                type_id: TypeId::of::<T>(),
+                serialize: serialize_resource::<T>,
+                deserialize: deserialize_resource::<T>,
+                insert: insert_resource::<T>,
+            }),
+        }
+    }
+}
+
+fn serialize_component<T: SerializableComponent>(
+    world_data: &mut WorldData,
+    entity_key: EntityKey,
+    writer: &mut dyn Write,
+) -> io::Result<bool> {
+    match world_data.get_single_component::<T>(entity_key) {
+        Some(component) => component.serialize(writer).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+fn deserialize_component<T: SerializableComponent>(
+    reader: &mut dyn Read,
+) -> io::Result<Box<dyn Any>> {
+    Ok(Box::new(T::deserialize(reader)?))
+}
+
+// The first component of an entity spawns it with its snapshot key.
+fn insert_component<T: SerializableComponent>(
+    world_data: &mut WorldData,
+    entity_key: EntityKey,
+    component: Box<dyn Any>,
+) {
+    let component = *component
+        .downcast::<T>()
+        .expect("Deserialized component should be of the registered type.");
+    if world_data.get_entities().get(entity_key).is_some() {
+        world_data.add_component_to_entity(entity_key, component, true);
+    } else {
+        world_data.add_entity_with_reserved_key(entity_key, component);
+    }
+}
+
+fn serialize_resource<T: SerializableResource>(
+    world_data: &WorldData,
+    writer: &mut dyn Write,
+) -> io::Result<bool> {
+    match world_data.get_resource::<T>() {
+        Some(resource) => resource.serialize(writer).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+fn deserialize_resource<T: SerializableResource>(
+    reader: &mut dyn Read,
+) -> io::Result<Box<dyn Any>> {
+    Ok(Box::new(T::deserialize(reader)?))
+}
+
+fn insert_resource<T: SerializableResource>(world_data: &mut WorldData, resource: Box<dyn Any>) {
+    let resource = *resource
+        .downcast::<T>()
+        .expect("Deserialized resource should be of the registered type.");
+    world_data.add_resource(resource);
+}
+
+// Snapshot layout, all integers are little endian u32 values:
+// magic, version,
+// component names: count, names,
+// resources: count, (name, data length, data) per resource,
+// entity generations: count, generation per entity id,
+// entities: count, (id, component count, (component name index, data length, data)) per entity.
+//
+// Only entities, which own at least one serializable component, are written.
+pub(crate) fn save_snapshot(world_data: &mut WorldData, writer: &mut dyn Write) -> io::Result<()> {
+    let components = world_data.snapshot_registry.components.clone();
+    let resources = world_data.snapshot_registry.resources.clone();
+
+    writer.write_all(SNAPSHOT_MAGIC)?;
+    write_u32(writer, SNAPSHOT_VERSION)?;
+
+    write_len(writer, components.len())?;
+    for serializer in components.iter() {
+        write_str(writer, serializer.name)?;
+    }
+
+    let mut resource_data = Vec::new();
+    let mut buffer = Vec::new();
+    for serializer in resources.iter() {
+        buffer.clear();
+        if (serializer.serialize)(world_data, &mut buffer)? {
+            resource_data.push((serializer.name, buffer.clone()));
+        }
+    }
+    write_len(writer, resource_data.len())?;
+    for (name, data) in resource_data.iter() {
+        write_str(writer, name)?;
+        write_len(writer, data.len())?;
+        writer.write_all(data)?;
+    }
+
+    let generations = world_data.get_entities().generations();
+    write_len(writer, generations.len())?;
+    for generation in generations {
+        write_u32(writer, generation)?;
+    }
+
+    let mut entity_data = Vec::new();
+    for entity_key in world_data.get_entities().keys() {
+        let mut comp_data = Vec::new();
+        for (index, serializer) in components.iter().enumerate() {
+            buffer.clear();
+            if (serializer.serialize)(world_data, entity_key, &mut buffer)? {
+                comp_data.push((index, buffer.clone()));
+            }
+        }
+        if !comp_data.is_empty() {
+            entity_data.push((entity_key, comp_data));
+        }
+    }
+    write_len(writer, entity_data.len())?;
+    for (entity_key, comp_data) in entity_data {
+        write_u32(writer, entity_key.get_id())?;
+        write_len(writer, comp_data.len())?;
+        for (index, data) in comp_data {
+            write_len(writer, index)?;
+            write_len(writer, data.len())?;
+            writer.write_all(&data)?;
+        }
+    }
+    Ok(())
+}
+
+// The whole snapshot is read and deserialized, before the world is modified,
+// so that an invalid snapshot leaves the world untouched.
+// All entities of the world are removed and replaced by the entities of the snapshot,
+// while resources, which are not part of the snapshot, are kept.
+pub(crate) fn load_snapshot(world_data: &mut WorldData, reader: &mut dyn Read) -> io::Result<()> {
+    let components = world_data.snapshot_registry.components.clone();
+    let resources = world_data.snapshot_registry.resources.clone();
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != SNAPSHOT_MAGIC {
+        return Err(invalid_data("Data is not a world snapshot.".to_string()));
+    }
+    let version = read_u32(reader)?;
+    if version != SNAPSHOT_VERSION {
+        return Err(invalid_data(format!(
+            "Unsupported snapshot version {version}."
+        )));
+    }
+
+    let mut comp_serializers = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        let name = read_string(reader)?;
+        match components.iter().find(|s| s.name == name) {
+            Some(serializer) => comp_serializers.push(*serializer),
+            None => {
+                return Err(invalid_data(format!(
+                    "Component {name} is not registered as serializable."
+                )));
+            }
+        }
+    }
+
+    let mut resource_values = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        let name = read_string(reader)?;
+        let data = read_bytes(reader)?;
+        match resources.iter().find(|s| s.name == name) {
+            Some(serializer) => {
+                resource_values.push((serializer.insert, (serializer.deserialize)(&mut &data[..])?))
+            }
+            None => {
+                return Err(invalid_data(format!(
+                    "Resource {name} is not registered as serializable."
+                )));
+            }
+        }
+    }
+
+    let mut generations = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        generations.push(read_u32(reader)?);
+    }
+
+    let mut entity_values = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        let id = read_u32(reader)?;
+        let Some(generation) = generations.get(id as usize) else {
+            return Err(invalid_data(format!("Entity id {id} is out of range.")));
+        };
+        let entity_key = EntityKey::new(id, *generation);
+        let mut comp_values = Vec::new();
+        for _ in 0..read_u32(reader)? {
+            let index = read_u32(reader)? as usize;
+            let data = read_bytes(reader)?;
+            let Some(serializer) = comp_serializers.get(index) else {
+                return Err(invalid_data(format!(
+                    "Component index {index} is out of range."
+                )));
+            };
+            comp_values.push((serializer.insert, (serializer.deserialize)(&mut &data[..])?));
+        }
+        entity_values.push((entity_key, comp_values));
+    }
+
+    for entity_key in world_data.get_entities().keys() {
+        world_data.remove_entity(entity_key);
+    }
+    world_data
+        .get_entities_mut()
+        .reset_with_generations(&generations);
+    for (entity_key, comp_values) in entity_values {
+        for (insert, value) in comp_values {
+            insert(world_data, entity_key, value);
+        }
+    }
+    world_data.get_entities_mut().rebuild_empty_indices();
+    for (insert, value) in resource_values {
+        insert(world_data, value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Read, Write};
+
+    use crate::ecs::{
+        prelude::*,
+        snapshot::{
+            SerializableComponent, SerializableResource, read_string, read_u32, write_str,
+            write_u32,
+        },
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Position(f32, f32);
+    impl Component for Position {}
+    impl SerializableComponent for Position {
+        const NAME: &'static str = "Position";
+        fn serialize(&self, writer: &mut dyn Write) -> io::Result<()> {
+            write_u32(writer, self.0.to_bits())?;
+            write_u32(writer, self.1.to_bits())
+        }
+        fn deserialize(reader: &mut dyn Read) -> io::Result<Self> {
+            Ok(Position(
+                f32::from_bits(read_u32(reader)?),
+                f32::from_bits(read_u32(reader)?),
+            ))
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+    impl Component for Health {
+        const STORAGE: StorageTypes = StorageTypes::TableAoS;
+    }
+    impl SerializableComponent for Health {
+        const NAME: &'static str = "Health";
+        fn serialize(&self, writer: &mut dyn Write) -> io::Result<()> {
+            write_u32(writer, self.0)
+        }
+        fn deserialize(reader: &mut dyn Read) -> io::Result<Self> {
+            Ok(Health(read_u32(reader)?))
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Name(String);
+    impl Component for Name {
+        const STORAGE: StorageTypes = StorageTypes::SparseSet;
+    }
+    impl SerializableComponent for Name {
+        const NAME: &'static str = "Name";
+        fn serialize(&self, writer: &mut dyn Write) -> io::Result<()> {
+            write_str(writer, &self.0)
+        }
+        fn deserialize(reader: &mut dyn Read) -> io::Result<Self> {
+            Ok(Name(read_string(reader)?))
+        }
+    }
+
+    // not registered as serializable, so it is not part of the snapshot
+    struct Cached;
+    impl Component for Cached {}
+
+    #[derive(Debug, PartialEq)]
+    struct Turn(u32);
+    impl SerializableResource for Turn {
+        const NAME: &'static str = "Turn";
+        fn serialize(&self, writer: &mut dyn Write) -> io::Result<()> {
+            write_u32(writer, self.0)
+        }
+        fn deserialize(reader: &mut dyn Read) -> io::Result<Self> {
+            Ok(Turn(read_u32(reader)?))
+        }
+    }
+
+    fn register_types(world: &mut World) {
+        world.register_serializable_component::<Position>();
+        world.register_serializable_component::<Health>();
+        world.register_serializable_component::<Name>();
+        world.register_serializable_resource::<Turn>();
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let mut world = World::new();
+        register_types(&mut world);
+        world.add_resource(Turn(7));
+        let removed = world.add_entity(Position(0., 0.));
+        let hero = world.add_entity((Position(1., 2.), Health(10), Name("hero".to_string())));
+        let tree = world.add_entity((Position(5., 5.), Cached));
+        world.data.get_mut().remove_entity(removed);
+        // reuses the id of the removed entity with a new generation
+        let orc = world.add_entity(Health(3));
+        let cache_only = world.add_entity(Cached);
+
+        let mut snapshot = Vec::new();
+        world.save_snapshot(&mut snapshot).unwrap();
+
+        let mut loaded = World::new();
+        register_types(&mut loaded);
+        loaded.add_entity(Health(99));
+        loaded.load_snapshot(&mut &snapshot[..]).unwrap();
+
+        assert_eq!(Some(&Turn(7)), loaded.get_resource::<Turn>());
+        assert_eq!(
+            Some(&Position(1., 2.)),
+            loaded.get_single_component::<Position>(hero)
+        );
+        assert_eq!(
+            Some(&Health(10)),
+            loaded.get_single_component::<Health>(hero)
+        );
+        assert_eq!(
+            Some(&Name("hero".to_string())),
+            loaded.get_single_component::<Name>(hero)
+        );
+        assert_eq!(
+            Some(&Position(5., 5.)),
+            loaded.get_single_component::<Position>(tree)
+        );
+        assert!(loaded.get_single_component::<Cached>(tree).is_none());
+        assert_eq!(Some(&Health(3)), loaded.get_single_component::<Health>(orc));
+        assert!(loaded.get_single_component::<Cached>(cache_only).is_none());
+        assert!(loaded.get_single_component::<Position>(removed).is_none());
+
+        // entities added after loading don't reuse the ids of loaded entities
+        let added = loaded.add_entity(Health(1));
+        assert!(
+            ![hero, tree, orc]
+                .iter()
+                .any(|key| key.get_id() == added.get_id())
+        );
+
+        // snapshots of the same world are identical
+        let mut loaded = World::new();
+        register_types(&mut loaded);
+        loaded.load_snapshot(&mut &snapshot[..]).unwrap();
+        let mut resaved = Vec::new();
+        loaded.save_snapshot(&mut resaved).unwrap();
+        assert_eq!(snapshot, resaved);
+    }
+
+    #[test]
+    fn test_invalid_snapshot() {
+        let mut world = World::new();
+        register_types(&mut world);
+        let hero = world.add_entity(Health(10));
+        let mut snapshot = Vec::new();
+        world.save_snapshot(&mut snapshot).unwrap();
+
+        let mut unregistered = World::new();
+        let entity = unregistered.add_entity(Health(1));
+        let err = unregistered.load_snapshot(&mut &snapshot[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        // the world is untouched by an invalid snapshot
+        assert_eq!(
+            Some(&Health(1)),
+            unregistered.get_single_component::<Health>(entity)
+        );
+
+        let err = world.load_snapshot(&mut &snapshot[..8]).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+        let err = world.load_snapshot(&mut &b"NOPE"[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        assert_eq!(
+            Some(&Health(10)),
+            world.get_single_component::<Health>(hero)
+        );
+    }
+}
@@ -1,6 +1,6 @@
 // cache.rs
 
-use std::ptr::NonNull;
+use std::{any::TypeId, ptr::NonNull};
 
 use crate::ecs::{component::ComponentId, query::QueryParamMetaData};
 
@@ -43,6 +43,8 @@ pub(crate) struct EntityStorageCache {
     pub(crate) query_param_meta_data_vec_cache: CollectionCache<Vec<QueryParamMetaData>>,
     pub(crate) compid_vec_cache: CollectionCache<Vec<ComponentId>>,
     pub(crate) compelemptr_vec_cache: CollectionCache<Vec<CompElemPtr>>,
+    pub(crate) typeid_vec_cache: CollectionCache<Vec<TypeId>>,
+    pub(crate) bool_vec_cache: CollectionCache<Vec<bool>>,
 }
 
 impl EntityStorageCache {
@@ -52,6 +54,8 @@ impl EntityStorageCache {
             compid_vec_cache: CollectionCache::new(),
             query_param_meta_data_vec_cache: CollectionCache::new(),
             compelemptr_vec_cache: CollectionCache::new(),
+            typeid_vec_cache: CollectionCache::new(),
+            bool_vec_cache: CollectionCache::new(),
         }
     }
 }
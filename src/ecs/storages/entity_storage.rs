@@ -1,15 +1,24 @@
 // entity_storage.rs
 
-use std::any::TypeId;
+use std::{
+    alloc::Layout,
+    any::TypeId,
+    ptr::NonNull,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 use crate::{
     ecs::{
+        change_detection::Tick,
         component::{Archetype, ArchetypeId, Component, ComponentId, ComponentInfo, Map},
         ecs_dependency_graph::EcsDependencyGraph,
-        entity::{Entities, Entity, EntityKey, TableRowId},
+        entity::{
+            Entities, Entity, EntityKey, TableRowId,
+            relation::{Relation, RelationKind},
+        },
         prelude::StorageTypes,
         query::{QueryParam, QueryParamMetaData, QueryState},
-        storages::{table_aos::TableAoS, table_soa::TableSoA},
+        storages::sparse_set::SparseSets,
     },
     utils::{
         ecs_id::EcsId,
@@ -27,12 +36,25 @@ pub struct EntityStorage {
     pub(crate) components: Vec<ComponentInfo>,
     pub(crate) archetypes: Vec<Archetype>,
     pub(crate) tables: Map<ArchetypeId, TableStorage>,
+    // boxed, so that tables can keep a stable pointer to the sparse sets
+    pub(crate) sparse_sets: Box<SparseSets>,
     pub(crate) query_data: Vec<QueryState>,
     //mapping data
     pub(crate) typeid_compid_map: Map<TypeId, ComponentId>,
     pub(crate) compids_archid_map: Map<SortedVec<ComponentId>, ArchetypeId>,
+    pub(crate) relation_pair_compid_map: Map<(ComponentId, EntityKey), ComponentId>,
+    // pair component ids of despawned targets keyed by their relation kind,
+    // which are reused together with their archetypes for new targets
+    pub(crate) free_relation_pairs: Map<ComponentId, Vec<ComponentId>>,
+    // relation kinds and pair component ids of every relation target,
+    // so that despawning an entity only touches its own pairs
+    pub(crate) relation_target_pairs: Map<EntityKey, Vec<(ComponentId, ComponentId)>>,
+    pub(crate) dynamic_compid_map: Map<String, ComponentId>,
     pub(crate) depend_graph: EcsDependencyGraph,
     pub(crate) cache: EntityStorageCache,
+    // incremented every time a system runs,
+    // added and changed components are marked with its current value
+    pub(crate) change_tick: AtomicU32,
 }
 
 #[derive(Debug)]
@@ -50,11 +72,17 @@ impl EntityStorage {
             components: Vec::new(),
             archetypes: Vec::new(),
             tables: Map::new(),
+            sparse_sets: Box::new(SparseSets::new()),
             query_data: Vec::new(),
             typeid_compid_map: Map::new(),
             compids_archid_map: Map::new(),
+            relation_pair_compid_map: Map::new(),
+            free_relation_pairs: Map::new(),
+            relation_target_pairs: Map::new(),
+            dynamic_compid_map: Map::new(),
             depend_graph: EcsDependencyGraph::new(),
             cache: EntityStorageCache::new(),
+            change_tick: AtomicU32::new(1),
         }
     }
 
@@ -78,7 +106,10 @@ impl EntityStorage {
         possible_subset: &SortedVec<QueryParamMetaData>,
         wholeset: &SortedVec<ComponentId>,
     ) -> bool {
-        let not_optional_count = possible_subset.iter().filter(|qpmd| !qpmd.optional).count();
+        let not_optional_count = possible_subset
+            .iter()
+            .filter(|qpmd| !qpmd.optional && qpmd.storage != StorageTypes::SparseSet)
+            .count();
         let subset_iter = possible_subset.iter();
         let mut contains_count = 0;
 
@@ -86,7 +117,11 @@ impl EntityStorage {
             return false;
         }
 
-        for el in subset_iter.filter(|qpmd| !qpmd.optional) {
+        // sparse set components are not part of archetypes
+        // and are checked per entity during query iteration
+        for el in
+            subset_iter.filter(|qpmd| !qpmd.optional && qpmd.storage != StorageTypes::SparseSet)
+        {
             for el2 in wholeset.iter() {
                 if el.comp_id == *el2 {
                     contains_count += 1;
@@ -98,6 +133,16 @@ impl EntityStorage {
         contains_count == not_optional_count
     }
 
+    pub(crate) fn change_tick(&self) -> Tick {
+        Tick::new(self.change_tick.load(Ordering::Acquire))
+    }
+
+    /// Increments the change tick of the world and returns its previous value,
+    /// which is used as the tick of the current run of a system.
+    pub(crate) fn increment_change_tick(&self) -> Tick {
+        Tick::new(self.change_tick.fetch_add(1, Ordering::AcqRel))
+    }
+
     pub(crate) fn add_entity<T: TupleTypesExt>(&mut self, input: T) -> EntityKey {
         let archetype_id = self.create_or_get_archetype::<T>();
         let key = self.entities.insert(Entity {
@@ -112,9 +157,13 @@ impl EntityStorage {
         &mut self,
         entity_key: EntityKey,
     ) -> Option<<P::Construct<'_> as TupleIterator>::Item> {
+        if !self.entity_contains_query_param::<P>(entity_key) {
+            return None;
+        }
         if let Some(entity) = self.entities.get(entity_key) {
+            let this_run = self.change_tick();
             if let Some(table) = self.tables.get_mut(&entity.archetype_id) {
-                return table.get_entity_components::<P>(*entity);
+                return table.get_entity_components::<P>(*entity, this_run);
             }
         }
         None
@@ -131,7 +180,129 @@ impl EntityStorage {
         &mut self,
         entity_key: EntityKey,
     ) -> Option<&mut T> {
+        // mutable access from outside of systems marks the component as changed
         self.get_entity_components::<&mut T>(entity_key)
+            .map(|comp| comp.into_inner())
+    }
+
+    // Tables can only return iterators for the components of their archetype,
+    // so entities, which don't own all required components of P, are skipped.
+    fn entity_contains_query_param<P: QueryParam>(&mut self, entity_key: EntityKey) -> bool {
+        let mut type_ids = self.cache.typeid_vec_cache.take_cached();
+        let mut optional = self.cache.bool_vec_cache.take_cached();
+        P::type_ids_rec(&mut type_ids);
+        P::optional_param_rec(&mut optional);
+        let contains = type_ids
+            .iter()
+            .zip(optional.iter())
+            .filter(|(_, optional)| !**optional)
+            .all(|(type_id, _)| {
+                self.typeid_compid_map
+                    .get(type_id)
+                    .is_some_and(|comp_id| self.entity_contains_component(entity_key, *comp_id))
+            });
+        self.cache.typeid_vec_cache.insert(type_ids);
+        self.cache.bool_vec_cache.insert(optional);
+        contains
+    }
+
+    /// Returns true, if the entity owns a component with the supplied component id,
+    /// either stored inside of its archetype table or inside of a sparse set.
+    pub(crate) fn entity_contains_component(
+        &self,
+        entity_key: EntityKey,
+        comp_id: ComponentId,
+    ) -> bool {
+        match self.entities.get(entity_key) {
+            Some(entity) => {
+                self.archetypes[entity.archetype_id.id_usize()].contains_comp(&comp_id)
+                    || self.sparse_sets.contains(&comp_id, entity_key)
+            }
+            None => false,
+        }
+    }
+
+    /// Pushes the component ids of all components owned by the entity onto the supplied vec.
+    pub(crate) fn get_entity_comp_ids(&self, entity_key: EntityKey, vec: &mut Vec<ComponentId>) {
+        if let Some(entity) = self.entities.get(entity_key) {
+            let archetype = &self.archetypes[entity.archetype_id.id_usize()];
+            vec.extend(
+                archetype
+                    .soa_comp_ids
+                    .iter()
+                    .chain(archetype.aos_comp_ids.iter()),
+            );
+            self.sparse_sets.get_entity_comp_ids(entity_key, vec);
+        }
+    }
+
+    /// Returns a pointer to the component of the entity with the supplied component id.
+    /// Relations don't store any data, so None is returned for them.
+    pub(crate) fn get_component_ptr(
+        &self,
+        entity_key: EntityKey,
+        comp_id: ComponentId,
+    ) -> Option<NonNull<u8>> {
+        let entity = self.entities.get(entity_key)?;
+        let cinfo = self.components.get(comp_id.id_usize())?;
+        if cinfo.relation.is_some() {
+            return None;
+        }
+        let row = entity.row_id.id_usize();
+        match cinfo.storage {
+            StorageTypes::SparseSet => {
+                let sparse_set = self.sparse_sets.get(&comp_id)?;
+                let index = sparse_set.dense_index(entity_key)?;
+                Some(sparse_set.dense.get_ptr_untyped(index, cinfo.layout))
+            }
+            StorageTypes::TableSoA => {
+                let table = self.tables.get(&entity.archetype_id)?;
+                let column = table.table_soa.columns.get(&cinfo.type_id)?;
+                Some(column.get_ptr_untyped(row, cinfo.layout))
+            }
+            StorageTypes::TableAoS => {
+                let table = self.tables.get(&entity.archetype_id)?;
+                let tmd_id = table.table_aos.type_meta_data_map.get(&cinfo.type_id)?;
+                let tmd = &table.table_aos.type_meta_data.get_vec()[*tmd_id];
+                Some(
+                    table
+                        .table_aos
+                        .vec
+                        .get_inner_ptr_untyped(row, tmd.ptr_offset),
+                )
+            }
+        }
+    }
+
+    /// Marks the component of the entity with the supplied component id as changed.
+    pub(crate) fn set_component_changed(&mut self, entity_key: EntityKey, comp_id: ComponentId) {
+        let tick = self.change_tick();
+        let (Some(entity), Some(cinfo)) = (
+            self.entities.get(entity_key),
+            self.components.get(comp_id.id_usize()),
+        ) else {
+            return;
+        };
+        let row = entity.row_id.id_usize();
+        match cinfo.storage {
+            StorageTypes::SparseSet => {
+                if let Some(sparse_set) = self.sparse_sets.get_mut(&comp_id)
+                    && let Some(index) = sparse_set.dense_index(entity_key)
+                {
+                    sparse_set.ticks[index].changed = tick;
+                }
+            }
+            StorageTypes::TableSoA => {
+                if let Some(table) = self.tables.get_mut(&entity.archetype_id) {
+                    table.table_soa.set_changed(&cinfo.type_id, row, tick);
+                }
+            }
+            StorageTypes::TableAoS => {
+                if let Some(table) = self.tables.get_mut(&entity.archetype_id) {
+                    table.table_aos.set_changed(comp_id, row, tick);
+                }
+            }
+        }
     }
 
     pub(crate) fn add_entity_with_reserved_key<T: TupleTypesExt>(
@@ -159,9 +330,16 @@ impl EntityStorage {
     ) -> EntityKey {
         let mut soa_comp_ids = self.cache.compid_vec_cache.take_cached();
         let mut aos_comp_ids = self.cache.compid_vec_cache.take_cached();
+        let mut sparse_comp_ids = self.cache.compid_vec_cache.take_cached();
 
-        T::get_comp_ids_by_storage(self, &mut soa_comp_ids, &mut aos_comp_ids);
+        T::get_comp_ids_by_storage(
+            self,
+            &mut soa_comp_ids,
+            &mut aos_comp_ids,
+            &mut sparse_comp_ids,
+        );
 
+        let tick = self.change_tick();
         let row_id = unsafe {
             self.tables
                 .get_mut(&archetype_id)
@@ -171,13 +349,17 @@ impl EntityStorage {
                     &self.components,
                     &soa_comp_ids,
                     &aos_comp_ids,
+                    &sparse_comp_ids,
+                    &mut self.sparse_sets,
                     &mut self.cache,
                     input,
+                    tick,
                 )
         };
 
         self.cache.compid_vec_cache.insert(soa_comp_ids);
         self.cache.compid_vec_cache.insert(aos_comp_ids);
+        self.cache.compid_vec_cache.insert(sparse_comp_ids);
 
         if let Some(e) = self.entities.get_mut(key) {
             e.row_id = row_id;
@@ -190,8 +372,15 @@ impl EntityStorage {
 
         let mut soa_comp_ids = self.cache.compid_vec_cache.take_cached();
         let mut aos_comp_ids = self.cache.compid_vec_cache.take_cached();
-        T::get_comp_ids_by_storage(self, &mut soa_comp_ids, &mut aos_comp_ids);
+        let mut sparse_comp_ids = self.cache.compid_vec_cache.take_cached();
+        T::get_comp_ids_by_storage(
+            self,
+            &mut soa_comp_ids,
+            &mut aos_comp_ids,
+            &mut sparse_comp_ids,
+        );
 
+        let tick = self.change_tick();
         let table = self
             .tables
             .get_mut(&archetype_id)
@@ -214,18 +403,23 @@ impl EntityStorage {
                 &self.components,
                 &soa_comp_ids,
                 &aos_comp_ids,
+                &sparse_comp_ids,
+                &mut self.sparse_sets,
                 &mut self.cache,
                 input,
+                tick,
             );
         }
 
         self.cache.compid_vec_cache.insert(soa_comp_ids);
         self.cache.compid_vec_cache.insert(aos_comp_ids);
+        self.cache.compid_vec_cache.insert(sparse_comp_ids);
 
         entity_keys
     }
 
     pub(crate) fn remove_entity(&mut self, entity_key: EntityKey) {
+        self.sparse_sets.remove_entity(entity_key);
         if let Some(entity) = self.entities.remove(entity_key) {
             if let Some(table) = self.tables.get_mut(&entity.archetype_id) {
                 if let Some((key, row_id)) = table.remove_entity(entity) {
@@ -235,6 +429,28 @@ impl EntityStorage {
                 }
             }
         }
+        self.remove_relations_to_target(entity_key);
+    }
+
+    // Removes all relation pairs pointing to the despawned target from their entities.
+    fn remove_relations_to_target(&mut self, target: EntityKey) {
+        let Some(pairs) = self.relation_target_pairs.remove(&target) else {
+            return;
+        };
+        for (kind, pair) in pairs {
+            self.relation_pair_compid_map.remove(&(kind, target));
+            // entities emptied by the removal are removed as well, which can move other
+            // entities with the pair into new archetypes, so search again after every removal
+            while let Some(entity_key) = self
+                .archetypes
+                .iter()
+                .filter(|arch| arch.contains_comp(&pair))
+                .find_map(|arch| self.tables[&arch.archetype_id].entities.last().copied())
+            {
+                let _ = self.remove_relation_pair(entity_key, kind, pair);
+            }
+            self.free_relation_pairs.entry(kind).or_default().push(pair);
+        }
     }
 
     pub(crate) fn add_component_to_entity<T: Component>(
@@ -245,6 +461,10 @@ impl EntityStorage {
     ) -> Result<Entity, EntStoreErr> {
         if let Some(entity) = self.entities.get_mut(entity_key) {
             let entity = entity.clone();
+            if T::STORAGE == StorageTypes::SparseSet {
+                return self
+                    .add_sparse_component_to_entity(entity_key, entity, component, overwrite);
+            }
             let to_table_arch_id = match self
                 .create_or_get_archetype_adding_comp_to_entity::<T>(entity.archetype_id)
             {
@@ -287,7 +507,36 @@ impl EntityStorage {
         Err(EntStoreErr::EntityNotFound)
     }
 
+    // Sparse set components do not change the archetype of an entity,
+    // so the entity does not need to be moved between tables.
+    fn add_sparse_component_to_entity<T: Component>(
+        &mut self,
+        entity_key: EntityKey,
+        entity: Entity,
+        component: T,
+        overwrite: bool,
+    ) -> Result<Entity, EntStoreErr> {
+        let comp_id = self.create_or_get_component::<T>();
+        let tick = self.change_tick();
+        let sparse_set = self
+            .sparse_sets
+            .get_mut(&comp_id)
+            .expect("Sparse set for component should exist at this point.");
+        if sparse_set.contains(entity_key) {
+            if overwrite {
+                // overwrite old component with new data
+                sparse_set.insert(entity_key, component, tick);
+            }
+            return Err(EntStoreErr::MultSameKindComp);
+        }
+        sparse_set.insert(entity_key, component, tick);
+        Ok(entity)
+    }
+
+    // Overwriting a component marks it as changed.
     fn replace_component_of_entity<T: Component>(&mut self, entity: Entity, mut component: T) {
+        let comp_id = self.create_or_get_component::<T>();
+        let tick = self.change_tick();
         let table_arch_id = entity.archetype_id;
         let table = self
             .tables
@@ -295,12 +544,22 @@ impl EntityStorage {
             .expect("Table should exist at this point.");
         match T::STORAGE {
             StorageTypes::TableAoS => {
-                let tmd_id= table.table_aos.type_meta_data_map.get(&TypeId::of::<T>()).expect("TypeId is not contained in table.");
+                let tmd_id = table
+                    .table_aos
+                    .type_meta_data_map
+                    .get(&TypeId::of::<T>())
+                    .expect("TypeId is not contained in table.");
                 let tmd = &table.table_aos.type_meta_data.get_vec()[*tmd_id];
-                let cur_component = unsafe{
-                    table.table_aos.vec.get_mut_inner_typed_lifetime::<T>(entity.row_id.id_usize(), tmd.ptr_offset)
+                let cur_component = unsafe {
+                    table
+                        .table_aos
+                        .vec
+                        .get_mut_inner_typed_lifetime::<T>(entity.row_id.id_usize(), tmd.ptr_offset)
                 };
                 std::mem::swap(cur_component, &mut component);
+                table
+                    .table_aos
+                    .set_changed(comp_id, entity.row_id.id_usize(), tick);
                 // moved out component will be dropped here
             }
             StorageTypes::TableSoA => {
@@ -313,9 +572,14 @@ impl EntityStorage {
                 //if let Some(on_remove) = T::on_remove(){}
                 let cur_component = unsafe { col.get_mut_typed::<T>(entity.row_id.id_usize()) };
                 std::mem::swap(cur_component, &mut component);
+                table
+                    .table_soa
+                    .set_changed(&TypeId::of::<T>(), entity.row_id.id_usize(), tick);
                 // moved out component will be dropped here
             }
-            StorageTypes::SparseSet => todo!(),
+            StorageTypes::SparseSet => {
+                unreachable!("Sparse set components are not stored inside of tables.")
+            }
         }
     }
 
@@ -324,25 +588,48 @@ impl EntityStorage {
         entity: Entity,
         to_table_arch_id: ArchetypeId,
         entity_key: EntityKey,
-        component: T,
+        mut component: T,
     ) -> Entity {
         let comp_id = self.create_or_get_component::<T>();
-        let row_id = if let Ok((table_from, table_to)) = self
+        let added = (comp_id, NonNull::from_mut(&mut component).cast());
+        let entity = self
+            .transfer_entity(entity, to_table_arch_id, entity_key, Some(added))
+            .expect("Tables for both from and to archetypes should exist at this point.");
+        // forget added component, to prevent it from being dropped
+        // it now lives type erased inside of the table
+        std::mem::forget(component);
+        entity
+    }
+
+    // Moves entity with all its table components into the table of another archetype.
+    // Components not contained by the new archetype are dropped.
+    fn transfer_entity(
+        &mut self,
+        entity: Entity,
+        to_table_arch_id: ArchetypeId,
+        entity_key: EntityKey,
+        added: Option<(ComponentId, NonNull<u8>)>,
+    ) -> Result<Entity, EntStoreErr> {
+        let tick = self.change_tick();
+        let row_id = match self
             .tables
             .split_mut2(&entity.archetype_id, &to_table_arch_id)
         {
-            Self::call_transfer_ent_w_new_comp_for_tables(
-                &self.components,
-                &mut self.cache,
-                entity_key,
-                entity,
-                component,
-                comp_id,
-                table_from,
-                table_to,
-            )
-        } else {
-            panic!("Tables for both from and to archetypes should exist at this point.")
+            Ok((table_from, table_to)) => unsafe {
+                TableStorage::transfer_entity(
+                    &self.components,
+                    &mut self.cache,
+                    table_from,
+                    table_to,
+                    entity,
+                    added,
+                    tick,
+                )
+            },
+            Err(SplitError::SameKey(_table)) => return Err(EntStoreErr::SameArch),
+            Err(SplitError::OnlyOneValue(_)) | Err(SplitError::NoValueFound) => {
+                panic!("Tables for both from and to archetypes should exist at this point.")
+            }
         };
         // update row id and archetype id, if replacement entity was needed to fill gap
         if let Some((entity_key, row_id)) = row_id.1 {
@@ -354,50 +641,7 @@ impl EntityStorage {
         entity.row_id = row_id.0;
         entity.archetype_id = to_table_arch_id;
 
-        *entity
-    }
-
-    fn call_transfer_ent_w_new_comp_for_tables<T: Component>(
-        component_infos: &[ComponentInfo],
-        cache: &mut EntityStorageCache,
-        entity_key: EntityKey,
-        entity: Entity,
-        component: T,
-        comp_id: ComponentId,
-        table_from: &mut TableStorage,
-        table_to: &mut TableStorage,
-    ) -> (TableRowId, Option<(EntityKey, TableRowId)>) {
-        let new_to_row_id = match T::STORAGE {
-            StorageTypes::TableAoS => {
-                let row_id = unsafe {
-                    TableAoS::transfer_entity_with_new_comp(
-                        component_infos,
-                        cache,
-                        &mut table_from.table_aos,
-                        &mut table_to.table_aos,
-                        &entity,
-                        component,
-                        comp_id,
-                    )
-                };
-                row_id
-            }
-            StorageTypes::TableSoA => {
-                //TODO: need to transfer aos and soa simultanously
-                let row_id = TableSoA::transfer_entity_with_new_comp(
-                    &mut table_from.table_soa,
-                    &mut table_to.table_soa,
-                    &entity,
-                    component,
-                );
-                row_id
-            }
-            StorageTypes::SparseSet => todo!(),
-        };
-        // need to update row id of replace entity of transfered entity
-        let replaced_entity_info = table_from.remove_replace_with_last_entity_key(entity);
-        table_to.entities.push(entity_key);
-        (new_to_row_id, replaced_entity_info)
+        Ok(*entity)
     }
 
     pub(crate) fn remove_component_from_entity<T: Component>(
@@ -407,9 +651,15 @@ impl EntityStorage {
         if let Some(entity) = self.entities.get_mut(entity_key) {
             let entity = entity.clone();
 
+            if T::STORAGE == StorageTypes::SparseSet {
+                return self.remove_sparse_component_from_entity::<T>(entity_key, entity);
+            }
+
             let to_table_arch_id = match self
-                .create_or_get_archetype_removing_comp_from_entity::<T>(entity.archetype_id)
-            {
+                .create_or_get_archetype_removing_comp_from_entity::<T>(
+                    entity_key,
+                    entity.archetype_id,
+                ) {
                 Ok(arch_id) => arch_id,
                 // same archetype found, component was already removed from entity
                 Err(EntStoreErr::SameArch) => return Err(EntStoreErr::SameArch),
@@ -426,82 +676,119 @@ impl EntityStorage {
                 ),
                 Err(EntStoreErr::EntityNotFound) => panic!("INVALID: Should be checked before."),
             };
-            return match self
-                .tables
-                .split_mut2(&entity.archetype_id, &to_table_arch_id)
-            {
-                Ok((table_from, table_to)) => {
-                    let row_id =
-                        Self::call_remove_component_for_tables::<T>(entity, table_from, table_to);
-                    //TODO: extract into methods
-                    // update row id and archetype id, if replacement entity was needed to fill gap
-                    if let Some((entity_key, row_id)) =
-                        table_from.remove_replace_with_last_entity_key(entity)
-                    {
-                        let entity = self.entities.get_mut(entity_key).unwrap();
-                        entity.row_id = row_id;
-                    }
-                    table_to.entities.push(entity_key);
-
-                    // update row id and archetype id, because entity moved tables
-                    let entity = self.entities.get_mut(entity_key).unwrap();
-                    entity.row_id = row_id;
-                    entity.archetype_id = to_table_arch_id;
-
-                    Ok(*entity)
-                }
-                Err(SplitError::SameKey(_table)) => Err(EntStoreErr::SameArch),
-                Err(SplitError::OnlyOneValue(_)) | Err(SplitError::NoValueFound) => {
-                    panic!("Tables for both from and to archetypes should exist at this point.")
-                }
-            };
+            return self.transfer_entity(entity, to_table_arch_id, entity_key, None);
         }
         Err(EntStoreErr::EntityNotFound)
     }
 
-    fn call_remove_component_for_tables<T: Component>(
+    fn remove_sparse_component_from_entity<T: Component>(
+        &mut self,
+        entity_key: EntityKey,
         entity: Entity,
-        table_from: &mut TableStorage,
-        table_to: &mut TableStorage,
-    ) -> TableRowId {
-        match T::STORAGE {
-            StorageTypes::TableAoS => todo!(),
-            StorageTypes::TableSoA => {
-                //TODO: need to transfer aos and soa simultanously
-                let row_id = TableSoA::remove_comp_and_transfer_entity::<T>(
-                    &mut table_from.table_soa,
-                    &mut table_to.table_soa,
-                    &entity,
-                );
-                row_id
-            }
-            StorageTypes::SparseSet => todo!(),
+    ) -> Result<Entity, EntStoreErr> {
+        let comp_id = self.create_or_get_component::<T>();
+        self.remove_sparse_component_by_id(entity_key, entity, comp_id)
+    }
+
+    fn remove_sparse_component_by_id(
+        &mut self,
+        entity_key: EntityKey,
+        entity: Entity,
+        comp_id: ComponentId,
+    ) -> Result<Entity, EntStoreErr> {
+        let removed = self
+            .sparse_sets
+            .get_mut(&comp_id)
+            .is_some_and(|sparse_set| sparse_set.remove(entity_key));
+        if !removed {
+            return Err(EntStoreErr::SameArch);
+        }
+        // entity does not contain any components anymore
+        // and entity should be removed
+        if self.archetypes[entity.archetype_id.id_usize()].is_empty()
+            && !self.sparse_sets.contains_entity(entity_key)
+        {
+            self.remove_entity(entity_key);
+            return Err(EntStoreErr::EmptyArch);
         }
+        Ok(entity)
     }
 
-    pub(crate) fn create_or_get_archetype<T: TupleTypesExt>(&mut self) -> ArchetypeId {
-        let mut comp_ids: Vec<ComponentId> = self.cache.compid_vec_cache.take_cached();
-        T::create_or_get_component(self, &mut comp_ids);
-        let comp_ids: SortedVec<ComponentId> = comp_ids.into();
+    /// Adds the dynamic component behind the supplied pointer to the entity.
+    /// An already existing component of the entity gets dropped and replaced.
+    ///
+    /// #SAFETY:
+    /// The pointer needs to point to a valid value of the dynamic component.
+    /// The caller must not drop the value afterwards, it is now owned by the sparse set.
+    pub(crate) unsafe fn add_dynamic_component_to_entity(
+        &mut self,
+        entity_key: EntityKey,
+        comp_id: ComponentId,
+        value_ptr: NonNull<u8>,
+    ) -> Result<Entity, EntStoreErr> {
+        let entity = *self
+            .entities
+            .get(entity_key)
+            .ok_or(EntStoreErr::EntityNotFound)?;
+        let tick = self.change_tick();
+        let sparse_set = self
+            .sparse_sets
+            .get_mut(&comp_id)
+            .expect("Sparse set for dynamic component should exist at this point.");
+        unsafe { sparse_set.insert_untyped(entity_key, value_ptr, tick) };
+        Ok(entity)
+    }
 
-        if let Some(archetype_id) = self.compids_archid_map.get(&comp_ids) {
-            self.cache.compid_vec_cache.insert(comp_ids.into());
-            return *archetype_id;
-        }
+    pub(crate) fn remove_dynamic_component_from_entity(
+        &mut self,
+        entity_key: EntityKey,
+        comp_id: ComponentId,
+    ) -> Result<Entity, EntStoreErr> {
+        let entity = *self
+            .entities
+            .get(entity_key)
+            .ok_or(EntStoreErr::EntityNotFound)?;
+        self.remove_sparse_component_by_id(entity_key, entity, comp_id)
+    }
 
-        // validation
-        if let Err(e) = Self::archetype_comp_ids_validation(&comp_ids) {
+    pub(crate) fn create_or_get_archetype<T: TupleTypesExt>(&mut self) -> ArchetypeId {
+        let mut soa_comp_ids: Vec<ComponentId> = self.cache.compid_vec_cache.take_cached();
+        let mut aos_comp_ids: Vec<ComponentId> = self.cache.compid_vec_cache.take_cached();
+        let mut sparse_comp_ids: Vec<ComponentId> = self.cache.compid_vec_cache.take_cached();
+        T::get_comp_ids_by_storage(
+            self,
+            &mut soa_comp_ids,
+            &mut aos_comp_ids,
+            &mut sparse_comp_ids,
+        );
+
+        // validation, also checking sparse set components for duplicates
+        let mut all_comp_ids: Vec<ComponentId> = self.cache.compid_vec_cache.take_cached();
+        all_comp_ids.extend(soa_comp_ids.iter().chain(aos_comp_ids.iter()));
+        all_comp_ids.extend(sparse_comp_ids.iter());
+        let all_comp_ids: SortedVec<ComponentId> = all_comp_ids.into();
+        if let Err(e) = Self::archetype_comp_ids_validation(&all_comp_ids, false) {
             panic!(
                 "ERROR: An error occurred during new archetype creation: {:?}",
                 e
             );
         }
+        self.cache.compid_vec_cache.insert(all_comp_ids.into());
+        self.cache.compid_vec_cache.insert(sparse_comp_ids);
 
-        let mut soa_comp_ids: Vec<ComponentId> = self.cache.compid_vec_cache.take_cached();
-        let mut aos_comp_ids: Vec<ComponentId> = self.cache.compid_vec_cache.take_cached();
-        T::get_comp_ids_by_storage(self, &mut soa_comp_ids, &mut aos_comp_ids);
+        // sparse set components are not part of the archetype
+        let mut comp_ids: Vec<ComponentId> = self.cache.compid_vec_cache.take_cached();
+        comp_ids.extend(soa_comp_ids.iter().chain(aos_comp_ids.iter()));
+        let comp_ids: SortedVec<ComponentId> = comp_ids.into();
 
-        self.create_archetype_inner(comp_ids, soa_comp_ids, aos_comp_ids)
+        if let Some(archetype_id) = self.compids_archid_map.get(&comp_ids) {
+            self.cache.compid_vec_cache.insert(comp_ids.into());
+            self.cache.compid_vec_cache.insert(soa_comp_ids);
+            self.cache.compid_vec_cache.insert(aos_comp_ids);
+            return *archetype_id;
+        }
+
+        self.create_archetype_inner(comp_ids, soa_comp_ids, aos_comp_ids, Vec::new())
     }
 
     pub(crate) fn create_or_get_archetype_adding_comp_to_entity<T: Component>(
@@ -510,10 +797,7 @@ impl EntityStorage {
     ) -> Result<ArchetypeId, EntStoreErr> {
         let arch = &self.archetypes[arch_id.id_usize()];
         let mut comp_ids = self.cache.compid_vec_cache.take_cached();
-        arch.aos_comp_ids
-            .iter()
-            .chain(arch.soa_comp_ids.iter())
-            .for_each(|cid| comp_ids.push(*cid));
+        arch.all_comp_ids().for_each(|cid| comp_ids.push(*cid));
         let added_compid = self.create_or_get_component::<T>();
         comp_ids.push(added_compid);
         let comp_ids = comp_ids.into();
@@ -525,7 +809,7 @@ impl EntityStorage {
         }
 
         // validation
-        Self::archetype_comp_ids_validation(&comp_ids)?;
+        Self::archetype_comp_ids_validation(&comp_ids, false)?;
 
         let arch = &self.archetypes[arch_id.id_usize()];
         let mut soa_compids: Vec<ComponentId> = arch.soa_comp_ids.clone().into();
@@ -533,22 +817,24 @@ impl EntityStorage {
         match T::STORAGE {
             StorageTypes::TableAoS => aos_compids.push(added_compid),
             StorageTypes::TableSoA => soa_compids.push(added_compid),
-            StorageTypes::SparseSet => todo!(),
+            StorageTypes::SparseSet => {
+                unreachable!("Sparse set components are not part of archetypes.")
+            }
         }
-        Ok(self.create_archetype_inner(comp_ids, soa_compids, aos_compids))
+        let relation_compids = arch.relation_comp_ids.clone().into();
+        Ok(self.create_archetype_inner(comp_ids, soa_compids, aos_compids, relation_compids))
     }
 
     pub(crate) fn create_or_get_archetype_removing_comp_from_entity<T: Component>(
         &mut self,
+        entity_key: EntityKey,
         arch_id: ArchetypeId,
     ) -> Result<ArchetypeId, EntStoreErr> {
         // remove comp id from current entity comp ids to find preexisting fitting archetype
         let remove_compid = self.create_or_get_component::<T>();
         let arch = &self.archetypes[arch_id.id_usize()];
         let mut comp_ids = self.cache.compid_vec_cache.take_cached();
-        arch.aos_comp_ids
-            .iter()
-            .chain(arch.soa_comp_ids.iter())
+        arch.all_comp_ids()
             .filter(|cid| **cid != remove_compid)
             .for_each(|cid| comp_ids.push(*cid));
         let comp_ids = comp_ids.into();
@@ -559,8 +845,9 @@ impl EntityStorage {
             return Ok(*archetype_id);
         }
 
-        // validation
-        Self::archetype_comp_ids_validation(&comp_ids)?;
+        // validation, an entity with an empty archetype may still own sparse set components
+        let allow_empty = self.sparse_sets.contains_entity(entity_key);
+        Self::archetype_comp_ids_validation(&comp_ids, allow_empty)?;
 
         fn get_filtered(
             comp_ids: &SortedVec<ComponentId>,
@@ -589,13 +876,19 @@ impl EntityStorage {
                 get_filtered(&arch.soa_comp_ids, remove_compid),
                 arch.aos_comp_ids.clone().into(),
             ),
-            StorageTypes::SparseSet => todo!(),
+            StorageTypes::SparseSet => {
+                unreachable!("Sparse set components are not part of archetypes.")
+            }
         };
-        Ok(self.create_archetype_inner(comp_ids, soa_compids, aos_compids))
+        let relation_compids = arch.relation_comp_ids.clone().into();
+        Ok(self.create_archetype_inner(comp_ids, soa_compids, aos_compids, relation_compids))
     }
 
-    fn archetype_comp_ids_validation(comp_ids: &SortedVec<ComponentId>) -> Result<(), EntStoreErr> {
-        if comp_ids.get_vec().is_empty() {
+    fn archetype_comp_ids_validation(
+        comp_ids: &SortedVec<ComponentId>,
+        allow_empty: bool,
+    ) -> Result<(), EntStoreErr> {
+        if comp_ids.get_vec().is_empty() && !allow_empty {
             println!("INVALID: Entity contains no components. An empty archetype cannot exist.");
             Err(EntStoreErr::EmptyArch)
         } else if let Some(_dup_compid) = comp_ids.check_duplicates() {
@@ -611,9 +904,15 @@ impl EntityStorage {
         comp_ids: SortedVec<ComponentId>,
         soa_comp_ids: Vec<ComponentId>,
         aos_comp_ids: Vec<ComponentId>,
+        relation_comp_ids: Vec<ComponentId>,
     ) -> ArchetypeId {
         let archetype_id = self.archetypes.len().into();
-        let archetype = Archetype::new(archetype_id, soa_comp_ids.into(), aos_comp_ids.into());
+        let archetype = Archetype::new(
+            archetype_id,
+            soa_comp_ids.into(),
+            aos_comp_ids.into(),
+            relation_comp_ids.into(),
+        );
         self.archetypes.push(archetype);
         self.tables
             .insert(archetype_id, TableStorage::new(archetype_id, self));
@@ -628,6 +927,231 @@ impl EntityStorage {
         archetype_id
     }
 
+    pub(crate) fn add_relation<R: Relation>(
+        &mut self,
+        entity_key: EntityKey,
+        target: EntityKey,
+    ) -> Result<Entity, EntStoreErr> {
+        let entity = *self
+            .entities
+            .get(entity_key)
+            .ok_or(EntStoreErr::EntityNotFound)?;
+        // pairs of despawned targets would never be removed again
+        if self.entities.get(target).is_none() {
+            return Err(EntStoreErr::EntityNotFound);
+        }
+        let (kind, pair) = self.create_or_get_relation_pair::<R>(target);
+        let arch = &self.archetypes[entity.archetype_id.id_usize()];
+        if arch.contains_comp(&pair) {
+            return Err(EntStoreErr::MultSameKindComp);
+        }
+        let mut relation_comp_ids: Vec<ComponentId> = arch.relation_comp_ids.clone().into();
+        relation_comp_ids.push(pair);
+        // the relation kind is added together with the first pair of its kind
+        if !arch.contains_comp(&kind) {
+            relation_comp_ids.push(kind);
+        }
+        let to_table_arch_id =
+            self.create_or_get_archetype_with_relations(entity.archetype_id, relation_comp_ids);
+        self.transfer_entity(entity, to_table_arch_id, entity_key, None)
+    }
+
+    pub(crate) fn remove_relation<R: Relation>(
+        &mut self,
+        entity_key: EntityKey,
+        target: EntityKey,
+    ) -> Result<Entity, EntStoreErr> {
+        if self.entities.get(entity_key).is_none() {
+            return Err(EntStoreErr::EntityNotFound);
+        }
+        // no pair is registered for targets, which never were related to or got despawned
+        let kind = self.create_or_get_component::<RelationKind<R>>();
+        let Some(pair) = self.relation_pair_compid_map.get(&(kind, target)).copied() else {
+            return Err(EntStoreErr::SameArch);
+        };
+        self.remove_relation_pair(entity_key, kind, pair)
+    }
+
+    fn remove_relation_pair(
+        &mut self,
+        entity_key: EntityKey,
+        kind: ComponentId,
+        pair: ComponentId,
+    ) -> Result<Entity, EntStoreErr> {
+        let entity = *self
+            .entities
+            .get(entity_key)
+            .ok_or(EntStoreErr::EntityNotFound)?;
+        let arch = &self.archetypes[entity.archetype_id.id_usize()];
+        if !arch.contains_comp(&pair) {
+            return Err(EntStoreErr::SameArch);
+        }
+        let mut relation_comp_ids: Vec<ComponentId> = arch
+            .relation_comp_ids
+            .iter()
+            .filter(|cid| **cid != pair)
+            .copied()
+            .collect();
+        // the relation kind is removed together with the last pair of its kind
+        let kind_left = relation_comp_ids.iter().any(|cid| {
+            self.components[cid.id_usize()]
+                .relation
+                .is_some_and(|(pair_kind, _)| pair_kind == kind)
+        });
+        if !kind_left {
+            relation_comp_ids.retain(|cid| *cid != kind);
+        }
+        // entity does not contain any components anymore
+        // and entity should be removed
+        if arch.soa_comp_ids.get_vec().is_empty()
+            && arch.aos_comp_ids.get_vec().is_empty()
+            && relation_comp_ids.is_empty()
+            && !self.sparse_sets.contains_entity(entity_key)
+        {
+            self.remove_entity(entity_key);
+            return Err(EntStoreErr::EmptyArch);
+        }
+        let to_table_arch_id =
+            self.create_or_get_archetype_with_relations(entity.archetype_id, relation_comp_ids);
+        self.transfer_entity(entity, to_table_arch_id, entity_key, None)
+    }
+
+    /// Returns the targets of all relations of kind R of the entity.
+    pub(crate) fn get_relation_targets<R: Relation>(
+        &self,
+        entity_key: EntityKey,
+    ) -> Vec<EntityKey> {
+        let Some(entity) = self.entities.get(entity_key) else {
+            return Vec::new();
+        };
+        let Some(kind) = self.typeid_compid_map.get(&TypeId::of::<RelationKind<R>>()) else {
+            return Vec::new();
+        };
+        self.archetypes[entity.archetype_id.id_usize()]
+            .relation_comp_ids
+            .iter()
+            .filter_map(|cid| match self.components[cid.id_usize()].relation {
+                Some((pair_kind, target)) if pair_kind == *kind => Some(target),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Archetype with the same table components, but other relations.
+    fn create_or_get_archetype_with_relations(
+        &mut self,
+        arch_id: ArchetypeId,
+        relation_comp_ids: Vec<ComponentId>,
+    ) -> ArchetypeId {
+        let arch = &self.archetypes[arch_id.id_usize()];
+        let mut comp_ids = self.cache.compid_vec_cache.take_cached();
+        comp_ids.extend(
+            arch.soa_comp_ids
+                .iter()
+                .chain(arch.aos_comp_ids.iter())
+                .chain(relation_comp_ids.iter()),
+        );
+        let comp_ids = comp_ids.into();
+
+        // archetype for this component combination already exists, get archetype id
+        if let Some(archetype_id) = self.compids_archid_map.get(&comp_ids) {
+            self.cache.compid_vec_cache.insert(comp_ids.into());
+            return *archetype_id;
+        }
+
+        let soa_compids = arch.soa_comp_ids.clone().into();
+        let aos_compids = arch.aos_comp_ids.clone().into();
+        self.create_archetype_inner(comp_ids, soa_compids, aos_compids, relation_comp_ids)
+    }
+
+    // Every pair of a relation kind and a target entity is registered as its own component,
+    // so that entities with different relation targets are stored inside of different archetypes.
+    // Returns the component ids of the relation kind and of the pair.
+    pub(crate) fn create_or_get_relation_pair<R: Relation>(
+        &mut self,
+        target: EntityKey,
+    ) -> (ComponentId, ComponentId) {
+        let kind = self.create_or_get_component::<RelationKind<R>>();
+        if let Some(pair) = self.relation_pair_compid_map.get(&(kind, target)) {
+            return (kind, *pair);
+        }
+
+        if let Some(pair) = self
+            .free_relation_pairs
+            .get_mut(&kind)
+            .and_then(|pairs| pairs.pop())
+        {
+            self.components[pair.id_usize()] =
+                ComponentInfo::new_relation_pair::<R>(pair.0, kind, target);
+            self.relation_pair_compid_map.insert((kind, target), pair);
+            self.relation_target_pairs
+                .entry(target)
+                .or_default()
+                .push((kind, pair));
+            // the archetypes of the pair are empty, but their tables still know the old target
+            let arch_ids: Vec<ArchetypeId> = self
+                .archetypes
+                .iter()
+                .filter(|arch| arch.contains_comp(&pair))
+                .map(|arch| arch.archetype_id)
+                .collect();
+            for arch_id in arch_ids {
+                let relation_targets = TableStorage::relation_targets(arch_id, self);
+                self.tables
+                    .get_mut(&arch_id)
+                    .expect("Table of the archetype should exist.")
+                    .relation_targets = relation_targets;
+            }
+            return (kind, pair);
+        }
+
+        let comp_id: u32 = self
+            .components
+            .len()
+            .try_into()
+            .expect("Component Ids have increased over their max possible u32 value!");
+        self.components
+            .push(ComponentInfo::new_relation_pair::<R>(comp_id, kind, target));
+        let pair = ComponentId(comp_id);
+        self.relation_pair_compid_map.insert((kind, target), pair);
+        self.relation_target_pairs
+            .entry(target)
+            .or_default()
+            .push((kind, pair));
+        self.depend_graph.insert_component(pair);
+        (kind, pair)
+    }
+
+    // Dynamic components are identified by their name instead of their type.
+    pub(crate) fn create_or_get_dynamic_component(
+        &mut self,
+        name: &str,
+        layout: Layout,
+        drop: Option<unsafe fn(*mut u8)>,
+    ) -> ComponentId {
+        if let Some(comp_id) = self.dynamic_compid_map.get(name) {
+            assert_eq!(
+                self.components[comp_id.id_usize()].layout,
+                layout,
+                "Dynamic component {name} is already registered with another layout."
+            );
+            return *comp_id;
+        }
+
+        let comp_id: u32 = self
+            .components
+            .len()
+            .try_into()
+            .expect("Component Ids have increased over their max possible u32 value!");
+        let comp_info = ComponentInfo::new_dynamic(comp_id, name.to_string(), layout, drop);
+        self.sparse_sets.create_set(&comp_info);
+        self.components.push(comp_info);
+        let comp_id = ComponentId(comp_id);
+        self.dynamic_compid_map.insert(name.to_string(), comp_id);
+        self.depend_graph.insert_component(comp_id);
+        comp_id
+    }
+
     pub(crate) fn create_or_get_component<T: Component>(&mut self) -> ComponentId {
         self.create_or_get_component_by_typeid::<T>(TypeId::of::<T>())
     }
@@ -646,6 +1170,9 @@ impl EntityStorage {
             .try_into()
             .expect("Component Ids have increased over their max possible u32 value!");
         let comp_info = ComponentInfo::new::<T>(comp_id);
+        if comp_info.storage == StorageTypes::SparseSet {
+            self.sparse_sets.create_set(&comp_info);
+        }
         self.components.push(comp_info);
         self.typeid_compid_map.insert(type_id, ComponentId(comp_id));
         let comp_id = ComponentId(comp_id);
@@ -744,14 +1271,16 @@ pub mod test {
                 dbg!(ek);
                 dbg!(&c);
             }
-            assert_eq!(10, query.iter().count());
+            // system one spawned an entity and removed its Comp1 again
+            assert_eq!(11, query.iter().count());
             for (ek, c) in query2.iter() {
                 dbg!(ek);
                 dbg!(&c);
                 // comp1 90 values should have been overwritten with 7 by now
                 assert_eq!(7, c.0);
             }
-            assert_eq!(11, query2.iter().count());
+            // the entity spawned with only Comp1 lost it in system one and was removed
+            assert_eq!(10, query2.iter().count());
         }
     }
 
@@ -856,4 +1385,54 @@ pub mod test {
 
         world.init_and_run();
     }
+
+    struct Spawned;
+    impl Component for Spawned {}
+
+    #[derive(Default)]
+    struct MatchCounts {
+        optional: usize,
+        filtered: usize,
+    }
+
+    fn spawn_new_archetype_system(mut commands: Commands) {
+        commands.spawn((Comp1(1), Spawned));
+    }
+
+    fn count_optional_system(
+        mut counts: ResMut<MatchCounts>,
+        mut query: Query<(EntityKey, Option<&Spawned>)>,
+    ) {
+        counts.optional = query
+            .iter()
+            .filter(|(_, spawned)| spawned.is_some())
+            .count();
+    }
+
+    fn count_filtered_system(
+        mut counts: ResMut<MatchCounts>,
+        mut query: Query<EntityKey, With<Spawned>>,
+    ) {
+        counts.filtered = query.iter().count();
+    }
+
+    #[test]
+    fn test_archetype_created_mid_frame_is_queried() {
+        let mut world = World::new();
+        world.add_resource(MatchCounts::default());
+        world.add_entity(Comp1(0));
+        world.add_systems(
+            (
+                spawn_new_archetype_system,
+                count_optional_system,
+                count_filtered_system,
+            )
+                .chain(),
+        );
+
+        world.init_and_run();
+        let counts = world.get_resource::<MatchCounts>().unwrap();
+        assert_eq!(1, counts.optional);
+        assert_eq!(1, counts.filtered);
+    }
 }
@@ -1,11 +1,457 @@
 //sparse_set.rs
 
-use std::hash::Hash;
-
-pub struct SparseSet<I, V>
-where
-    I: PartialEq + Eq + Clone + Hash,
-{
-    dense: Vec<V>,
-    sparse: Vec<I>,
+use std::{alloc::Layout, any::TypeId, marker::PhantomData, ptr::NonNull};
+
+use crate::{
+    ecs::{
+        change_detection::{ComponentTicks, Mut, Tick},
+        component::{Component, ComponentId, ComponentInfo, Map},
+        entity::EntityKey,
+    },
+    utils::tuple_iters::TupleIterator,
+};
+
+use super::thin_blob_vec::ThinBlobVec;
+
+/// Type erased storage of a single component type,
+/// keyed by the id of the entity the component belongs to.
+///
+/// The components are densely packed inside of a ThinBlobVec,
+/// the sparse vec maps entity ids to their index in the dense storage.
+/// The change ticks of the components are stored at the same dense index.
+#[derive(Debug)]
+pub struct SparseSet {
+    #[allow(unused)]
+    pub(crate) comp_id: ComponentId,
+    pub(crate) dense: ThinBlobVec,
+    pub(crate) ticks: Vec<ComponentTicks>,
+    pub(crate) entities: Vec<EntityKey>,
+    pub(crate) sparse: Vec<Option<u32>>,
+    pub(crate) len: usize,
+    pub(crate) cap: usize,
+}
+
+impl SparseSet {
+    pub(crate) fn new(
+        comp_id: ComponentId,
+        layout: Layout,
+        drop_fn: Option<unsafe fn(*mut u8)>,
+    ) -> Self {
+        Self {
+            comp_id,
+            dense: ThinBlobVec::new(layout, drop_fn),
+            ticks: Vec::new(),
+            entities: Vec::new(),
+            sparse: Vec::new(),
+            len: 0,
+            cap: 0,
+        }
+    }
+
+    pub(crate) fn dense_index(&self, entity_key: EntityKey) -> Option<usize> {
+        match self.sparse.get(entity_key.get_id() as usize) {
+            Some(Some(index)) if self.entities[*index as usize] == entity_key => {
+                Some(*index as usize)
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn contains(&self, entity_key: EntityKey) -> bool {
+        self.dense_index(entity_key).is_some()
+    }
+
+    pub(crate) fn get_ticks(&self, entity_key: EntityKey) -> Option<ComponentTicks> {
+        self.dense_index(entity_key).map(|index| self.ticks[index])
+    }
+
+    /// Inserts the component behind the supplied pointer for the entity.
+    /// An already existing component of this entity gets dropped and replaced,
+    /// which marks the component as changed.
+    ///
+    /// #SAFETY:
+    /// The pointer needs to point to a value of the component type of this set.
+    /// The caller needs to forget/leak the inserted value afterwards,
+    /// so that its owned allocations will not be dropped.
+    pub(crate) unsafe fn insert_untyped(
+        &mut self,
+        entity_key: EntityKey,
+        value_ptr: NonNull<u8>,
+        tick: Tick,
+    ) {
+        if let Some(index) = self.dense_index(entity_key) {
+            self.ticks[index].changed = tick;
+            unsafe {
+                let elem_ptr = self.dense.call_drop_on_elem(index);
+                std::ptr::copy(
+                    value_ptr.as_ptr(),
+                    elem_ptr.as_ptr(),
+                    self.dense.elem_layout.size(),
+                );
+            }
+            return;
+        }
+
+        let sparse_index = entity_key.get_id() as usize;
+        if self.sparse.len() <= sparse_index {
+            self.sparse.resize(sparse_index + 1, None);
+        }
+        self.sparse[sparse_index] = Some(
+            self.len
+                .try_into()
+                .expect("Sparse set length has increased over the max u32 value!"),
+        );
+        self.entities.push(entity_key);
+        self.ticks.push(ComponentTicks::new(tick));
+        self.cap = unsafe { self.dense.push_untyped(self.cap, self.len, value_ptr) };
+        self.len += 1;
+    }
+
+    pub(crate) fn insert<T: Component>(&mut self, entity_key: EntityKey, mut value: T, tick: Tick) {
+        unsafe {
+            self.insert_untyped(entity_key, NonNull::from_mut(&mut value).cast(), tick);
+        }
+        std::mem::forget(value);
+    }
+
+    /// Removes and drops the component of the entity.
+    /// Returns false, if the entity did not contain a component of this set.
+    pub(crate) fn remove(&mut self, entity_key: EntityKey) -> bool {
+        let Some(index) = self.dense_index(entity_key) else {
+            return false;
+        };
+        unsafe {
+            self.dense.drop_and_replace_with_last(self.len, index);
+        }
+        self.swap_remove_entity(entity_key, index);
+        true
+    }
+
+    fn swap_remove_entity(&mut self, entity_key: EntityKey, index: usize) {
+        self.sparse[entity_key.get_id() as usize] = None;
+        self.entities.swap_remove(index);
+        self.ticks.swap_remove(index);
+        // last entity got moved into the now empty spot
+        if let Some(moved_entity_key) = self.entities.get(index) {
+            self.sparse[moved_entity_key.get_id() as usize] = Some(index as u32);
+        }
+        self.len -= 1;
+    }
+}
+
+impl Drop for SparseSet {
+    fn drop(&mut self) {
+        unsafe {
+            self.dense.dealloc(self.cap, self.len);
+        }
+    }
+}
+
+/// All sparse sets of the world, one for each sparse set component type.
+#[derive(Debug)]
+pub(crate) struct SparseSets {
+    sets: Vec<SparseSet>,
+    typeid_index_map: Map<TypeId, usize>,
+    compid_index_map: Map<ComponentId, usize>,
+}
+
+impl SparseSets {
+    pub(crate) fn new() -> Self {
+        Self {
+            sets: Vec::new(),
+            typeid_index_map: Map::new(),
+            compid_index_map: Map::new(),
+        }
+    }
+
+    pub(crate) fn create_set(&mut self, comp_info: &ComponentInfo) {
+        if self.compid_index_map.contains_key(&comp_info.comp_id) {
+            return;
+        }
+        let index = self.sets.len();
+        self.sets.push(SparseSet::new(
+            comp_info.comp_id,
+            comp_info.layout,
+            comp_info.drop,
+        ));
+        // dynamic components share their type id and can only be accessed by component id
+        if !comp_info.is_dynamic() {
+            self.typeid_index_map.insert(comp_info.type_id, index);
+        }
+        self.compid_index_map.insert(comp_info.comp_id, index);
+    }
+
+    pub(crate) fn get(&self, comp_id: &ComponentId) -> Option<&SparseSet> {
+        self.compid_index_map
+            .get(comp_id)
+            .map(|index| &self.sets[*index])
+    }
+
+    pub(crate) fn get_mut(&mut self, comp_id: &ComponentId) -> Option<&mut SparseSet> {
+        self.compid_index_map
+            .get(comp_id)
+            .map(|index| &mut self.sets[*index])
+    }
+
+    pub(crate) fn get_typed<T: Component>(&self) -> Option<&SparseSet> {
+        self.typeid_index_map
+            .get(&TypeId::of::<T>())
+            .map(|index| &self.sets[*index])
+    }
+
+    pub(crate) fn get_typed_mut<T: Component>(&mut self) -> Option<&mut SparseSet> {
+        self.typeid_index_map
+            .get(&TypeId::of::<T>())
+            .map(|index| &mut self.sets[*index])
+    }
+
+    pub(crate) fn contains(&self, comp_id: &ComponentId, entity_key: EntityKey) -> bool {
+        self.get(comp_id)
+            .is_some_and(|set| set.contains(entity_key))
+    }
+
+    /// Returns true, if the entity owns a component in any sparse set.
+    pub(crate) fn contains_entity(&self, entity_key: EntityKey) -> bool {
+        self.sets.iter().any(|set| set.contains(entity_key))
+    }
+
+    /// Pushes the component ids of all sparse sets containing the entity onto the supplied vec.
+    pub(crate) fn get_entity_comp_ids(&self, entity_key: EntityKey, vec: &mut Vec<ComponentId>) {
+        self.sets
+            .iter()
+            .filter(|set| set.contains(entity_key))
+            .for_each(|set| vec.push(set.comp_id));
+    }
+
+    /// Removes and drops all sparse set components of the entity.
+    pub(crate) fn remove_entity(&mut self, entity_key: EntityKey) {
+        for set in self.sets.iter_mut() {
+            set.remove(entity_key);
+        }
+    }
+
+    /// #SAFETY:
+    /// Component type T needs to be stored inside of a sparse set,
+    /// otherwise this function will panic.
+    pub(crate) unsafe fn get_single_comp_iter<'c, T: Component>(
+        &'c self,
+        entities: &'c [EntityKey],
+    ) -> SparseSetIterUnsafe<'c, T> {
+        let set = self.get_typed::<T>().expect(&format!(
+            "No sparse set found for type: {}.",
+            std::any::type_name::<T>()
+        ));
+        SparseSetIterUnsafe::new(set, entities)
+    }
+
+    pub(crate) unsafe fn get_single_comp_iter_opt<'c, T: Component>(
+        &'c self,
+        entities: &'c [EntityKey],
+    ) -> Option<SparseSetIterUnsafe<'c, T>> {
+        let set = self.get_typed::<T>()?;
+        Some(SparseSetIterUnsafe::new(set, entities))
+    }
+
+    /// #SAFETY:
+    /// Component type T needs to be stored inside of a sparse set,
+    /// otherwise this function will panic.
+    pub(crate) unsafe fn get_single_comp_iter_mut<'c, T: Component>(
+        &'c mut self,
+        entities: &'c [EntityKey],
+        this_run: Tick,
+    ) -> SparseSetIterMutUnsafe<'c, T> {
+        let set = self.get_typed_mut::<T>().expect(&format!(
+            "No sparse set found for type: {}.",
+            std::any::type_name::<T>()
+        ));
+        SparseSetIterMutUnsafe::new(set, entities, this_run)
+    }
+
+    pub(crate) unsafe fn get_single_comp_iter_mut_opt<'c, T: Component>(
+        &'c mut self,
+        entities: &'c [EntityKey],
+        this_run: Tick,
+    ) -> Option<SparseSetIterMutUnsafe<'c, T>> {
+        let set = self.get_typed_mut::<T>()?;
+        Some(SparseSetIterMutUnsafe::new(set, entities, this_run))
+    }
+}
+
+/// Iterates over the sparse set components of the rows of a table.
+/// Rows whose entity does not own a component in this set, do not match.
+pub struct SparseSetIterUnsafe<'c, T: 'static> {
+    set: &'c SparseSet,
+    entities: &'c [EntityKey],
+    marker: PhantomData<T>,
+}
+
+impl<'c, T: 'static> SparseSetIterUnsafe<'c, T> {
+    pub(crate) fn new(set: &'c SparseSet, entities: &'c [EntityKey]) -> Self {
+        Self {
+            set,
+            entities,
+            marker: PhantomData,
+        }
+    }
+
+    pub(crate) unsafe fn next_opt(&mut self, index: usize) -> Option<&'c T> {
+        let dense_index = self.set.dense_index(self.entities[index])?;
+        Some(unsafe { self.set.dense.get_typed_lifetime(dense_index) })
+    }
+}
+
+impl<'c, T: Component> TupleIterator for SparseSetIterUnsafe<'c, T> {
+    type Item = &'c T;
+    unsafe fn next(&mut self, index: usize) -> Self::Item {
+        unsafe {
+            self.next_opt(index)
+                .expect("Row of entity without sparse set component was not skipped.")
+        }
+    }
+    fn row_matches(&self, index: usize) -> bool {
+        self.set.contains(self.entities[index])
+    }
+}
+
+pub struct SparseSetIterMutUnsafe<'c, T: 'static> {
+    set: &'c SparseSet,
+    ticks: NonNull<ComponentTicks>,
+    this_run: Tick,
+    entities: &'c [EntityKey],
+    marker: PhantomData<T>,
+}
+
+impl<'c, T: 'static> SparseSetIterMutUnsafe<'c, T> {
+    pub(crate) fn new(set: &'c mut SparseSet, entities: &'c [EntityKey], this_run: Tick) -> Self {
+        let ticks =
+            NonNull::new(set.ticks.as_mut_ptr()).expect("Vec pointer should never be null.");
+        Self {
+            set,
+            ticks,
+            this_run,
+            entities,
+            marker: PhantomData,
+        }
+    }
+
+    pub(crate) unsafe fn next_opt(&mut self, index: usize) -> Option<Mut<'c, T>> {
+        let dense_index = self.set.dense_index(self.entities[index])?;
+        unsafe {
+            let value = self.set.dense.get_ptr_typed::<T>(dense_index).as_mut();
+            let ticks = self.ticks.add(dense_index).as_mut();
+            Some(Mut::new(value, ticks, self.this_run))
+        }
+    }
+}
+
+impl<'c, T: Component> TupleIterator for SparseSetIterMutUnsafe<'c, T> {
+    type Item = Mut<'c, T>;
+    unsafe fn next(&mut self, index: usize) -> Self::Item {
+        unsafe {
+            self.next_opt(index)
+                .expect("Row of entity without sparse set component was not skipped.")
+        }
+    }
+    fn row_matches(&self, index: usize) -> bool {
+        self.set.contains(self.entities[index])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ecs::{prelude::*, system::ResMut};
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Poisoned(u32);
+    impl Component for Poisoned {
+        const STORAGE: StorageTypes = StorageTypes::SparseSet;
+    }
+
+    struct Marker;
+    impl Component for Marker {
+        const STORAGE: StorageTypes = StorageTypes::SparseSet;
+    }
+
+    struct Frame(usize);
+
+    fn poison_system(
+        mut commands: Commands,
+        frame: ResMut<Frame>,
+        mut query: Query<(EntityKey, &mut Health, Option<&Poisoned>)>,
+    ) {
+        let poisoned_count = query.iter().filter(|(_, _, p)| p.is_some()).count();
+        let healthy_count = query.iter().filter(|(_, _, p)| p.is_none()).count();
+        match frame.0 {
+            0 => {
+                assert_eq!(5, healthy_count);
+                assert_eq!(1, poisoned_count);
+                for (ek, _health, _) in query.iter() {
+                    commands.add_component(ek, Poisoned(2), false);
+                }
+            }
+            1 => {
+                assert_eq!(0, healthy_count);
+                assert_eq!(6, poisoned_count);
+                for (ek, mut health, poisoned) in query.iter() {
+                    health.0 -= poisoned.unwrap().0;
+                    commands.remove_component::<Poisoned>(ek);
+                }
+            }
+            _ => {
+                assert_eq!(6, healthy_count);
+                assert_eq!(0, poisoned_count);
+            }
+        }
+    }
+
+    fn marker_system(mut query: Query<(&Health, Option<&Marker>), With<Marker>>) {
+        assert_eq!(2, query.iter().count());
+        for (_health, marker) in query.iter() {
+            assert!(marker.is_some());
+        }
+    }
+
+    fn unmarked_system(
+        frame: Res<Frame>,
+        mut query: Query<EntityKey, (Without<Marker>, Without<Poisoned>)>,
+    ) {
+        // all unmarked entities are poisoned during the first frame
+        let expected = if frame.0 == 0 { 0 } else { 4 };
+        assert_eq!(expected, query.iter().count());
+    }
+
+    #[test]
+    fn test_sparse_set_components_do_not_move_entities() {
+        let mut world = World::new();
+        world.add_resource(Frame(0));
+        world.add_systems((poison_system, marker_system, unmarked_system).chain());
+
+        let poisoned = world.add_entity((Health(10), Poisoned(3), Marker));
+        let keys: Vec<EntityKey> = (0..4).map(|_| world.add_entity(Health(10))).collect();
+        world.add_entity((Health(10), Marker));
+        let only_sparse = world.add_entity(Marker);
+
+        let archetype_before = world.data.get_mut().get_entities().get(keys[0]).cloned();
+
+        world.init_and_run();
+        assert_eq!(Some(&Poisoned(2)), world.get_single_component(keys[0]));
+        assert_eq!(
+            archetype_before,
+            world.data.get_mut().get_entities().get(keys[0]).cloned()
+        );
+
+        world.get_resource_mut::<Frame>().unwrap().0 += 1;
+        world.run();
+        assert_eq!(Some(&Health(7)), world.get_single_component(poisoned));
+        assert_eq!(Some(&Health(8)), world.get_single_component(keys[3]));
+        assert_eq!(None, world.get_single_component::<Poisoned>(keys[3]));
+
+        world.get_resource_mut::<Frame>().unwrap().0 += 1;
+        world.run();
+        assert!(world.get_single_component::<Marker>(only_sparse).is_some());
+    }
 }
@@ -8,6 +8,7 @@ use std::{
 
 use crate::{
     ecs::{
+        change_detection::{ChangeTicksIterUnsafe, ComponentTicks, Tick},
         component::{ArchetypeId, Component, ComponentId, ComponentInfo, Map},
         entity::{Entity, TableRowId},
     },
@@ -56,6 +57,9 @@ pub struct TableAoS {
     pub(crate) len: usize,
     pub(crate) type_meta_data_map: Map<TypeId, usize>,
     pub(crate) type_meta_data: SortedVec<TypeMetaData>,
+    // change ticks of every component field of a row,
+    // indexed in the same order as the type meta data
+    pub(crate) change_ticks: Vec<Vec<ComponentTicks>>,
 }
 
 impl TableAoS {
@@ -97,6 +101,7 @@ impl TableAoS {
                 type_meta_data_map.insert(comp_info.type_id, index);
             }
             row_layout = row_layout.pad_to_align();
+            let change_ticks = meta_data.iter().map(|_| Vec::new()).collect();
             return Self {
                 archetype_id,
                 vec: ThinBlobVec::new(row_layout, None),
@@ -104,6 +109,7 @@ impl TableAoS {
                 len: 0,
                 type_meta_data_map,
                 type_meta_data: meta_data.into(),
+                change_ticks,
             };
         }
         Self {
@@ -113,6 +119,7 @@ impl TableAoS {
             len: 0,
             type_meta_data_map: Map::new(),
             type_meta_data: SortedVec::new(),
+            change_ticks: Vec::new(),
         }
     }
 
@@ -137,11 +144,8 @@ impl TableAoS {
         aos_comp_ids: &[ComponentId],
         aos_ptrs: &[NonNull<u8>],
         cache: &mut EntityStorageCache,
+        tick: Tick,
     ) {
-        if aos_comp_ids.len() == 0 {
-            return;
-        }
-
         let mut comp_elem_ptrs: Vec<CompElemPtr> = cache.compelemptr_vec_cache.take_cached();
 
         for i in 0..aos_comp_ids.len() {
@@ -164,6 +168,10 @@ impl TableAoS {
         }
 
         cache.compelemptr_vec_cache.insert(comp_elem_ptrs.into());
+        // every field of a row gets inserted at once
+        self.change_ticks
+            .iter_mut()
+            .for_each(|ticks| ticks.push(ComponentTicks::new(tick)));
     }
 
     pub(crate) unsafe fn insert_batch(
@@ -174,11 +182,8 @@ impl TableAoS {
         value_layout: Layout,
         batch_len: usize,
         cache: &mut EntityStorageCache,
+        tick: Tick,
     ) {
-        if aos_comp_ids.len() == 0 {
-            return;
-        }
-
         let mut comp_elem_ptrs: Vec<CompElemPtr> = cache.compelemptr_vec_cache.take_cached();
         for i in 0..aos_comp_ids.len() {
             comp_elem_ptrs.push(CompElemPtr {
@@ -202,6 +207,9 @@ impl TableAoS {
             }
         }
         cache.compelemptr_vec_cache.insert(comp_elem_ptrs.into());
+        self.change_ticks.iter_mut().for_each(|ticks| {
+            ticks.extend(std::iter::repeat_n(ComponentTicks::new(tick), batch_len))
+        });
     }
 
     pub(crate) fn remove(&mut self, entity: &Entity) {
@@ -211,139 +219,114 @@ impl TableAoS {
                 self.drop_entity_row(row_id);
                 self.vec.drop_and_replace_with_last(self.len, row_id);
             }
+            self.change_ticks.iter_mut().for_each(|ticks| {
+                ticks.swap_remove(row_id);
+            });
             self.len -= 1;
         }
     }
 
-    pub(crate) unsafe fn transfer_entity_with_new_comp<T: Component>(
+    /// Moves the components of the supplied entity from one table into another
+    /// and returns the new row id of the entity inside of the table moved to.
+    /// Components, which are not stored inside of the table moved to, get dropped.
+    /// Moved components keep their change ticks, the added component is marked as added.
+    ///
+    /// #SAFETY:
+    /// The table moved to should store the optionally added component.
+    /// The caller of this function should forget/leak the added component value,
+    /// so that its owned allocations will not be dropped.
+    pub(crate) unsafe fn transfer_entity(
         component_infos: &[ComponentInfo],
         cache: &mut EntityStorageCache,
         from: &mut TableAoS,
         to: &mut TableAoS,
         entity: &Entity,
-        mut component: T,
-        comp_id: ComponentId,
+        added: Option<(ComponentId, NonNull<u8>)>,
+        tick: Tick,
     ) -> TableRowId {
-        // transfers entities components to different archetype table
+        let from_row_id = entity.row_id.id_usize();
         let mut from_ptr_vec: Vec<NonNull<u8>> = cache.ptr_vec_cache.take_cached();
         let mut from_compid_vec: Vec<ComponentId> = cache.compid_vec_cache.take_cached();
         let from_row_base_ptr = unsafe {
             from.vec
                 .data_ptr
-                .add(from.vec.elem_layout.size() * entity.row_id.id_usize())
+                .add(from.vec.elem_layout.size() * from_row_id)
         };
-        from.type_meta_data
-            .iter()
-            .map(|tmd| {
-                (tmd.comp_id, unsafe {
-                    from_row_base_ptr.add(tmd.ptr_offset)
-                })
-            })
-            .for_each(|(compid, ptr)| {
-                from_compid_vec.push(compid);
+        for tmd in from.type_meta_data.iter() {
+            let ptr = unsafe { from_row_base_ptr.add(tmd.ptr_offset) };
+            if to.contains_comp(tmd.comp_id) {
+                from_compid_vec.push(tmd.comp_id);
                 from_ptr_vec.push(ptr);
-            });
+            } else if let Some(drop_fn) = tmd.drop_fn {
+                unsafe { drop_fn(ptr.as_ptr()) };
+            }
+        }
 
         // add new components id and current ptr
-        from_compid_vec.push(comp_id);
-        from_ptr_vec.push(NonNull::from_mut(&mut component).cast());
+        if let Some((comp_id, added_ptr)) = added {
+            from_compid_vec.push(comp_id);
+            from_ptr_vec.push(added_ptr);
+        }
 
+        let new_to_table_entity_row_id = to.len.into();
         unsafe {
-            to.insert(component_infos, &from_compid_vec, &from_ptr_vec, cache);
+            to.insert(
+                component_infos,
+                &from_compid_vec,
+                &from_ptr_vec,
+                cache,
+                tick,
+            );
         }
         cache.ptr_vec_cache.insert(from_ptr_vec);
         cache.compid_vec_cache.insert(from_compid_vec);
 
-        // forget this added component, to prevent it from being dropped
-        // it now lives type erased inside of the aos table
-        std::mem::forget(component);
+        // moved components keep their ticks, the inserted row got new ticks
+        for (from_index, tmd) in from.type_meta_data.iter().enumerate() {
+            let comp_ticks = from.change_ticks[from_index].swap_remove(from_row_id);
+            if let Some(to_index) = to.meta_data_index(tmd.comp_id) {
+                if let Some(to_ticks) = to.change_ticks[to_index].last_mut() {
+                    *to_ticks = comp_ticks;
+                }
+            }
+        }
 
         // remove transfered entity from old table
         unsafe {
-            from.vec
-                .dont_drop_replace_with_last(from.len, entity.row_id.id_usize());
+            from.vec.dont_drop_replace_with_last(from.len, from_row_id);
         }
-
         from.len -= 1;
-        let new_to_table_entity_row_id = to.len.into();
-        to.len += 1;
         new_to_table_entity_row_id
     }
 
+    fn contains_comp(&self, comp_id: ComponentId) -> bool {
+        self.meta_data_index(comp_id).is_some()
+    }
 
-    pub(crate) unsafe fn transfer_entity(
-        component_infos: &[ComponentInfo],
-        cache: &mut EntityStorageCache,
-        from: &mut TableAoS,
-        to: &mut TableAoS,
-        entity: &Entity,
-    ) -> TableRowId {
-        // transfers entities components to different archetype table
-        let mut from_ptr_vec: Vec<NonNull<u8>> = cache.ptr_vec_cache.take_cached();
-        let mut from_compid_vec: Vec<ComponentId> = cache.compid_vec_cache.take_cached();
-        let from_row_base_ptr = unsafe {
-            from.vec
-                .data_ptr
-                .add(from.vec.elem_layout.size() * entity.row_id.id_usize())
-        };
-        from.type_meta_data
-            .iter()
-            .map(|tmd| {
-                (tmd.comp_id, unsafe {
-                    from_row_base_ptr.add(tmd.ptr_offset)
-                })
-            })
-            .for_each(|(compid, ptr)| {
-                from_compid_vec.push(compid);
-                from_ptr_vec.push(ptr);
-            });
-
-        unsafe {
-            to.insert(component_infos, &from_compid_vec, &from_ptr_vec, cache);
-        }
-        cache.ptr_vec_cache.insert(from_ptr_vec);
-        cache.compid_vec_cache.insert(from_compid_vec);
-
-        // remove transfered entity from old table
-        unsafe {
-            from.vec
-                .dont_drop_replace_with_last(from.len, entity.row_id.id_usize());
-        }
-
-        from.len -= 1;
-        let new_to_table_entity_row_id = to.len.into();
-        to.len += 1;
-        new_to_table_entity_row_id
+    fn meta_data_index(&self, comp_id: ComponentId) -> Option<usize> {
+        self.type_meta_data
+            .get_vec()
+            .binary_search_by_key(&comp_id, |tmd| tmd.comp_id)
+            .ok()
     }
 
-    //TODO
-    fn get_compid_ptr_vecs(
-        component_infos: &[ComponentInfo],
-        cache: &mut EntityStorageCache,
-        from: &mut TableAoS,
-        to: &mut TableAoS,
-        entity: &Entity,
-    ){
-        let mut from_ptr_vec: Vec<NonNull<u8>> = cache.ptr_vec_cache.take_cached();
-        let mut from_compid_vec: Vec<ComponentId> = cache.compid_vec_cache.take_cached();
-        let from_row_base_ptr = unsafe {
-            from.vec
-                .data_ptr
-                .add(from.vec.elem_layout.size() * entity.row_id.id_usize())
-        };
-        from.type_meta_data
-            .iter()
-            .map(|tmd| {
-                (tmd.comp_id, unsafe {
-                    from_row_base_ptr.add(tmd.ptr_offset)
-                })
-            })
-            .for_each(|(compid, ptr)| {
-                from_compid_vec.push(compid);
-                from_ptr_vec.push(ptr);
-            });
+    pub(crate) fn get_change_ticks(
+        &self,
+        comp_id: ComponentId,
+        row: usize,
+    ) -> Option<ComponentTicks> {
+        let index = self.meta_data_index(comp_id)?;
+        self.change_ticks[index].get(row).copied()
     }
 
+    /// Marks the component of the entity in the supplied row as changed.
+    pub(crate) fn set_changed(&mut self, comp_id: ComponentId, row: usize, tick: Tick) {
+        if let Some(index) = self.meta_data_index(comp_id) {
+            if let Some(ticks) = self.change_ticks[index].get_mut(row) {
+                ticks.changed = tick;
+            }
+        }
+    }
 
     /*
     #[allow(unused)]
@@ -371,16 +354,17 @@ impl TableAoS {
 
     pub(crate) unsafe fn get_single_comp_iter_mut<'c, T: Component>(
         &'c mut self,
-    ) -> ThinBlobInnerTypeIterMutUnsafe<'c, T> {
-        let index = self
-            .type_meta_data_map
-            .get(&TypeId::of::<T>())
-            .expect(&format!(
+        this_run: Tick,
+    ) -> (
+        ThinBlobInnerTypeIterMutUnsafe<'c, T>,
+        ChangeTicksIterUnsafe<'c>,
+    ) {
+        unsafe {
+            self.get_single_comp_iter_mut_opt(this_run).expect(&format!(
                 "No component id found for type id of type: {}.",
                 type_name::<T>()
-            ));
-        let offset = &self.type_meta_data.get_vec()[*index].ptr_offset;
-        unsafe { self.vec.tuple_inner_type_iter_mut(*offset) }
+            ))
+        }
     }
 
     pub(crate) unsafe fn get_single_comp_iter_opt<'c, T: Component>(
@@ -396,13 +380,15 @@ impl TableAoS {
 
     pub(crate) unsafe fn get_single_comp_iter_mut_opt<'c, T: Component>(
         &'c mut self,
-    ) -> Option<ThinBlobInnerTypeIterMutUnsafe<'c, T>> {
-        if let Some(index) = self.type_meta_data_map.get(&TypeId::of::<T>()) {
-            let offset = &self.type_meta_data.get_vec()[*index].ptr_offset;
-            Some(unsafe { self.vec.tuple_inner_type_iter_mut(*offset) })
-        } else {
-            None
-        }
+        this_run: Tick,
+    ) -> Option<(
+        ThinBlobInnerTypeIterMutUnsafe<'c, T>,
+        ChangeTicksIterUnsafe<'c>,
+    )> {
+        let index = *self.type_meta_data_map.get(&TypeId::of::<T>())?;
+        let offset = self.type_meta_data.get_vec()[index].ptr_offset;
+        let ticks = ChangeTicksIterUnsafe::new(&mut self.change_ticks[index], this_run);
+        Some((unsafe { self.vec.tuple_inner_type_iter_mut(offset) }, ticks))
     }
 
     unsafe fn drop_entity_row(&mut self, index: usize) {
@@ -554,7 +540,7 @@ mod test {
     ) {
         println!("testsystem1 res: {}, {}", prm.value, prm2.value);
 
-        for (comp1, comp2) in query.iter() {
+        for (comp1, mut comp2) in query.iter() {
             println!("comp1: {:?}", comp1);
             println!("comp2: {:?}", comp2);
             println!("comp1: {}", comp1.0);
@@ -565,7 +551,7 @@ mod test {
 
         assert_eq!(query.iter().count(), 3);
 
-        for (pos, pos4, pos3) in query2.iter() {
+        for (pos, mut pos4, pos3) in query2.iter() {
             println!("pos1 : {:?}", pos);
             println!("pos3: {:?}", pos3);
 
@@ -8,6 +8,7 @@ use std::{
 
 use crate::{
     ecs::{
+        change_detection::{ChangeTicksIterUnsafe, ComponentTicks, Tick},
         component::{ArchetypeId, Component, ComponentId, ComponentInfo, Map},
         entity::{Entity, TableRowId},
     },
@@ -24,6 +25,8 @@ pub(crate) struct TableSoA {
     #[allow(unused)]
     pub(crate) archetype_id: ArchetypeId,
     pub(crate) columns: Map<TypeId, ThinBlobVec>,
+    // change ticks of the components, stored next to their column
+    pub(crate) change_ticks: Map<TypeId, Vec<ComponentTicks>>,
     pub(crate) len: usize,
     pub(crate) cap: usize,
 }
@@ -31,15 +34,18 @@ pub(crate) struct TableSoA {
 impl TableSoA {
     pub(crate) fn new(archetype_id: ArchetypeId, entity_storage: &EntityStorage) -> Self {
         let mut columns = Map::new();
+        let mut change_ticks = Map::new();
         let archetype = &entity_storage.archetypes[usize::from(archetype_id)];
         archetype.soa_comp_ids.get_vec().iter().for_each(|cid| {
             let cinfo = &entity_storage.components[usize::from(*cid)];
             columns.insert(cinfo.type_id, ThinBlobVec::new(cinfo.layout, cinfo.drop));
+            change_ticks.insert(cinfo.type_id, Vec::new());
         });
 
         Self {
             archetype_id,
             columns,
+            change_ticks,
             len: 0,
             cap: 0,
         }
@@ -66,11 +72,8 @@ impl TableSoA {
         component_infos: &[ComponentInfo],
         soa_comp_ids: &[ComponentId],
         soa_ptrs: &[NonNull<u8>],
+        tick: Tick,
     ) {
-        if soa_comp_ids.len() <= 0 {
-            return;
-        }
-
         for (i, cid) in soa_comp_ids.iter().enumerate() {
             let cinfo = &component_infos[cid.0 as usize];
             unsafe {
@@ -79,6 +82,7 @@ impl TableSoA {
                     .expect("Type T is not stored inside this table!")
                     .push_untyped(self.cap, self.len, soa_ptrs[i]);
             }
+            self.push_change_ticks(&cinfo.type_id, tick);
         }
 
         self.update_capacity();
@@ -107,13 +111,8 @@ impl TableSoA {
         soa_base_ptrs: &[NonNull<u8>],
         value_layout: Layout,
         batch_len: usize,
+        tick: Tick,
     ) {
-        // no components marked for SOA storage,
-        // return early and do no work
-        if soa_comp_ids.len() == 0 {
-            return;
-        }
-
         //TODO: why did i do this, document
         let mut thin_columns: Vec<NonNull<ThinBlobVec>> = Vec::with_capacity(soa_comp_ids.len());
         for cid in soa_comp_ids.iter() {
@@ -124,6 +123,10 @@ impl TableSoA {
                     .expect("Type T is not stored inside this table!")
                     .into(),
             );
+            self.change_ticks
+                .get_mut(&cinfo.type_id)
+                .expect("Type T is not stored inside this table!")
+                .extend(std::iter::repeat_n(ComponentTicks::new(tick), batch_len));
         }
 
         for i in 0..batch_len {
@@ -140,26 +143,53 @@ impl TableSoA {
         }
     }
 
-    //TODO: add safety comments
-    pub(crate) fn transfer_entity_with_new_comp<T: Component>(
+    /// Moves the components of the supplied entity from one table into another
+    /// and returns the new row id of the entity inside of the table moved to.
+    /// Components without a column inside of the table moved to get dropped.
+    /// Moved components keep their change ticks, the added component is marked as added.
+    ///
+    /// #SAFETY:
+    /// The table moved to should contain a column for the optionally added component.
+    /// The caller of this function should forget/leak the added component value,
+    /// so that its owned allocations will not be dropped.
+    pub(crate) unsafe fn transfer_entity(
         from: &mut TableSoA,
         to: &mut TableSoA,
         entity: &Entity,
-        component: T,
+        added: Option<(TypeId, NonNull<u8>)>,
+        tick: Tick,
     ) -> TableRowId {
-        let from_iter = from
-            .columns
-            .iter_mut()
-            .filter(|(tid, _)| **tid != TypeId::of::<T>());
-        //SAFETY: from_len and the from_iter are from the same table, so from_len and the single
-        //columns should have the same length
-        unsafe {
-            Self::transfer_entity(from.len, from_iter, to, entity);
+        let from_row_id = entity.row_id.id_usize();
+        for (type_id, from_col) in from.columns.iter_mut() {
+            let from_ticks = from
+                .change_ticks
+                .get_mut(type_id)
+                .expect("Every column should have its change ticks.");
+            let comp_ticks = from_ticks.swap_remove(from_row_id);
+            //SAFETY: from.len is equal to the length of all columns of the from table
+            unsafe {
+                if let Some(to_col) = to.columns.get_mut(type_id) {
+                    let from_col_elem_ptr =
+                        from_col.get_ptr_untyped(from_row_id, from_col.elem_layout);
+                    to_col.push_untyped(to.cap, to.len, from_col_elem_ptr);
+                    from_col.dont_drop_replace_with_last(from.len, from_row_id);
+                    to.change_ticks
+                        .get_mut(type_id)
+                        .expect("Every column should have its change ticks.")
+                        .push(comp_ticks);
+                } else {
+                    from_col.drop_and_replace_with_last(from.len, from_row_id);
+                }
+            }
         }
-        let to_col_added_comp = to.columns.get_mut(&TypeId::of::<T>())
-            .expect("TableSoA to move entity to does not contain needed column to add the new component to.");
-        unsafe {
-            to_col_added_comp.push_typed(to.cap, to.len, component);
+        if let Some((type_id, added_ptr)) = added {
+            let to_col_added_comp = to.columns.get_mut(&type_id).expect(
+                "TableSoA to move entity to does not contain needed column to add the new component to.",
+            );
+            unsafe {
+                to_col_added_comp.push_untyped(to.cap, to.len, added_ptr);
+            }
+            to.push_change_ticks(&type_id, tick);
         }
         from.len -= 1;
         let new_to_table_entity_row_id = to.len.into();
@@ -168,54 +198,25 @@ impl TableSoA {
         new_to_table_entity_row_id
     }
 
-    pub(crate) fn remove_comp_and_transfer_entity<T: Component>(
-        from: &mut TableSoA,
-        to: &mut TableSoA,
-        entity: &Entity,
-    ) -> TableRowId {
-        // remove and drop component from from_table
-        let column_drop_comp_from = from
-            .columns
-            .get_mut(&TypeId::of::<T>())
-            .expect("Component to be removed from entity is not contained in AoS table.");
-        //SAFETY: table contains a columns for the to be removed component
-        unsafe {
-            column_drop_comp_from.drop_and_replace_with_last(from.len, entity.row_id.id_usize());
-        }
-        let from_iter = from
-            .columns
-            .iter_mut()
-            .filter(|(tid, _)| **tid != TypeId::of::<T>());
-        //SAFETY: from_len and the from_iter are from the same table, so from_len and the single
-        //columns should have the same length
-        unsafe {
-            Self::transfer_entity(from.len, from_iter, to, entity);
-        }
-        from.len -= 1;
-        let new_to_table_entity_row_id = to.len.into();
-        to.len += 1;
-        to.update_capacity();
-        new_to_table_entity_row_id
+    fn push_change_ticks(&mut self, type_id: &TypeId, tick: Tick) {
+        self.change_ticks
+            .get_mut(type_id)
+            .expect("Type T is not stored inside this table!")
+            .push(ComponentTicks::new(tick));
     }
 
-    unsafe fn transfer_entity<'to>(
-        from_len: usize,
-        from_iter: impl Iterator<Item = (&'to TypeId, &'to mut ThinBlobVec)>,
-        to: &mut TableSoA,
-        entity: &Entity,
-    ) {
-        for (type_id, from_col) in from_iter {
-            let to_col = to
-                .columns
-                .get_mut(type_id)
-                .expect("TableSoA to move entity to does not contain needed component column.");
-            let from_row_id = entity.row_id.id_usize();
-            let from_col_elem_ptr = from_col.get_ptr_untyped(from_row_id, from_col.elem_layout);
-            //SAFETY: from_len needs to be equal to the length of the columns in the from_iter
-            unsafe {
-                to_col.push_untyped(to.cap, to.len, from_col_elem_ptr);
-                from_col.dont_drop_replace_with_last(from_len, from_row_id);
-            }
+    pub(crate) fn get_change_ticks(&self, type_id: &TypeId, row: usize) -> Option<ComponentTicks> {
+        self.change_ticks.get(type_id)?.get(row).copied()
+    }
+
+    /// Marks the component of the entity in the supplied row as changed.
+    pub(crate) fn set_changed(&mut self, type_id: &TypeId, row: usize, tick: Tick) {
+        if let Some(ticks) = self
+            .change_ticks
+            .get_mut(type_id)
+            .and_then(|t| t.get_mut(row))
+        {
+            ticks.changed = tick;
         }
     }
 
@@ -234,6 +235,9 @@ impl TableSoA {
                     col.drop_and_replace_with_last(self.len, entity.row_id.id_usize());
                 }
             }
+            for ticks in self.change_ticks.values_mut() {
+                ticks.swap_remove(entity.row_id.id_usize());
+            }
             self.len -= 1;
         }
     }
@@ -281,27 +285,28 @@ impl TableSoA {
     /// otherwise this function will panic.
     pub(crate) unsafe fn get_single_comp_iter_mut<'c, T: Component>(
         &'c mut self,
-    ) -> ThinBlobIterMutUnsafe<'c, T> {
+        this_run: Tick,
+    ) -> (ThinBlobIterMutUnsafe<'c, T>, ChangeTicksIterUnsafe<'c>) {
         unsafe {
-            self.columns
-                .get_mut(&TypeId::of::<T>())
-                .expect(&format!(
-                    "No column with type id for type: {}.",
-                    type_name::<T>()
-                ))
-                .tuple_iter_mut()
+            self.get_single_comp_iter_mut_opt(this_run).expect(&format!(
+                "No column with type id for type: {}.",
+                type_name::<T>()
+            ))
         }
     }
 
     pub(crate) unsafe fn get_single_comp_iter_mut_opt<'c, T: Component>(
         &'c mut self,
-    ) -> Option<ThinBlobIterMutUnsafe<'c, T>> {
+        this_run: Tick,
+    ) -> Option<(ThinBlobIterMutUnsafe<'c, T>, ChangeTicksIterUnsafe<'c>)> {
+        let type_id = TypeId::of::<T>();
+        let col = self.columns.get_mut(&type_id)?;
+        let ticks = self.change_ticks.get_mut(&type_id)?;
         unsafe {
-            if let Some(col) = self.columns.get_mut(&TypeId::of::<T>()) {
-                Some(col.tuple_iter_mut())
-            } else {
-                None
-            }
+            Some((
+                col.tuple_iter_mut(),
+                ChangeTicksIterUnsafe::new(ticks, this_run),
+            ))
         }
     }
 }
@@ -426,7 +431,7 @@ mod tests {
         println!("testsystem1 res: {}, {}", prm.value, prm2.value);
 
         let mut count = 0;
-        for (comp1, comp2) in query.iter() {
+        for (comp1, mut comp2) in query.iter() {
             println!("comp1: {}", comp1.0);
             println!("comp2: {}", comp2.0);
             comp2.0 = 2;
@@ -437,7 +442,7 @@ mod tests {
         assert_eq!(count, 3);
         assert_eq!(query.iter().count(), 3);
 
-        for (_pos, pos4, _pos3) in query2.iter() {
+        for (_pos, mut pos4, _pos3) in query2.iter() {
             println!("pos4 : {}", pos4.0);
             pos4.0 = 23234;
             pos4.0 -= 2344;
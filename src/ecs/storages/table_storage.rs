@@ -1,15 +1,19 @@
 // table_storage.rs
 
-use std::alloc::Layout;
+use std::{alloc::Layout, any::TypeId, ops::Range, ptr::NonNull};
 
 use crate::{
     ecs::{
-        component::{ArchetypeId, Component, ComponentId, ComponentInfo, StorageTypes},
+        change_detection::{ChangeTicksIterUnsafe, ComponentTicks, Mut, Tick},
+        component::{ArchetypeId, Component, ComponentId, ComponentInfo, Map, StorageTypes},
         entity::{Entity, EntityKey, EntityKeyIterUnsafe, TableRowId},
         query::QueryParam,
-        storages::thin_blob_vec::{
-            ThinBlobInnerTypeIterMutUnsafe, ThinBlobInnerTypeIterUnsafe, ThinBlobIterMutUnsafe,
-            ThinBlobIterUnsafe,
+        storages::{
+            sparse_set::{SparseSetIterMutUnsafe, SparseSetIterUnsafe, SparseSets},
+            thin_blob_vec::{
+                ThinBlobInnerTypeIterMutUnsafe, ThinBlobInnerTypeIterUnsafe, ThinBlobIterMutUnsafe,
+                ThinBlobIterUnsafe,
+            },
         },
     },
     utils::{
@@ -29,6 +33,12 @@ pub struct TableStorage {
     pub(crate) entities: Vec<EntityKey>,
     pub(crate) table_soa: TableSoA,
     pub(crate) table_aos: TableAoS,
+    // sparse set components of the entities in this table are stored
+    // outside of the table, the pointed to sparse sets are boxed by the entity storage
+    pub(crate) sparse_sets: NonNull<SparseSets>,
+    // targets of the relation pairs of the archetype, keyed by the type of their relation kind,
+    // all entities in this table share the same relation targets
+    pub(crate) relation_targets: Map<TypeId, Vec<EntityKey>>,
     //pub(crate) len: u32,
 }
 
@@ -38,10 +48,30 @@ impl TableStorage {
             entities: Vec::new(),
             table_soa: TableSoA::new(archetype_id, entity_storage),
             table_aos: TableAoS::new(archetype_id, entity_storage),
+            sparse_sets: NonNull::from(&*entity_storage.sparse_sets),
+            relation_targets: Self::relation_targets(archetype_id, entity_storage),
             //len: 0,
         }
     }
 
+    pub(crate) fn relation_targets(
+        archetype_id: ArchetypeId,
+        entity_storage: &EntityStorage,
+    ) -> Map<TypeId, Vec<EntityKey>> {
+        let mut relation_targets: Map<TypeId, Vec<EntityKey>> = Map::new();
+        let archetype = &entity_storage.archetypes[archetype_id.id_usize()];
+        for cid in archetype.relation_comp_ids.iter() {
+            let cinfo = &entity_storage.components[cid.id_usize()];
+            if let Some((_kind, target)) = cinfo.relation {
+                relation_targets
+                    .entry(cinfo.type_id)
+                    .or_default()
+                    .push(target);
+            }
+        }
+        relation_targets
+    }
+
     // Returns the row id in the table of the inserted entity.
     //
     // #SAFETY:
@@ -54,26 +84,41 @@ impl TableStorage {
         component_infos: &[ComponentInfo],
         soa_comp_ids: &[ComponentId],
         aos_comp_ids: &[ComponentId],
+        sparse_comp_ids: &[ComponentId],
+        sparse_sets: &mut SparseSets,
         cache: &mut EntityStorageCache,
         mut value: T,
+        tick: Tick,
     ) -> TableRowId {
         let row_id = self.entities.len().into();
 
         let mut soa_ptr_vec = cache.ptr_vec_cache.take_cached();
         let mut aos_ptr_vec = cache.ptr_vec_cache.take_cached();
+        let mut sparse_ptr_vec = cache.ptr_vec_cache.take_cached();
 
-        value.self_get_value_ptrs_by_storage(&mut soa_ptr_vec, &mut aos_ptr_vec);
+        value.self_get_value_ptrs_by_storage(
+            &mut soa_ptr_vec,
+            &mut aos_ptr_vec,
+            &mut sparse_ptr_vec,
+        );
 
         unsafe {
             self.table_soa
-                .insert(component_infos, &soa_comp_ids, &soa_ptr_vec);
+                .insert(component_infos, &soa_comp_ids, &soa_ptr_vec, tick);
             self.table_aos
-                .insert(component_infos, &aos_comp_ids, &aos_ptr_vec, cache);
+                .insert(component_infos, &aos_comp_ids, &aos_ptr_vec, cache, tick);
+            for (cid, ptr) in sparse_comp_ids.iter().zip(sparse_ptr_vec.iter()) {
+                sparse_sets
+                    .get_mut(cid)
+                    .expect("Sparse set for component should exist at this point.")
+                    .insert_untyped(entity, *ptr, tick);
+            }
         }
         std::mem::forget(value);
 
         cache.ptr_vec_cache.insert(soa_ptr_vec);
         cache.ptr_vec_cache.insert(aos_ptr_vec);
+        cache.ptr_vec_cache.insert(sparse_ptr_vec);
 
         self.entities.push(entity);
         row_id
@@ -93,8 +138,11 @@ impl TableStorage {
         component_infos: &[ComponentInfo],
         soa_comp_ids: &[ComponentId],
         aos_comp_ids: &[ComponentId],
+        sparse_comp_ids: &[ComponentId],
+        sparse_sets: &mut SparseSets,
         cache: &mut EntityStorageCache,
         mut values: Vec<T>,
+        tick: Tick,
     ) -> Option<(u32, u32)> {
         if values.len() == 0 {
             return None;
@@ -102,8 +150,13 @@ impl TableStorage {
 
         let mut soa_ptr_vec = cache.ptr_vec_cache.take_cached();
         let mut aos_ptr_vec = cache.ptr_vec_cache.take_cached();
+        let mut sparse_ptr_vec = cache.ptr_vec_cache.take_cached();
 
-        values[0].self_get_value_ptrs_by_storage(&mut soa_ptr_vec, &mut aos_ptr_vec);
+        values[0].self_get_value_ptrs_by_storage(
+            &mut soa_ptr_vec,
+            &mut aos_ptr_vec,
+            &mut sparse_ptr_vec,
+        );
         let value_layout = Layout::new::<T>();
 
         self.entities.extend(entities.iter());
@@ -114,6 +167,7 @@ impl TableStorage {
                 &soa_ptr_vec,
                 value_layout,
                 values.len(),
+                tick,
             );
             self.table_aos.insert_batch(
                 component_infos,
@@ -122,11 +176,21 @@ impl TableStorage {
                 value_layout,
                 values.len(),
                 cache,
+                tick,
             );
+            for (cid, base_ptr) in sparse_comp_ids.iter().zip(sparse_ptr_vec.iter()) {
+                let sparse_set = sparse_sets
+                    .get_mut(cid)
+                    .expect("Sparse set for component should exist at this point.");
+                for (i, entity) in entities.iter().enumerate() {
+                    sparse_set.insert_untyped(*entity, base_ptr.add(value_layout.size() * i), tick);
+                }
+            }
         }
 
         cache.ptr_vec_cache.insert(soa_ptr_vec);
         cache.ptr_vec_cache.insert(aos_ptr_vec);
+        cache.ptr_vec_cache.insert(sparse_ptr_vec);
 
         let row_id_start = self.entities.len() as u32;
         let row_id_end = row_id_start + values.len() as u32;
@@ -161,6 +225,66 @@ impl TableStorage {
         self.remove_replace_with_last_entity_key(entity)
     }
 
+    /// Moves the supplied entity with its table components from one table into another.
+    /// Components, which are not stored inside of the table moved to, get dropped.
+    /// Returns the new row id of the moved entity and, if another entity had to be moved
+    /// to fill the gap inside of the old table, its key and new row id.
+    ///
+    /// #SAFETY:
+    /// The table moved to should store the optionally added component.
+    /// The caller of this function should forget/leak the added component value,
+    /// so that its owned allocations will not be dropped.
+    pub(crate) unsafe fn transfer_entity(
+        component_infos: &[ComponentInfo],
+        cache: &mut EntityStorageCache,
+        from: &mut TableStorage,
+        to: &mut TableStorage,
+        entity: Entity,
+        added: Option<(ComponentId, NonNull<u8>)>,
+        tick: Tick,
+    ) -> (TableRowId, Option<(EntityKey, TableRowId)>) {
+        let (soa_added, aos_added) = match added {
+            Some((comp_id, ptr)) => {
+                let comp_info = &component_infos[comp_id.id_usize()];
+                match comp_info.storage {
+                    StorageTypes::TableSoA => (Some((comp_info.type_id, ptr)), None),
+                    StorageTypes::TableAoS => (None, Some((comp_id, ptr))),
+                    StorageTypes::SparseSet => {
+                        unreachable!("Sparse set components are not stored inside of tables.")
+                    }
+                }
+            }
+            None => (None, None),
+        };
+        let new_row_id = unsafe {
+            TableSoA::transfer_entity(
+                &mut from.table_soa,
+                &mut to.table_soa,
+                &entity,
+                soa_added,
+                tick,
+            )
+        };
+        let aos_row_id = unsafe {
+            TableAoS::transfer_entity(
+                component_infos,
+                cache,
+                &mut from.table_aos,
+                &mut to.table_aos,
+                &entity,
+                aos_added,
+                tick,
+            )
+        };
+        debug_assert_eq!(new_row_id, aos_row_id);
+
+        let entity_key = from.entities[entity.row_id.id_usize()];
+        // need to update row id of replace entity of transfered entity
+        let replaced_entity_info = from.remove_replace_with_last_entity_key(entity);
+        to.entities.push(entity_key);
+        (new_row_id, replaced_entity_info)
+    }
+
     pub(crate) fn remove_replace_with_last_entity_key(
         &mut self,
         entity: Entity,
@@ -178,22 +302,58 @@ impl TableStorage {
         }
     }
 
+    /// Returns the change ticks of the component of the entity in the supplied row,
+    /// if the entity owns the component.
+    pub(crate) fn get_change_ticks(
+        &self,
+        comp_info: &ComponentInfo,
+        row: usize,
+    ) -> Option<ComponentTicks> {
+        match comp_info.storage {
+            StorageTypes::TableSoA => self.table_soa.get_change_ticks(&comp_info.type_id, row),
+            StorageTypes::TableAoS => self.table_aos.get_change_ticks(comp_info.comp_id, row),
+            StorageTypes::SparseSet => unsafe {
+                self.sparse_sets
+                    .as_ref()
+                    .get(&comp_info.comp_id)?
+                    .get_ticks(*self.entities.get(row)?)
+            },
+        }
+    }
+
     pub(crate) fn get_entity_components<P: QueryParam>(
         &mut self,
         entity: Entity,
+        this_run: Tick,
     ) -> Option<<P::Construct<'_> as TupleIterator>::Item> {
         unsafe {
-            <_ as Iterator>::next(&mut new_table_storage_iter_with_index::<P>(
-                self,
-                entity.row_id.id_usize(),
-            ))
+            new_table_storage_iter_with_index::<P>(self, entity.row_id.id_usize(), this_run)
+                .get_current()
         }
     }
 
     pub(crate) unsafe fn tuple_iter<'a, TC: TupleIterConstructor<TableStorage>>(
         &'a mut self,
+        this_run: Tick,
     ) -> TableStorageTupleIter<TC::Construct<'a>> {
-        unsafe { new_table_storage_iter::<TC>(self) }
+        unsafe { new_table_storage_iter::<TC>(self, this_run) }
+    }
+
+    // Iterator over a range of rows, used to split the table into chunks,
+    // which can be iterated in parallel.
+    pub(crate) unsafe fn tuple_iter_rows<'a, TC: TupleIterConstructor<TableStorage>>(
+        &'a mut self,
+        rows: Range<usize>,
+        this_run: Tick,
+    ) -> TableStorageTupleIter<TC::Construct<'a>> {
+        let len = rows.end.min(self.entities.len());
+        unsafe {
+            TableStorageTupleIter {
+                tuple_iters: TC::construct(self.into(), this_run),
+                len,
+                index: rows.start,
+            }
+        }
     }
 }
 
@@ -205,10 +365,11 @@ pub(crate) struct TableStorageTupleIter<T: TupleIterator> {
 
 pub(crate) unsafe fn new_table_storage_iter<'table, TC: TupleIterConstructor<TableStorage>>(
     table: &'table mut TableStorage,
+    this_run: Tick,
 ) -> TableStorageTupleIter<TC::Construct<'table>> {
     unsafe {
         TableStorageTupleIter {
-            tuple_iters: TC::construct(table.into()),
+            tuple_iters: TC::construct(table.into(), this_run),
             len: table.entities.len(),
             index: 0,
         }
@@ -221,32 +382,53 @@ pub(crate) unsafe fn new_table_storage_iter_with_index<
 >(
     table: &'table mut TableStorage,
     index: usize,
+    this_run: Tick,
 ) -> TableStorageTupleIter<TC::Construct<'table>> {
     unsafe {
         TableStorageTupleIter {
-            tuple_iters: TC::construct(table.into()),
+            tuple_iters: TC::construct(table.into(), this_run),
             len: table.entities.len(),
             index: index,
         }
     }
 }
 
+impl<T: TupleIterator> TableStorageTupleIter<T> {
+    /// Returns the item of the row at the current index without advancing,
+    /// if the row is contained in the table and matches.
+    pub(crate) fn get_current(&mut self) -> Option<T::Item> {
+        if self.index < self.len && self.tuple_iters.row_matches(self.index) {
+            unsafe { Some(self.tuple_iters.next(self.index)) }
+        } else {
+            None
+        }
+    }
+
+    /// Row index of the item last returned by next.
+    pub(crate) fn last_row_index(&self) -> usize {
+        self.index - 1
+    }
+}
+
 impl<T: TupleIterator> Iterator for TableStorageTupleIter<T> {
     type Item = T::Item;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.len {
-            let next = unsafe { Some(self.tuple_iters.next(self.index)) };
+        while self.index < self.len {
+            let index = self.index;
             self.index += 1;
-            next
-        } else {
-            None
+            // skip rows, e.g. of entities without required sparse set components
+            if self.tuple_iters.row_matches(index) {
+                return unsafe { Some(self.tuple_iters.next(index)) };
+            }
         }
+        None
     }
 }
 
 pub enum TableStorageIterUnsafe<'c, T: Component> {
     TableSoaIter(ThinBlobIterUnsafe<'c, T>),
     TableAosIter(ThinBlobInnerTypeIterUnsafe<'c, T>),
+    SparseSetIter(SparseSetIterUnsafe<'c, T>),
 }
 
 impl<'c, T: Component> TupleIterator for TableStorageIterUnsafe<'c, T> {
@@ -255,36 +437,63 @@ impl<'c, T: Component> TupleIterator for TableStorageIterUnsafe<'c, T> {
         match self {
             TableStorageIterUnsafe::TableSoaIter(iter) => unsafe { iter.next(index) },
             TableStorageIterUnsafe::TableAosIter(iter) => unsafe { iter.next(index) },
+            TableStorageIterUnsafe::SparseSetIter(iter) => unsafe { iter.next(index) },
+        }
+    }
+    fn row_matches(&self, index: usize) -> bool {
+        match self {
+            TableStorageIterUnsafe::SparseSetIter(iter) => iter.row_matches(index),
+            _ => true,
         }
     }
 }
 
+// Mutable iterators also track the change ticks of the components,
+// so that their items can mark the components as changed.
 pub enum TableStorageIterMutUnsafe<'c, T: Component> {
-    TableSoaIterMut(ThinBlobIterMutUnsafe<'c, T>),
-    TableAosIterMut(ThinBlobInnerTypeIterMutUnsafe<'c, T>),
+    TableSoaIterMut(ThinBlobIterMutUnsafe<'c, T>, ChangeTicksIterUnsafe<'c>),
+    TableAosIterMut(
+        ThinBlobInnerTypeIterMutUnsafe<'c, T>,
+        ChangeTicksIterUnsafe<'c>,
+    ),
+    SparseSetIterMut(SparseSetIterMutUnsafe<'c, T>),
 }
 
 impl<'c, T: Component> TupleIterator for TableStorageIterMutUnsafe<'c, T> {
-    type Item = &'c mut T;
+    type Item = Mut<'c, T>;
     #[inline(always)]
     unsafe fn next(&mut self, index: usize) -> Self::Item {
         match self {
-            TableStorageIterMutUnsafe::TableSoaIterMut(iter) => unsafe { iter.next(index) },
-            TableStorageIterMutUnsafe::TableAosIterMut(iter) => unsafe { iter.next(index) },
+            TableStorageIterMutUnsafe::TableSoaIterMut(iter, ticks) => unsafe {
+                ticks.wrap(index, iter.next(index))
+            },
+            TableStorageIterMutUnsafe::TableAosIterMut(iter, ticks) => unsafe {
+                ticks.wrap(index, iter.next(index))
+            },
+            TableStorageIterMutUnsafe::SparseSetIterMut(iter) => unsafe { iter.next(index) },
+        }
+    }
+    fn row_matches(&self, index: usize) -> bool {
+        match self {
+            TableStorageIterMutUnsafe::SparseSetIterMut(iter) => iter.row_matches(index),
+            _ => true,
         }
     }
 }
 
 impl<'c, T: Component> TupleIterator for Option<TableStorageIterMutUnsafe<'c, T>> {
-    type Item = Option<&'c mut T>;
+    type Item = Option<Mut<'c, T>>;
     #[inline(always)]
     unsafe fn next(&mut self, index: usize) -> Self::Item {
         match self {
-            Some(TableStorageIterMutUnsafe::TableSoaIterMut(iter)) => unsafe {
-                Some(iter.next(index))
+            Some(TableStorageIterMutUnsafe::TableSoaIterMut(iter, ticks)) => unsafe {
+                Some(ticks.wrap(index, iter.next(index)))
             },
-            Some(TableStorageIterMutUnsafe::TableAosIterMut(iter)) => unsafe {
-                Some(iter.next(index))
+            Some(TableStorageIterMutUnsafe::TableAosIterMut(iter, ticks)) => unsafe {
+                Some(ticks.wrap(index, iter.next(index)))
+            },
+            Some(TableStorageIterMutUnsafe::SparseSetIterMut(iter)) => unsafe {
+                iter.next_opt(index)
             },
             None => None,
         }
@@ -298,6 +507,7 @@ impl<'c, T: Component> TupleIterator for Option<TableStorageIterUnsafe<'c, T>> {
         match self {
             Some(TableStorageIterUnsafe::TableSoaIter(iter)) => unsafe { Some(iter.next(index)) },
             Some(TableStorageIterUnsafe::TableAosIter(iter)) => unsafe { Some(iter.next(index)) },
+            Some(TableStorageIterUnsafe::SparseSetIter(iter)) => unsafe { iter.next_opt(index) },
             None => None,
         }
     }
@@ -305,15 +515,21 @@ impl<'c, T: Component> TupleIterator for Option<TableStorageIterUnsafe<'c, T>> {
 
 impl<T: Component> TupleIterConstructor<TableStorage> for Option<&T> {
     type Construct<'c> = Option<<TableStorage as TupleConstructorSource>::IterType<'c, T>>;
-    unsafe fn construct<'s>(mut source: std::ptr::NonNull<TableStorage>) -> Self::Construct<'s> {
+    unsafe fn construct<'s>(
+        mut source: std::ptr::NonNull<TableStorage>,
+        _this_run: Tick,
+    ) -> Self::Construct<'s> {
         unsafe { source.as_mut().get_iter_opt() }
     }
 }
 
 impl<T: Component> TupleIterConstructor<TableStorage> for Option<&mut T> {
     type Construct<'c> = Option<<TableStorage as TupleConstructorSource>::IterMutType<'c, T>>;
-    unsafe fn construct<'s>(mut source: std::ptr::NonNull<TableStorage>) -> Self::Construct<'s> {
-        unsafe { source.as_mut().get_iter_mut_opt() }
+    unsafe fn construct<'s>(
+        mut source: std::ptr::NonNull<TableStorage>,
+        this_run: Tick,
+    ) -> Self::Construct<'s> {
+        unsafe { source.as_mut().get_iter_mut_opt(this_run) }
     }
 }
 
@@ -331,18 +547,31 @@ impl TupleConstructorSource for TableStorage {
             StorageTypes::TableAoS => TableStorageIterUnsafe::TableAosIter(unsafe {
                 self.table_aos.get_single_comp_iter()
             }),
-            StorageTypes::SparseSet => unimplemented!(),
+            StorageTypes::SparseSet => TableStorageIterUnsafe::SparseSetIter(unsafe {
+                self.sparse_sets
+                    .as_ref()
+                    .get_single_comp_iter(&self.entities)
+            }),
         }
     }
-    unsafe fn get_iter_mut<'c, T: Component>(&'c mut self) -> Self::IterMutType<'c, T> {
+    unsafe fn get_iter_mut<'c, T: Component>(
+        &'c mut self,
+        this_run: Tick,
+    ) -> Self::IterMutType<'c, T> {
         match T::STORAGE {
-            StorageTypes::TableSoA => TableStorageIterMutUnsafe::TableSoaIterMut(unsafe {
-                self.table_soa.get_single_comp_iter_mut()
-            }),
-            StorageTypes::TableAoS => TableStorageIterMutUnsafe::TableAosIterMut(unsafe {
-                self.table_aos.get_single_comp_iter_mut()
+            StorageTypes::TableSoA => {
+                let (iter, ticks) = unsafe { self.table_soa.get_single_comp_iter_mut(this_run) };
+                TableStorageIterMutUnsafe::TableSoaIterMut(iter, ticks)
+            }
+            StorageTypes::TableAoS => {
+                let (iter, ticks) = unsafe { self.table_aos.get_single_comp_iter_mut(this_run) };
+                TableStorageIterMutUnsafe::TableAosIterMut(iter, ticks)
+            }
+            StorageTypes::SparseSet => TableStorageIterMutUnsafe::SparseSetIterMut(unsafe {
+                self.sparse_sets
+                    .as_mut()
+                    .get_single_comp_iter_mut(&self.entities, this_run)
             }),
-            StorageTypes::SparseSet => unimplemented!(),
         }
     }
     unsafe fn get_iter_opt<'c, T: Component>(&'c mut self) -> Option<Self::IterType<'c, T>> {
@@ -355,20 +584,39 @@ impl TupleConstructorSource for TableStorage {
                 let iter = unsafe { self.table_aos.get_single_comp_iter_opt()? };
                 Some(TableStorageIterUnsafe::TableAosIter(iter))
             }
-            StorageTypes::SparseSet => unimplemented!(),
+            StorageTypes::SparseSet => {
+                let iter = unsafe {
+                    self.sparse_sets
+                        .as_ref()
+                        .get_single_comp_iter_opt(&self.entities)?
+                };
+                Some(TableStorageIterUnsafe::SparseSetIter(iter))
+            }
         }
     }
-    unsafe fn get_iter_mut_opt<'c, T: Component>(&'c mut self) -> Option<Self::IterMutType<'c, T>> {
+    unsafe fn get_iter_mut_opt<'c, T: Component>(
+        &'c mut self,
+        this_run: Tick,
+    ) -> Option<Self::IterMutType<'c, T>> {
         match T::STORAGE {
             StorageTypes::TableSoA => {
-                let iter = unsafe { self.table_soa.get_single_comp_iter_mut_opt()? };
-                Some(TableStorageIterMutUnsafe::TableSoaIterMut(iter))
+                let (iter, ticks) =
+                    unsafe { self.table_soa.get_single_comp_iter_mut_opt(this_run)? };
+                Some(TableStorageIterMutUnsafe::TableSoaIterMut(iter, ticks))
             }
             StorageTypes::TableAoS => {
-                let iter = unsafe { self.table_aos.get_single_comp_iter_mut_opt()? };
-                Some(TableStorageIterMutUnsafe::TableAosIterMut(iter))
+                let (iter, ticks) =
+                    unsafe { self.table_aos.get_single_comp_iter_mut_opt(this_run)? };
+                Some(TableStorageIterMutUnsafe::TableAosIterMut(iter, ticks))
+            }
+            StorageTypes::SparseSet => {
+                let iter = unsafe {
+                    self.sparse_sets
+                        .as_mut()
+                        .get_single_comp_iter_mut_opt(&self.entities, this_run)?
+                };
+                Some(TableStorageIterMutUnsafe::SparseSetIterMut(iter))
             }
-            StorageTypes::SparseSet => unimplemented!(),
         }
     }
 }
@@ -242,10 +242,10 @@ impl ThinBlobVec {
                         std::ptr::copy(value_ptr_src.as_ptr(), dst_comp_ptr, layout_size);
                     }
                 }
-
-                *len += 1;
             }
         }
+        // rows of zero-sized-types are still counted
+        *len += 1;
     }
 
     #[allow(unused)]
@@ -262,7 +262,6 @@ impl ThinBlobVec {
         new_cap
     }
 
-    #[allow(unused)]
     pub(crate) fn get_ptr_untyped(&self, index: usize, layout: Layout) -> NonNull<u8> {
         if layout.size() != 0 {
             unsafe { self.data_ptr.add(index * layout.size()) }
@@ -312,6 +311,16 @@ impl ThinBlobVec {
         }
     }
 
+    // Pointer to a component inside of an aos row, the offset is the offset of the component
+    // inside of the row layout.
+    pub(crate) fn get_inner_ptr_untyped(&self, index: usize, offset: usize) -> NonNull<u8> {
+        if self.elem_layout.size() != 0 {
+            unsafe { self.data_ptr.add(index * self.elem_layout.size() + offset) }
+        } else {
+            self.data_ptr
+        }
+    }
+
     pub(crate) unsafe fn get_mut_inner_typed_lifetime<'vec, T>(
         &mut self,
         index: usize,
@@ -12,8 +12,11 @@ use builder::{IntoSystemConfig, IntoSystemTuple, SystemConfig};
 use crate::{
     all_tuples,
     ecs::{
+        change_detection::{SystemTicks, Tick},
         ecs_dependency_graph::{EcsEdge, QueryId},
+        event::EventReaderId,
         resource::ResourceId,
+        schedule::ScheduleLabel,
     },
     utils::ecs_id::{EcsId, impl_ecs_id},
 };
@@ -21,8 +24,11 @@ use crate::{
 use super::world::WorldData;
 
 pub mod builder;
+pub mod condition;
+pub mod exclusive;
+pub mod pipe;
 
-type StoredSystem = Box<dyn System + Sync + Send>;
+pub(crate) type StoredSystem = Box<dyn System + Sync + Send>;
 
 /// Storage for systems.
 pub struct Systems {
@@ -31,7 +37,18 @@ pub struct Systems {
     // with systemId as index
     pub(crate) constraints: HashMap<SystemId, Constraint>,
     pub(crate) system_param_data: HashMap<SystemId, Vec<SystemParamId>>,
-    func_system_map: HashMap<TypeId, SystemId>,
+    // ids of the systems, which belong to a schedule
+    pub(crate) schedules: HashMap<ScheduleLabel, HashSet<SystemId>>,
+    // ids of the run condition systems of a system
+    pub(crate) conditions: HashMap<SystemId, Vec<SystemId>>,
+    // instances of every added system function,
+    // the first one is shared by all additions of the function, which are no instances
+    func_system_map: HashMap<TypeId, Vec<SystemId>>,
+    // system functions, which are only referenced by constraints so far,
+    // with the schedule of the referencing systems
+    referenced_systems: Vec<(TypeId, StoredSystem, ScheduleLabel)>,
+    // constraints are resolved on init, when all instances of the referenced functions are known
+    pending_constraints: Vec<PendingConstraint>,
 }
 
 unsafe impl Send for Systems {}
@@ -47,10 +64,32 @@ pub enum RefType {
 #[derive(Debug)]
 pub enum SystemParamId {
     Resource(ResourceId, RefType),
+    // resource params, which don't require the resource to exist
+    OptionalResource(ResourceId, RefType),
+    // resources, which are not Send, their systems run on the thread, which owns the world
+    NonSendResource(ResourceId, RefType),
     Query(QueryId),
+    EventReader(ResourceId, EventReaderId),
+    // index of the local in the locals of the system
+    Local(SystemId, usize),
+    Commands,
     NotRelevant,
 }
 
+/// System referenced by an after or before constraint.
+/// System functions are resolved to all of their instances, when the systems get initialized.
+#[derive(Debug, Clone, Copy)]
+pub enum ConstraintTarget {
+    System(SystemId),
+    Func(TypeId),
+}
+
+struct PendingConstraint {
+    system_ids: Vec<SystemId>,
+    after: Vec<ConstraintTarget>,
+    before: Vec<ConstraintTarget>,
+}
+
 #[derive(Debug)]
 pub(crate) struct Constraint {
     #[allow(unused)]
@@ -75,7 +114,11 @@ impl Systems {
             system_vec: Vec::new(),
             constraints: HashMap::new(),
             system_param_data: HashMap::new(),
+            schedules: HashMap::new(),
+            conditions: HashMap::new(),
             func_system_map: HashMap::new(),
+            referenced_systems: Vec::new(),
+            pending_constraints: Vec::new(),
         }
     }
 
@@ -89,27 +132,97 @@ impl Systems {
         &self.system_param_data.get(system_id).unwrap()
     }
 
+    /// Adds the system, if its function wasn't added before.
+    /// Otherwise the id of the already added system is returned.
     pub fn add_system<Input, S: System + 'static, IS: IntoSystem<Input, System = S> + 'static>(
         &mut self,
         value: IS,
     ) -> SystemId {
-        let config_id = TypeId::of::<IS>();
-        self.add_system_inner(value, config_id)
+        if let Some(system_ids) = self.func_system_map.get(&TypeId::of::<IS>()) {
+            return system_ids[0];
+        }
+        self.add_system_instance(value)
     }
 
-    pub fn add_system_inner<Input, S: System + 'static>(
+    /// Adds a new instance of the system, even if its function was added before.
+    /// Adding the same function multiple times creates multiple independent systems.
+    pub fn add_system_instance<
+        Input,
+        S: System + 'static,
+        IS: IntoSystem<Input, System = S> + 'static,
+    >(
         &mut self,
-        value: impl IntoSystem<Input, System = S>,
-        sys_config_id: TypeId,
+        value: IS,
     ) -> SystemId {
-        let system = value.into_system();
-        if let Some(system_id) = self.func_system_map.get(&sys_config_id) {
-            return *system_id;
-        }
+        let type_id = TypeId::of::<IS>();
+        self.referenced_systems
+            .retain(|(ref_type_id, ..)| *ref_type_id != type_id);
         let next_id: SystemId = self.system_vec.len().into();
-        self.system_vec.push(Box::new(system));
-        self.func_system_map.insert(sys_config_id, next_id);
-        next_id.into()
+        self.system_vec.push(Box::new(value.into_system()));
+        self.func_system_map
+            .entry(type_id)
+            .or_default()
+            .push(next_id);
+        next_id
+    }
+
+    // References the system function in a constraint.
+    // The system is kept until init, in case its function is never added explicitly.
+    pub(crate) fn reference_system<
+        Input,
+        S: System + 'static,
+        IS: IntoSystem<Input, System = S> + 'static,
+    >(
+        &mut self,
+        value: IS,
+        schedule: ScheduleLabel,
+    ) -> ConstraintTarget {
+        let type_id = TypeId::of::<IS>();
+        if !self.func_system_map.contains_key(&type_id)
+            && !self
+                .referenced_systems
+                .iter()
+                .any(|(ref_type_id, ..)| *ref_type_id == type_id)
+        {
+            self.referenced_systems
+                .push((type_id, Box::new(value.into_system()), schedule));
+        }
+        ConstraintTarget::Func(type_id)
+    }
+
+    // Resolves the pending constraints to all instances of the referenced system functions.
+    // Functions, which were only referenced by constraints, are added
+    // to the schedule of the first referencing systems.
+    fn resolve_constraints(&mut self) {
+        for (type_id, system, schedule) in std::mem::take(&mut self.referenced_systems) {
+            let system_id: SystemId = self.system_vec.len().into();
+            self.system_vec.push(system);
+            self.func_system_map.insert(type_id, vec![system_id]);
+            self.schedules
+                .entry(schedule)
+                .or_default()
+                .insert(system_id);
+        }
+        for pending in std::mem::take(&mut self.pending_constraints) {
+            let after = self.resolve_constraint_targets(&pending.after);
+            let before = self.resolve_constraint_targets(&pending.before);
+            for system_id in pending.system_ids {
+                self.add_system_constraints(system_id, &after, &before);
+            }
+        }
+    }
+
+    fn resolve_constraint_targets(&self, targets: &[ConstraintTarget]) -> Vec<SystemId> {
+        let mut system_ids = Vec::new();
+        for target in targets {
+            match target {
+                ConstraintTarget::System(system_id) => system_ids.push(*system_id),
+                ConstraintTarget::Func(type_id) => {
+                    system_ids.extend(&self.func_system_map[type_id]);
+                }
+            }
+        }
+        system_ids
     }
 
     pub fn add_system_builder<
@@ -129,28 +242,26 @@ impl Systems {
             chain,
             after,
             before,
+            schedule,
+            conditions,
         } = value.build();
         let mut system_ids = Vec::new();
         system_tuple.add_systems_to_stor(self, &mut system_ids);
 
-        let after: Vec<SystemId> = if let Some(after) = after {
-            let mut system_ids = Vec::new();
-            after.add_systems_to_stor(self, &mut system_ids);
-            system_ids
-        } else {
-            Vec::new()
-        };
-
-        let before: Vec<SystemId> = if let Some(before) = before {
-            let mut system_ids = Vec::new();
-            before.add_systems_to_stor(self, &mut system_ids);
-            system_ids
-        } else {
-            Vec::new()
-        };
-
-        for system_id in system_ids.iter() {
-            self.add_system_constraints(*system_id, &after, &before);
+        let mut after_targets = Vec::new();
+        if let Some(after) = after {
+            after.constraint_targets(self, schedule, &mut after_targets);
+        }
+        let mut before_targets = Vec::new();
+        if let Some(before) = before {
+            before.constraint_targets(self, schedule, &mut before_targets);
+        }
+        if !after_targets.is_empty() || !before_targets.is_empty() {
+            self.pending_constraints.push(PendingConstraint {
+                system_ids: system_ids.clone(),
+                after: after_targets,
+                before: before_targets,
+            });
         }
 
         if chain {
@@ -174,9 +285,53 @@ impl Systems {
             }
         }
 
+        self.schedules
+            .entry(schedule)
+            .or_default()
+            .extend(system_ids.iter());
+
+        // run conditions are stored as systems, which are not part of any schedule
+        for condition in conditions {
+            let condition_id: SystemId = self.system_vec.len().into();
+            self.system_vec.push(condition);
+            for system_id in system_ids.iter() {
+                self.conditions
+                    .entry(*system_id)
+                    .or_default()
+                    .push(condition_id);
+            }
+        }
+
         system_ids
     }
 
+    /// Evaluates the run conditions of a system.
+    /// The results are cached, so that conditions shared by multiple systems only run once.
+    pub(crate) fn conditions_met(
+        &mut self,
+        system_id: SystemId,
+        world_data: *mut WorldData,
+        results: &mut HashMap<SystemId, bool>,
+    ) -> bool {
+        let Some(condition_ids) = self.conditions.get(&system_id) else {
+            return true;
+        };
+        for condition_id in condition_ids.iter() {
+            let result = *results.entry(*condition_id).or_insert_with(|| {
+                self.system_vec[condition_id.id_usize()].run_condition(
+                    self.system_param_data
+                        .get(condition_id)
+                        .expect("Run condition should be initialized."),
+                    world_data,
+                )
+            });
+            if !result {
+                return false;
+            }
+        }
+        true
+    }
+
     fn add_system_constraints(
         &mut self,
         system_id: SystemId,
@@ -232,6 +387,7 @@ impl Systems {
     }
 
     pub fn init_systems(&mut self, world_data: &mut WorldData) {
+        self.resolve_constraints();
         for (system_id, system) in self.system_vec.iter_mut().enumerate() {
             let system_id: SystemId = system_id.into();
             let mut system_param_ids = Vec::new();
@@ -276,15 +432,32 @@ pub trait System: Send + Sync {
     );
     //TODO: make unsafe
     fn run(&mut self, system_param_ids: &[SystemParamId], world_data: *mut WorldData);
+    /// Exclusive systems access the whole world and can't run in parallel to other systems.
+    fn is_exclusive(&self) -> bool {
+        false
+    }
+    /// Runs the system as a run condition and returns, if the conditioned systems should run.
+    /// Systems, which are no run conditions, always allow the conditioned systems to run.
+    fn run_condition(
+        &mut self,
+        system_param_ids: &[SystemParamId],
+        world_data: *mut WorldData,
+    ) -> bool {
+        self.run(system_param_ids, world_data);
+        true
+    }
 }
 
 pub trait SystemParam: Send + Sync {
     type Item<'new>;
 
+    /// The system ticks contain the ticks of the last and the current run of the system,
+    /// which are used for change detection.
     unsafe fn retrieve<'r>(
         system_param_index: &mut usize,
         system_param_ids: &[SystemParamId],
         world_data: *mut WorldData,
+        system_ticks: SystemTicks,
     ) -> Self::Item<'r>;
     fn create_system_param_data(
         system_id: SystemId,
@@ -293,6 +466,10 @@ pub trait SystemParam: Send + Sync {
     );
 }
 
+/// Marker for system params, which only access the world immutably.
+/// Only those can be used in run conditions.
+pub trait ReadOnlySystemParam: SystemParam {}
+
 pub struct Res<'a, T> {
     pub value: &'a T,
 }
@@ -327,12 +504,15 @@ impl<'a, T> DerefMut for ResMut<'a, T> {
     }
 }
 
+/// Takes the resource out of the world, the system consumes it.
+/// The resource does not exist afterwards, unless it gets added again.
+/// Until then the system is skipped, like all systems requiring the resource.
+/// Systems owning resources run in their own batch, because the resources of the world change.
 pub struct ResOwned<T> {
     pub value: T,
 }
 
-unsafe impl<'a, T> Send for ResOwned<T> {}
-unsafe impl<'a, T> Sync for ResOwned<T> {}
+impl<'res, T: 'static> ReadOnlySystemParam for Res<'res, T> {}
 
 impl<'res, T: 'static> SystemParam for Res<'res, T> {
     type Item<'new> = Res<'new, T>;
@@ -341,6 +521,7 @@ impl<'res, T: 'static> SystemParam for Res<'res, T> {
         system_param_index: &mut usize,
         _system_param_ids: &[SystemParamId],
         world_data: *mut WorldData,
+        _system_ticks: SystemTicks,
     ) -> Self::Item<'r> {
         *system_param_index += 1;
         unsafe {
@@ -358,14 +539,14 @@ impl<'res, T: 'static> SystemParam for Res<'res, T> {
         system_param_ids: &mut Vec<SystemParamId>,
         world_data: &mut WorldData,
     ) {
-        let resource_id = ResourceId::new(TypeId::of::<T>());
+        let resource_id = ResourceId::of::<T>();
         world_data.get_depend_graph_mut().insert_system_resource(
             system_id,
             resource_id,
             EcsEdge::Shared,
         );
         system_param_ids.push(SystemParamId::Resource(
-            ResourceId::new(TypeId::of::<T>()),
+            ResourceId::of::<T>(),
             RefType::Shared,
         ));
     }
@@ -378,6 +559,7 @@ impl<'res, T: 'static> SystemParam for ResMut<'res, T> {
         system_param_index: &mut usize,
         _system_param_ids: &[SystemParamId],
         world_data: *mut WorldData,
+        _system_ticks: SystemTicks,
     ) -> Self::Item<'r> {
         *system_param_index += 1;
         unsafe {
@@ -395,53 +577,322 @@ impl<'res, T: 'static> SystemParam for ResMut<'res, T> {
         system_param_ids: &mut Vec<SystemParamId>,
         world_data: &mut WorldData,
     ) {
-        let resource_id = ResourceId::new(TypeId::of::<T>());
+        let resource_id = ResourceId::of::<T>();
         world_data.get_depend_graph_mut().insert_system_resource(
             system_id,
             resource_id,
             EcsEdge::Excl,
         );
         system_param_ids.push(SystemParamId::Resource(
-            ResourceId::new(TypeId::of::<T>()),
+            ResourceId::of::<T>(),
             RefType::Exclusive,
         ));
     }
 }
 
-impl<T: 'static> SystemParam for ResOwned<T> {
-    type Item<'new> = ResOwned<T>;
+impl<'res, T: 'static> ReadOnlySystemParam for Option<Res<'res, T>> {}
+
+/// Optional resources yield None instead of panicking, if the resource does not exist.
+impl<'res, T: 'static> SystemParam for Option<Res<'res, T>> {
+    type Item<'new> = Option<Res<'new, T>>;
 
     unsafe fn retrieve<'r>(
         system_param_index: &mut usize,
         _system_param_ids: &[SystemParamId],
         world_data: *mut WorldData,
+        _system_ticks: SystemTicks,
+    ) -> Self::Item<'r> {
+        *system_param_index += 1;
+        unsafe { (*world_data).resources.get().map(|value| Res { value }) }
+    }
+
+    fn create_system_param_data(
+        system_id: SystemId,
+        system_param_ids: &mut Vec<SystemParamId>,
+        world_data: &mut WorldData,
+    ) {
+        let resource_id = ResourceId::of::<T>();
+        world_data.get_depend_graph_mut().insert_system_resource(
+            system_id,
+            resource_id,
+            EcsEdge::Shared,
+        );
+        system_param_ids.push(SystemParamId::OptionalResource(
+            resource_id,
+            RefType::Shared,
+        ));
+    }
+}
+
+impl<'res, T: 'static> SystemParam for Option<ResMut<'res, T>> {
+    type Item<'new> = Option<ResMut<'new, T>>;
+
+    unsafe fn retrieve<'r>(
+        system_param_index: &mut usize,
+        _system_param_ids: &[SystemParamId],
+        world_data: *mut WorldData,
+        _system_ticks: SystemTicks,
     ) -> Self::Item<'r> {
         *system_param_index += 1;
         unsafe {
             (*world_data)
                 .resources
-                .remove()
-                .expect("Requested resource does not exist!")
+                .get_mut()
+                .map(|value| ResMut { value })
         }
     }
+
     fn create_system_param_data(
         system_id: SystemId,
         system_param_ids: &mut Vec<SystemParamId>,
         world_data: &mut WorldData,
     ) {
-        let resource_id = ResourceId::new(TypeId::of::<T>());
+        let resource_id = ResourceId::of::<T>();
+        world_data.get_depend_graph_mut().insert_system_resource(
+            system_id,
+            resource_id,
+            EcsEdge::Excl,
+        );
+        system_param_ids.push(SystemParamId::OptionalResource(
+            resource_id,
+            RefType::Exclusive,
+        ));
+    }
+}
+
+impl<T: Send + Sync + 'static> SystemParam for ResOwned<T> {
+    type Item<'new> = ResOwned<T>;
+
+    unsafe fn retrieve<'r>(
+        system_param_index: &mut usize,
+        _system_param_ids: &[SystemParamId],
+        world_data: *mut WorldData,
+        _system_ticks: SystemTicks,
+    ) -> Self::Item<'r> {
+        *system_param_index += 1;
+        let value = unsafe {
+            (*world_data)
+                .remove_resource()
+                .expect("Requested resource does not exist!")
+        };
+        ResOwned { value }
+    }
+    fn create_system_param_data(
+        system_id: SystemId,
+        system_param_ids: &mut Vec<SystemParamId>,
+        world_data: &mut WorldData,
+    ) {
+        let resource_id = ResourceId::of::<T>();
         world_data.get_depend_graph_mut().insert_system_resource(
             system_id,
             resource_id,
             EcsEdge::Owned,
         );
         system_param_ids.push(SystemParamId::Resource(
-            ResourceId::new(TypeId::of::<T>()),
+            ResourceId::of::<T>(),
             RefType::Owned,
         ));
     }
 }
 
+/// Shared access to a resource, which is not Send.
+/// Systems using it are never dispatched onto other threads.
+pub struct NonSend<'a, T> {
+    pub value: &'a T,
+}
+
+// #SAFETY: the scheduler runs systems using them on the thread, which owns the world,
+// retrieving them on another thread panics
+unsafe impl<'a, T> Send for NonSend<'a, T> {}
+unsafe impl<'a, T> Sync for NonSend<'a, T> {}
+
+impl<'a, T> Deref for NonSend<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+/// Exclusive access to a resource, which is not Send.
+/// Systems using it are never dispatched onto other threads.
+pub struct NonSendMut<'a, T> {
+    pub value: &'a mut T,
+}
+
+unsafe impl<'a, T> Send for NonSendMut<'a, T> {}
+unsafe impl<'a, T> Sync for NonSendMut<'a, T> {}
+
+impl<'a, T> Deref for NonSendMut<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<'a, T> DerefMut for NonSendMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+impl<'res, T: 'static> ReadOnlySystemParam for NonSend<'res, T> {}
+
+impl<'res, T: 'static> SystemParam for NonSend<'res, T> {
+    type Item<'new> = NonSend<'new, T>;
+
+    unsafe fn retrieve<'r>(
+        system_param_index: &mut usize,
+        _system_param_ids: &[SystemParamId],
+        world_data: *mut WorldData,
+        _system_ticks: SystemTicks,
+    ) -> Self::Item<'r> {
+        *system_param_index += 1;
+        unsafe {
+            NonSend {
+                value: (*world_data)
+                    .get_non_send_resource()
+                    .expect("Requested non send resource does not exist!"),
+            }
+        }
+    }
+
+    fn create_system_param_data(
+        system_id: SystemId,
+        system_param_ids: &mut Vec<SystemParamId>,
+        world_data: &mut WorldData,
+    ) {
+        let resource_id = ResourceId::of::<T>();
+        world_data.get_depend_graph_mut().insert_system_resource(
+            system_id,
+            resource_id,
+            EcsEdge::Shared,
+        );
+        system_param_ids.push(SystemParamId::NonSendResource(resource_id, RefType::Shared));
+    }
+}
+
+impl<'res, T: 'static> SystemParam for NonSendMut<'res, T> {
+    type Item<'new> = NonSendMut<'new, T>;
+
+    unsafe fn retrieve<'r>(
+        system_param_index: &mut usize,
+        _system_param_ids: &[SystemParamId],
+        world_data: *mut WorldData,
+        _system_ticks: SystemTicks,
+    ) -> Self::Item<'r> {
+        *system_param_index += 1;
+        unsafe {
+            NonSendMut {
+                value: (*world_data)
+                    .get_non_send_resource_mut()
+                    .expect("Requested non send resource does not exist!"),
+            }
+        }
+    }
+
+    fn create_system_param_data(
+        system_id: SystemId,
+        system_param_ids: &mut Vec<SystemParamId>,
+        world_data: &mut WorldData,
+    ) {
+        let resource_id = ResourceId::of::<T>();
+        world_data.get_depend_graph_mut().insert_system_resource(
+            system_id,
+            resource_id,
+            EcsEdge::Excl,
+        );
+        system_param_ids.push(SystemParamId::NonSendResource(
+            resource_id,
+            RefType::Exclusive,
+        ));
+    }
+}
+
+/// Returns true, if a system with these params takes resources out of the world.
+pub(crate) fn takes_resources(system_param_ids: &[SystemParamId]) -> bool {
+    system_param_ids
+        .iter()
+        .any(|sys_prm_id| matches!(sys_prm_id, SystemParamId::Resource(_, RefType::Owned)))
+}
+
+/// Returns true, if a system with these params has to run on the thread, which owns the world.
+pub(crate) fn requires_world_thread(system_param_ids: &[SystemParamId]) -> bool {
+    system_param_ids
+        .iter()
+        .any(|sys_prm_id| matches!(sys_prm_id, SystemParamId::NonSendResource(..)))
+}
+
+/// State, which belongs to a single system and persists between its runs.
+/// It starts with the default value and is not shared with other systems,
+/// so it does not restrict the parallel execution of systems.
+pub struct Local<'a, T> {
+    pub value: &'a mut T,
+}
+
+unsafe impl<'a, T> Send for Local<'a, T> {}
+unsafe impl<'a, T> Sync for Local<'a, T> {}
+
+impl<'a, T> Deref for Local<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<'a, T> DerefMut for Local<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+// locals are only accessible by their own system
+impl<'a, T: Default + Send + 'static> ReadOnlySystemParam for Local<'a, T> {}
+
+impl<'a, T: Default + Send + 'static> SystemParam for Local<'a, T> {
+    type Item<'new> = Local<'new, T>;
+
+    unsafe fn retrieve<'r>(
+        system_param_index: &mut usize,
+        system_param_ids: &[SystemParamId],
+        world_data: *mut WorldData,
+        _system_ticks: SystemTicks,
+    ) -> Self::Item<'r> {
+        let sys_prm_id = &system_param_ids[*system_param_index];
+        *system_param_index += 1;
+        if let SystemParamId::Local(system_id, local_index) = sys_prm_id {
+            let locals = unsafe { &mut (*world_data).system_locals };
+            let value = locals
+                .get_mut(system_id)
+                .and_then(|locals| locals.get_mut(*local_index))
+                .and_then(|local| local.downcast_mut())
+                .expect("Local of the system should be initialized.");
+            return Local { value };
+        }
+        panic!(
+            "SystemParamId=<{}> is not a Local! param_ids: {:?}",
+            *system_param_index - 1,
+            system_param_ids
+        )
+    }
+
+    fn create_system_param_data(
+        system_id: SystemId,
+        system_param_ids: &mut Vec<SystemParamId>,
+        world_data: &mut WorldData,
+    ) {
+        let local_index = system_param_ids
+            .iter()
+            .filter(|sys_prm_id| matches!(sys_prm_id, SystemParamId::Local(..)))
+            .count();
+        // the state is kept, if the system gets initialized again
+        let locals = world_data.system_locals.entry(system_id).or_default();
+        if locals.len() <= local_index {
+            locals.push(Box::new(T::default()));
+        }
+        system_param_ids.push(SystemParamId::Local(system_id, local_index));
+    }
+}
+
 macro_rules! impl_systemparam_for_tuples {
     ( $($t:ident), * ) => {
        impl<$($t : SystemParam,)*> SystemParam for ($($t,)*){
@@ -450,12 +901,13 @@ macro_rules! impl_systemparam_for_tuples {
          unsafe fn retrieve<'r>(
              system_param_index: &mut usize,
              system_param_ids: &[SystemParamId],
-             world_data: *mut WorldData
+             world_data: *mut WorldData,
+             system_ticks: SystemTicks,
          ) -> Self::Item<'r> {
              unsafe{
                  (
                    $(
-                     $t::retrieve(system_param_index, system_param_ids, world_data),
+                     $t::retrieve(system_param_index, system_param_ids, world_data, system_ticks),
                    )*
                  )
              }
@@ -480,9 +932,23 @@ all_tuples!(
     T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16
 );
 
+macro_rules! impl_read_only_systemparam_for_tuples {
+    ( $($t:ident), * ) => {
+       impl<$($t : ReadOnlySystemParam,)*> ReadOnlySystemParam for ($($t,)*){}
+    }
+}
+
+#[rustfmt::skip]
+all_tuples!(
+    impl_read_only_systemparam_for_tuples,
+    T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16
+);
+
 pub struct FunctionSystem<Input, F> {
     pub f: F,
     pub marker: PhantomData<fn() -> Input>,
+    // world change tick of the previous run of this system
+    pub(crate) last_run: Tick,
 }
 
 unsafe impl<F: FnMut()> Send for FunctionSystem<(), F> {}
@@ -538,9 +1004,12 @@ macro_rules! impl_system_for_params {
                ){
                   f($( $t,)*)
                }
+               let this_run = unsafe { (*world_data).increment_change_tick() };
+               let system_ticks = SystemTicks { last_run: self.last_run, this_run };
                let mut system_param_index = 0;
-               $(let $t = unsafe{$t::retrieve(&mut system_param_index, system_params, world_data)};)*
+               $(let $t = unsafe{$t::retrieve(&mut system_param_index, system_params, world_data, system_ticks)};)*
                call_inner(&mut self.f, $($t,)* );
+               self.last_run = this_run;
            }
        }
     };
@@ -565,6 +1034,7 @@ impl<F: FnMut()> IntoSystem<()> for F {
         FunctionSystem {
             f: self,
             marker: Default::default(),
+            last_run: Tick::default(),
         }
     }
 }
@@ -582,6 +1052,7 @@ macro_rules! impl_into_system_for_functionsystem {
                FunctionSystem{
                    f : self,
                    marker : Default::default(),
+                   last_run : Tick::default(),
                }
            }
         }
@@ -596,9 +1067,20 @@ all_tuples!(
 
 #[cfg(test)]
 mod test {
-    use crate::ecs::{system::ResMut, world::World};
+    use crate::ecs::{
+        ecs_dependency_graph::EcsEdge,
+        resource::{FromWorld, ResourceId},
+        schedule::ScheduleLabel,
+        system::ResMut,
+        world::{World, WorldData},
+    };
+
+    use std::{rc::Rc, thread::ThreadId};
 
-    use super::Res;
+    use super::{
+        Local, NonSend, NonSendMut, Res, ResOwned,
+        builder::{IntoSystemConfig, IntoSystemInstance},
+    };
 
     fn test_system1(prm: Res<i32>, prm2: ResMut<usize>) {
         println!("testsystem1 res: {}, {}", prm.value, prm2.value);
@@ -620,4 +1102,242 @@ mod test {
 
         world.init_and_run();
     }
+
+    struct Settings(u32);
+    struct Counter(u32);
+
+    fn optional_res_system(settings: Option<Res<Settings>>, counter: Option<ResMut<Counter>>) {
+        if let (Some(settings), Some(mut counter)) = (settings, counter) {
+            counter.0 += settings.0;
+        }
+    }
+
+    #[test]
+    fn test_optional_resources() {
+        let mut world = World::new();
+        let system_id = world.add_systems(optional_res_system).pop().unwrap();
+
+        // resources do not exist yet
+        world.init_and_run();
+        world.add_resource(Settings(3));
+        world.add_resource(Counter(1));
+        world.run();
+        assert_eq!(4, world.get_resource::<Counter>().unwrap().0);
+
+        let graph = world.data.get_mut().get_depend_graph();
+        let system_key = graph.system_keys[&system_id];
+        let edge_of = |resource_id| {
+            let resource_key = graph.resource_keys[&resource_id];
+            graph.resources[resource_key as usize].system_edges[&system_key]
+        };
+        assert_eq!(EcsEdge::Shared, edge_of(ResourceId::of::<Settings>()));
+        assert_eq!(EcsEdge::Excl, edge_of(ResourceId::of::<Counter>()));
+    }
+
+    #[derive(Default)]
+    struct Runs(Vec<(usize, usize)>);
+
+    fn first_local_system(mut count: Local<usize>, mut runs: ResMut<Runs>) {
+        *count += 1;
+        runs.0.push((1, *count));
+    }
+
+    fn second_local_system(mut count: Local<usize>, mut doubled: Local<usize>) {
+        *count += 1;
+        *doubled += 2;
+        assert_eq!(*count * 2, *doubled);
+    }
+
+    #[test]
+    fn test_local() {
+        let mut world = World::new();
+        world.add_resource(Runs::default());
+        let first_id = world.add_systems(first_local_system).pop().unwrap();
+        let second_id = world.add_systems(second_local_system).pop().unwrap();
+        world.init_and_run();
+        world.run();
+        world.run();
+
+        assert_eq!(
+            vec![(1, 1), (1, 2), (1, 3)],
+            world.get_resource::<Runs>().unwrap().0
+        );
+        // locals don't add edges to the graph
+        let graph = world.data.get_mut().get_depend_graph();
+        let second_key = graph.system_keys[&second_id] as usize;
+        assert!(graph.systems[second_key].resource_edges.is_empty());
+        let first_key = graph.system_keys[&first_id] as usize;
+        assert_eq!(1, graph.systems[first_key].resource_edges.len());
+    }
+
+    // not Send because of the Rc
+    #[derive(Default)]
+    struct ThreadLog(Vec<ThreadId>, Rc<()>);
+
+    #[derive(Default)]
+    struct SendCounter(usize);
+
+    fn record_thread_system(mut log: NonSendMut<ThreadLog>) {
+        log.0.push(std::thread::current().id());
+    }
+
+    fn read_thread_system(log: NonSend<ThreadLog>, mut counter: ResMut<SendCounter>) {
+        counter.0 += log.0.len();
+    }
+
+    fn count_system(mut counter: ResMut<SendCounter>) {
+        counter.0 += 100;
+    }
+
+    #[test]
+    fn test_non_send_resources() {
+        let mut world = World::new();
+        world.add_non_send_resource(ThreadLog::default());
+        world.add_resource(SendCounter::default());
+        world.add_systems(record_thread_system);
+        world.add_systems(record_thread_system.instance());
+        world.add_systems(read_thread_system.after(record_thread_system));
+        world.add_systems(count_system);
+        world.init_and_run();
+        world.run();
+
+        let owner_thread = std::thread::current().id();
+        let log = world.get_non_send_resource::<ThreadLog>().unwrap();
+        assert_eq!(4, log.0.len());
+        assert!(log.0.iter().all(|thread_id| *thread_id == owner_thread));
+        // the log is read after both instances recorded their thread in each run
+        assert_eq!(
+            2 + 100 + 4 + 100,
+            world.get_resource::<SendCounter>().unwrap().0
+        );
+    }
+
+    #[test]
+    fn test_non_send_resource_on_other_thread() {
+        let mut world = World::new();
+        world.add_non_send_resource(ThreadLog::default());
+        let world_data = world.data.get_mut();
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                world_data
+                    .get_non_send_resource_mut::<ThreadLog>()
+                    .is_some()
+            })
+            .join()
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_non_send_resource_dropped_on_other_thread() {
+        let mut world = World::new();
+        let rc = Rc::new(());
+        world.add_non_send_resource(ThreadLog(Vec::new(), rc.clone()));
+        let result = std::thread::spawn(move || drop(world)).join();
+        assert!(result.is_err());
+        // the resource was leaked instead of dropped on the other thread
+        assert_eq!(2, Rc::strong_count(&rc));
+    }
+
+    #[derive(Default, Debug, PartialEq)]
+    struct Gravity(i32);
+
+    #[derive(Debug, PartialEq)]
+    struct DoubleGravity(i32);
+
+    impl FromWorld for DoubleGravity {
+        fn from_world(world_data: &mut WorldData) -> Self {
+            DoubleGravity(world_data.get_resource::<Gravity>().unwrap().0 * 2)
+        }
+    }
+
+    #[test]
+    fn test_resource_management() {
+        let mut world = World::new();
+        assert!(!world.contains_resource::<Gravity>());
+        world.init_resource::<Gravity>();
+        assert_eq!(Some(&Gravity(0)), world.get_resource::<Gravity>());
+        assert_eq!(
+            Some(Gravity(0)),
+            world.insert_or_replace_resource(Gravity(-10))
+        );
+        // existing resources are not initialized again
+        world.init_resource::<Gravity>();
+        world.init_resource::<DoubleGravity>();
+        assert_eq!(
+            Some(&DoubleGravity(-20)),
+            world.get_resource::<DoubleGravity>()
+        );
+
+        let resource_id = ResourceId::of::<Gravity>();
+        assert!(
+            world
+                .data
+                .get_mut()
+                .get_depend_graph()
+                .contains_resource(resource_id)
+        );
+        assert_eq!(Some(Gravity(-10)), world.remove_resource::<Gravity>());
+        assert!(!world.contains_resource::<Gravity>());
+        assert!(
+            !world
+                .data
+                .get_mut()
+                .get_depend_graph()
+                .contains_resource(resource_id)
+        );
+        assert_eq!(None, world.remove_resource::<Gravity>());
+        assert_eq!(None, world.insert_or_replace_resource(Gravity(5)));
+        assert!(
+            world
+                .data
+                .get_mut()
+                .get_depend_graph()
+                .contains_resource(resource_id)
+        );
+    }
+
+    fn consume_gravity_system(gravity: ResOwned<Gravity>, mut runs: ResMut<Runs>) {
+        runs.0.push((gravity.value.0 as usize, 0));
+    }
+
+    fn idle_system() {}
+
+    #[test]
+    fn test_res_owned() {
+        let mut world = World::new();
+        world.add_resource(Gravity(3));
+        world.add_resource(Runs::default());
+        let owner_id = world.add_systems(consume_gravity_system).pop().unwrap();
+        world.add_systems(idle_system);
+        world.init_and_run();
+
+        assert_eq!(vec![(3, 0)], world.get_resource::<Runs>().unwrap().0);
+        assert!(!world.contains_resource::<Gravity>());
+        assert!(
+            !world
+                .data
+                .get_mut()
+                .get_depend_graph()
+                .contains_resource(ResourceId::of::<Gravity>())
+        );
+        let schedule = &world.scheduler.schedule[&ScheduleLabel::Update];
+        let owner_batch = schedule
+            .iter()
+            .flatten()
+            .find(|batch| batch.contains(&owner_id))
+            .unwrap();
+        assert_eq!(1, owner_batch.len());
+
+        // the owning system is skipped until the resource is added again
+        world.run();
+        assert_eq!(vec![(3, 0)], world.get_resource::<Runs>().unwrap().0);
+        world.add_resource(Gravity(5));
+        world.run();
+        assert_eq!(
+            vec![(3, 0), (5, 0)],
+            world.get_resource::<Runs>().unwrap().0
+        );
+        assert!(!world.contains_resource::<Gravity>());
+    }
 }
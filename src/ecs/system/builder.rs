@@ -4,26 +4,93 @@ use std::marker::PhantomData;
 
 use crate::all_tuples_wout_single;
 
-use super::{IntoSystem, System, SystemId, Systems};
+use super::{
+    ConstraintTarget, IntoSystem, StoredSystem, System, SystemId, Systems, condition::IntoCondition,
+};
+use crate::ecs::schedule::ScheduleLabel;
 
 pub trait IntoSystemTuple<I> {
     fn add_systems_to_stor(self, sys_stor: &mut Systems, system_ids: &mut Vec<SystemId>);
+    /// References the systems in after or before constraints.
+    /// System functions reference all of their instances.
+    fn constraint_targets(
+        self,
+        sys_stor: &mut Systems,
+        schedule: ScheduleLabel,
+        targets: &mut Vec<ConstraintTarget>,
+    );
 }
 
 impl IntoSystemTuple<SystemId> for SystemId {
     fn add_systems_to_stor(self, _sys_stor: &mut Systems, system_ids: &mut Vec<SystemId>) {
         system_ids.push(self);
     }
+    fn constraint_targets(
+        self,
+        _sys_stor: &mut Systems,
+        _schedule: ScheduleLabel,
+        targets: &mut Vec<ConstraintTarget>,
+    ) {
+        targets.push(ConstraintTarget::System(self));
+    }
 }
 
 impl<I, S: System + 'static, IS: IntoSystem<I, System = S> + 'static> IntoSystemTuple<I> for IS {
     fn add_systems_to_stor(self, sys_stor: &mut Systems, system_ids: &mut Vec<SystemId>) {
         system_ids.push(sys_stor.add_system(self));
     }
+    fn constraint_targets(
+        self,
+        sys_stor: &mut Systems,
+        schedule: ScheduleLabel,
+        targets: &mut Vec<ConstraintTarget>,
+    ) {
+        targets.push(sys_stor.reference_system(self, schedule));
+    }
+}
+
+/// System, which is added as a new instance, created by `IntoSystemInstance::instance`.
+pub struct SystemInstance<IS>(IS);
+
+/// Marker for the input of system instances.
+pub struct SystemInstanceMarker<I>(PhantomData<I>);
+
+pub trait IntoSystemInstance<I>: IntoSystem<I> + Sized {
+    /// Adds the system as a new instance, even if its function was added before,
+    /// e.g. to run a generic system with different configurations.
+    /// Constraints referencing the function apply to all of its instances.
+    fn instance(self) -> SystemInstance<Self> {
+        SystemInstance(self)
+    }
+}
+
+impl<I, IS: IntoSystem<I>> IntoSystemInstance<I> for IS {}
+
+impl<I, S: System + 'static, IS: IntoSystem<I, System = S> + 'static>
+    IntoSystemTuple<SystemInstanceMarker<I>> for SystemInstance<IS>
+{
+    fn add_systems_to_stor(self, sys_stor: &mut Systems, system_ids: &mut Vec<SystemId>) {
+        system_ids.push(sys_stor.add_system_instance(self.0));
+    }
+    fn constraint_targets(
+        self,
+        sys_stor: &mut Systems,
+        schedule: ScheduleLabel,
+        targets: &mut Vec<ConstraintTarget>,
+    ) {
+        targets.push(sys_stor.reference_system(self.0, schedule));
+    }
 }
 
 impl IntoSystemTuple<()> for () {
     fn add_systems_to_stor(self, _sys_stor: &mut Systems, _system_ids: &mut Vec<SystemId>) {}
+    fn constraint_targets(
+        self,
+        _sys_stor: &mut Systems,
+        _schedule: ScheduleLabel,
+        _targets: &mut Vec<ConstraintTarget>,
+    ) {
+    }
 }
 
 macro_rules! impl_into_system_tuples {
@@ -40,6 +107,17 @@ macro_rules! impl_into_system_tuples {
                 let ($($t1), *) = self;
                 $($t1::add_systems_to_stor($t1, sys_stor, system_ids)); *
             }
+
+            fn constraint_targets(
+                self,
+                sys_stor: &mut Systems,
+                schedule: ScheduleLabel,
+                targets: &mut Vec<ConstraintTarget>
+            ) {
+                 #[allow(non_snake_case)]
+                let ($($t1), *) = self;
+                $($t1::constraint_targets($t1, sys_stor, schedule, targets)); *
+            }
         }
     }
 }
@@ -64,6 +142,8 @@ pub struct SystemConfig<
     pub(crate) chain: bool,
     pub(crate) after: Option<AS>,
     pub(crate) before: Option<BS>,
+    pub(crate) schedule: ScheduleLabel,
+    pub(crate) conditions: Vec<StoredSystem>,
 }
 
 pub trait IntoSystemConfig<
@@ -85,6 +165,13 @@ pub trait IntoSystemConfig<
         self,
         before_systems: ST2,
     ) -> impl IntoSystemConfig<I, ST, IA, AS, I2, ST2>;
+    /// Adds the systems to the schedule with the given label instead of the update schedule.
+    fn in_schedule(self, schedule: ScheduleLabel) -> SystemConfig<I, ST, IA, AS, IB, BS>;
+    /// Only runs the systems, if the condition returns true.
+    /// Skipped systems still satisfy the after and before constraints of other systems.
+    fn run_if<IC, C: IntoCondition<IC>>(self, condition: C) -> SystemConfig<I, ST, IA, AS, IB, BS>
+    where
+        C::Condition: 'static;
 }
 
 impl<I, ST: IntoSystemTuple<I>> IntoSystemConfig<I, ST, (), (), (), ()> for ST {
@@ -95,6 +182,8 @@ impl<I, ST: IntoSystemTuple<I>> IntoSystemConfig<I, ST, (), (), (), ()> for ST {
             chain: false,
             after: None,
             before: None,
+            schedule: ScheduleLabel::default(),
+            conditions: Vec::new(),
         }
     }
     fn chain(self) -> SystemConfig<I, ST, (), (), (), ()> {
@@ -104,6 +193,8 @@ impl<I, ST: IntoSystemTuple<I>> IntoSystemConfig<I, ST, (), (), (), ()> for ST {
             chain: true,
             after: None,
             before: None,
+            schedule: ScheduleLabel::default(),
+            conditions: Vec::new(),
         }
     }
     fn after<I2, ST2: IntoSystemTuple<I2>>(
@@ -116,6 +207,8 @@ impl<I, ST: IntoSystemTuple<I>> IntoSystemConfig<I, ST, (), (), (), ()> for ST {
             chain: false,
             after: Some(after_systems),
             before: None,
+            schedule: ScheduleLabel::default(),
+            conditions: Vec::new(),
         }
     }
     fn before<I2, ST2: IntoSystemTuple<I2>>(
@@ -128,6 +221,33 @@ impl<I, ST: IntoSystemTuple<I>> IntoSystemConfig<I, ST, (), (), (), ()> for ST {
             chain: false,
             after: None,
             before: Some(before_systems),
+            schedule: ScheduleLabel::default(),
+            conditions: Vec::new(),
+        }
+    }
+    fn in_schedule(self, schedule: ScheduleLabel) -> SystemConfig<I, ST, (), (), (), ()> {
+        SystemConfig {
+            system_tuple: self,
+            _marker: PhantomData::default(),
+            chain: false,
+            after: None,
+            before: None,
+            schedule,
+            conditions: Vec::new(),
+        }
+    }
+    fn run_if<IC, C: IntoCondition<IC>>(self, condition: C) -> SystemConfig<I, ST, (), (), (), ()>
+    where
+        C::Condition: 'static,
+    {
+        SystemConfig {
+            system_tuple: self,
+            _marker: PhantomData::default(),
+            chain: false,
+            after: None,
+            before: None,
+            schedule: ScheduleLabel::default(),
+            conditions: vec![Box::new(condition.into_condition())],
         }
     }
 }
@@ -142,6 +262,8 @@ impl<I, ST: IntoSystemTuple<I>, IA, AS: IntoSystemTuple<IA>, IB, BS: IntoSystemT
             chain: self.chain,
             after: self.after,
             before: self.before,
+            schedule: self.schedule,
+            conditions: self.conditions,
         }
     }
     fn chain(self) -> SystemConfig<I, ST, IA, AS, IB, BS> {
@@ -151,6 +273,8 @@ impl<I, ST: IntoSystemTuple<I>, IA, AS: IntoSystemTuple<IA>, IB, BS: IntoSystemT
             chain: true,
             after: self.after,
             before: self.before,
+            schedule: self.schedule,
+            conditions: self.conditions,
         }
     }
     fn after<I2, ST2: IntoSystemTuple<I2>>(
@@ -163,6 +287,8 @@ impl<I, ST: IntoSystemTuple<I>, IA, AS: IntoSystemTuple<IA>, IB, BS: IntoSystemT
             chain: self.chain,
             after: Some(after_systems),
             before: self.before,
+            schedule: self.schedule,
+            conditions: self.conditions,
         }
     }
     fn before<I2, ST2: IntoSystemTuple<I2>>(
@@ -175,8 +301,31 @@ impl<I, ST: IntoSystemTuple<I>, IA, AS: IntoSystemTuple<IA>, IB, BS: IntoSystemT
             chain: self.chain,
             after: self.after,
             before: Some(before_systems),
+            schedule: self.schedule,
+            conditions: self.conditions,
         }
     }
+    fn in_schedule(self, schedule: ScheduleLabel) -> SystemConfig<I, ST, IA, AS, IB, BS> {
+        SystemConfig {
+            system_tuple: self.system_tuple,
+            _marker: PhantomData::default(),
+            chain: self.chain,
+            after: self.after,
+            before: self.before,
+            schedule,
+            conditions: self.conditions,
+        }
+    }
+    fn run_if<IC, C: IntoCondition<IC>>(
+        mut self,
+        condition: C,
+    ) -> SystemConfig<I, ST, IA, AS, IB, BS>
+    where
+        C::Condition: 'static,
+    {
+        self.conditions.push(Box::new(condition.into_condition()));
+        self
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,206 @@
+// condition.rs
+
+use std::marker::PhantomData;
+
+use crate::{
+    all_tuples,
+    ecs::{
+        change_detection::{SystemTicks, Tick},
+        world::WorldData,
+    },
+};
+
+use super::{ReadOnlySystemParam, System, SystemId, SystemParam, SystemParamId};
+
+/// A run condition is a system, which only accesses the world immutably and returns a bool.
+/// Systems with run conditions are only executed, if all of their conditions return true.
+pub struct ConditionSystem<Input, F> {
+    pub f: F,
+    pub marker: PhantomData<fn() -> Input>,
+    // world change tick of the previous run of this condition
+    pub(crate) last_run: Tick,
+}
+
+pub trait IntoCondition<Input> {
+    type Condition: System;
+
+    fn into_condition(self) -> Self::Condition;
+}
+
+unsafe impl<F: FnMut() -> bool> Send for ConditionSystem<(), F> {}
+unsafe impl<F: FnMut() -> bool> Sync for ConditionSystem<(), F> {}
+
+impl<F: FnMut() -> bool> System for ConditionSystem<(), F> {
+    fn system_name(&self) -> &str {
+        std::any::type_name::<F>()
+    }
+    fn init(
+        &mut self,
+        _system_id: SystemId,
+        _system_param_ids: &mut Vec<SystemParamId>,
+        _world_data: &mut WorldData,
+    ) {
+    }
+    fn run(&mut self, _system_params: &[SystemParamId], _world_data: *mut WorldData) {
+        (self.f)();
+    }
+    fn run_condition(
+        &mut self,
+        _system_params: &[SystemParamId],
+        _world_data: *mut WorldData,
+    ) -> bool {
+        (self.f)()
+    }
+}
+
+impl<F: FnMut() -> bool> IntoCondition<()> for F {
+    type Condition = ConditionSystem<(), Self>;
+
+    fn into_condition(self) -> Self::Condition {
+        ConditionSystem {
+            f: self,
+            marker: Default::default(),
+            last_run: Tick::default(),
+        }
+    }
+}
+
+macro_rules! impl_condition_for_params {
+    ( $($t:ident), * ) => {
+       unsafe impl<F, $($t : ReadOnlySystemParam,)*> Send for ConditionSystem<($($t,)*), F>
+       where
+         for<'a, 'b> &'a mut F : FnMut($($t,)*) -> bool
+         + FnMut($(<$t as SystemParam>::Item<'b>,)*) -> bool, {}
+
+       unsafe impl<F, $($t : ReadOnlySystemParam,)*> Sync for ConditionSystem<($($t,)*), F>
+       where
+         for<'a, 'b> &'a mut F : FnMut($($t,)*) -> bool
+         + FnMut($(<$t as SystemParam>::Item<'b>,)*) -> bool, {}
+
+       impl<F, $($t : ReadOnlySystemParam,)*> System for ConditionSystem<($($t,)*), F>
+       where
+         for<'a, 'b> &'a mut F : FnMut($($t,)*) -> bool
+         + FnMut($(<$t as SystemParam>::Item<'b>,)*) -> bool,
+       {
+           fn system_name(&self) -> &str {
+               std::any::type_name::<F>()
+           }
+
+           fn init(&mut self, system_id: SystemId, system_param_ids: &mut Vec<SystemParamId>, world_data: &mut WorldData) {
+              $(
+                $t::create_system_param_data(system_id, system_param_ids, world_data);
+              )*
+           }
+
+           fn run(&mut self, system_params: &[SystemParamId], world_data: *mut WorldData){
+               self.run_condition(system_params, world_data);
+           }
+
+           #[allow(non_snake_case)]
+           fn run_condition(&mut self, system_params: &[SystemParamId], world_data: *mut WorldData) -> bool {
+               fn call_inner<$($t,)*>(
+                   mut f: impl FnMut($($t,)*) -> bool,
+                   $( $t : $t,)*
+               ) -> bool {
+                  f($( $t,)*)
+               }
+               let this_run = unsafe { (*world_data).increment_change_tick() };
+               let system_ticks = SystemTicks { last_run: self.last_run, this_run };
+               let mut system_param_index = 0;
+               $(let $t = unsafe{$t::retrieve(&mut system_param_index, system_params, world_data, system_ticks)};)*
+               let result = call_inner(&mut self.f, $($t,)* );
+               self.last_run = this_run;
+               result
+           }
+       }
+
+       impl<F, $($t : ReadOnlySystemParam,)*> IntoCondition<($($t,)*)> for F
+           where
+             for<'a, 'b> &'a mut F:
+             FnMut($($t,)*) -> bool + FnMut($(<$t as SystemParam>::Item<'b>,)*) -> bool
+        {
+           type Condition = ConditionSystem<( $($t,)* ), Self>;
+
+           fn into_condition(self) -> Self::Condition {
+               ConditionSystem{
+                   f : self,
+                   marker : Default::default(),
+                   last_run : Tick::default(),
+               }
+           }
+        }
+    };
+}
+
+#[rustfmt::skip]
+all_tuples!(
+    impl_condition_for_params,
+    T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16
+);
+
+#[cfg(test)]
+mod test {
+    use crate::ecs::{prelude::*, system::ResMut};
+
+    #[derive(PartialEq, Eq)]
+    enum GameState {
+        Running,
+        Paused,
+    }
+
+    #[derive(Default)]
+    struct Log(Vec<&'static str>);
+
+    struct Player;
+    impl Component for Player {}
+
+    fn is_running(state: Res<GameState>) -> bool {
+        *state == GameState::Running
+    }
+
+    fn has_player(mut query: Query<&Player>) -> bool {
+        query.iter().count() > 0
+    }
+
+    fn input_system(mut log: ResMut<Log>) {
+        log.0.push("input");
+    }
+
+    fn physics_system(mut log: ResMut<Log>) {
+        log.0.push("physics");
+    }
+
+    fn render_system(mut log: ResMut<Log>) {
+        log.0.push("render");
+    }
+
+    fn player_system(mut log: ResMut<Log>) {
+        log.0.push("player");
+    }
+
+    #[test]
+    fn test_run_conditions() {
+        let mut world = World::new();
+        world.add_resource(GameState::Running);
+        world.add_resource(Log::default());
+        world.add_systems(
+            (input_system, physics_system, render_system)
+                .chain()
+                .run_if(|| true),
+        );
+        // skipped physics system still needs to run before the render system
+        world.add_systems(physics_system.run_if(is_running));
+        world.add_systems(player_system.after(render_system).run_if(has_player));
+
+        world.init_and_run();
+        *world.get_resource_mut::<GameState>().unwrap() = GameState::Paused;
+        world.add_entity(Player);
+        world.run();
+
+        let log = world.get_resource::<Log>().unwrap();
+        assert_eq!(
+            vec!["input", "physics", "render", "input", "render", "player"],
+            log.0
+        );
+    }
+}
@@ -0,0 +1,115 @@
+// exclusive.rs
+
+use std::{marker::PhantomData, ptr::NonNull};
+
+use crate::ecs::world::WorldData;
+
+use super::{IntoSystem, System, SystemId, SystemParamId};
+
+/// An exclusive system gets mutable access to the whole world data.
+/// It never runs in parallel to other systems and commands are executed
+/// before and after it runs, so that it sees and leaves an up to date world.
+pub struct ExclusiveSystem<F> {
+    pub f: F,
+    pub marker: PhantomData<fn()>,
+}
+
+/// Marker for the input of exclusive systems.
+pub struct ExclusiveSystemMarker;
+
+impl<F: FnMut(&mut WorldData) + Send + Sync> System for ExclusiveSystem<F> {
+    fn system_name(&self) -> &str {
+        std::any::type_name::<F>()
+    }
+    fn init(
+        &mut self,
+        _system_id: SystemId,
+        _system_param_ids: &mut Vec<SystemParamId>,
+        _world_data: &mut WorldData,
+    ) {
+    }
+    fn run(&mut self, _system_params: &[SystemParamId], world_data: *mut WorldData) {
+        // #SAFETY: the scheduler runs exclusive systems without any other system running
+        let mut world_data = NonNull::new(world_data).expect("World data should exist.");
+        let world_data = unsafe { world_data.as_mut() };
+        (self.f)(world_data);
+    }
+    fn is_exclusive(&self) -> bool {
+        true
+    }
+}
+
+impl<F: FnMut(&mut WorldData) + Send + Sync> IntoSystem<ExclusiveSystemMarker> for F {
+    type System = ExclusiveSystem<Self>;
+
+    fn into_system(self) -> Self::System {
+        ExclusiveSystem {
+            f: self,
+            marker: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ecs::{prelude::*, system::ResMut};
+
+    struct Enemy(usize);
+    impl Component for Enemy {}
+
+    #[derive(Default)]
+    struct Spawned(Vec<EntityKey>);
+
+    #[derive(Default)]
+    struct Log(Vec<usize>);
+
+    fn spawn_enemies_system(mut commands: Commands, mut spawned: ResMut<Spawned>) {
+        spawned.0.push(commands.spawn(Enemy(1)));
+        spawned.0.push(commands.spawn(Enemy(2)));
+    }
+
+    fn count_enemies_system(world_data: &mut WorldData) {
+        // commands of the systems before were executed
+        let spawned = world_data.get_resource::<Spawned>().unwrap().0.clone();
+        let sum = spawned
+            .into_iter()
+            .filter_map(|key| world_data.get_single_component::<Enemy>(key).map(|e| e.0))
+            .sum();
+        world_data.get_resource_mut::<Log>().unwrap().0.push(sum);
+        world_data.add_entity(Enemy(10));
+    }
+
+    fn log_enemies_system(mut log: ResMut<Log>, mut query: Query<&Enemy>) {
+        let sum = query.iter().map(|enemy| enemy.0).sum();
+        log.0.push(sum);
+    }
+
+    fn idle_system() {}
+
+    #[test]
+    fn test_exclusive_system() {
+        let mut world = World::new();
+        world.add_resource(Log::default());
+        world.add_resource(Spawned::default());
+        let exclusive_id = world.add_systems(
+            (
+                spawn_enemies_system,
+                count_enemies_system,
+                log_enemies_system,
+            )
+                .chain(),
+        )[1];
+        world.add_systems(idle_system);
+        world.init_and_run();
+
+        assert!(world.systems.get_system(exclusive_id).is_exclusive());
+        let schedule = &world.scheduler.schedule[&ScheduleLabel::Update];
+        let exclusive_batch = schedule
+            .iter()
+            .flatten()
+            .find(|batch| batch.contains(&exclusive_id))
+            .unwrap();
+        assert_eq!(1, exclusive_batch.len());
+        assert_eq!(vec![3, 13], world.get_resource::<Log>().unwrap().0);
+    }
+}
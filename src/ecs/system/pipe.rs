@@ -0,0 +1,525 @@
+// pipe.rs
+
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+use crate::{
+    all_tuples,
+    ecs::{
+        change_detection::{SystemTicks, Tick},
+        world::WorldData,
+    },
+};
+
+use super::{IntoSystem, System, SystemId, SystemParam, SystemParamId};
+
+/// Param of a system, which receives the output of the previous system of a pipe.
+/// It needs to be the first param of the system.
+pub struct In<T>(pub T);
+
+impl<T> Deref for In<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for In<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// A system, which returns a value, that gets passed to the next system of a pipe.
+pub trait OutputSystem: Send + Sync {
+    type Out;
+
+    fn system_name(&self) -> &str;
+    fn init(
+        &mut self,
+        system_id: SystemId,
+        system_param_ids: &mut Vec<SystemParamId>,
+        world_data: &mut WorldData,
+    );
+    fn run_output(
+        &mut self,
+        system_param_ids: &[SystemParamId],
+        world_data: *mut WorldData,
+    ) -> Self::Out;
+}
+
+/// A system, which receives the output of the previous system of a pipe through its In param.
+pub trait InputSystem<I>: Send + Sync {
+    type Out;
+
+    fn system_name(&self) -> &str;
+    fn init(
+        &mut self,
+        system_id: SystemId,
+        system_param_ids: &mut Vec<SystemParamId>,
+        world_data: &mut WorldData,
+    );
+    fn run_input(
+        &mut self,
+        input: I,
+        system_param_ids: &[SystemParamId],
+        world_data: *mut WorldData,
+    ) -> Self::Out;
+}
+
+pub trait IntoOutputSystem<Input> {
+    type Out;
+    type System: OutputSystem<Out = Self::Out>;
+
+    fn into_output_system(self) -> Self::System;
+}
+
+pub trait IntoInputSystem<I, Input> {
+    type System: InputSystem<I>;
+
+    fn into_input_system(self) -> Self::System;
+}
+
+pub trait IntoPipeSystem<Input>: IntoOutputSystem<Input> + Sized {
+    /// Runs the other system after this one and passes the output of this system
+    /// to the In param of the other system.
+    /// Both systems are a single system for the scheduler.
+    fn pipe<OtherInput, B: IntoInputSystem<Self::Out, OtherInput>>(
+        self,
+        other: B,
+    ) -> PipeSystem<Self::System, B::System> {
+        let a = self.into_output_system();
+        let b = other.into_input_system();
+        let name = format!("{} | {}", a.system_name(), b.system_name());
+        PipeSystem {
+            a,
+            b,
+            a_param_count: 0,
+            name,
+        }
+    }
+}
+
+impl<Input, T: IntoOutputSystem<Input>> IntoPipeSystem<Input> for T {}
+
+/// Two systems, which are run one after another,
+/// with the output of the first one passed to the second one.
+pub struct PipeSystem<A, B> {
+    a: A,
+    b: B,
+    // the params of the first system are followed by the params of the second one
+    a_param_count: usize,
+    name: String,
+}
+
+/// Marker for the input of pipe systems.
+pub struct PipeSystemMarker;
+
+impl<A: OutputSystem, B: InputSystem<A::Out>> OutputSystem for PipeSystem<A, B> {
+    type Out = B::Out;
+
+    fn system_name(&self) -> &str {
+        &self.name
+    }
+    fn init(
+        &mut self,
+        system_id: SystemId,
+        system_param_ids: &mut Vec<SystemParamId>,
+        world_data: &mut WorldData,
+    ) {
+        let first_param_index = system_param_ids.len();
+        self.a.init(system_id, system_param_ids, world_data);
+        self.a_param_count = system_param_ids.len() - first_param_index;
+        self.b.init(system_id, system_param_ids, world_data);
+    }
+    fn run_output(
+        &mut self,
+        system_param_ids: &[SystemParamId],
+        world_data: *mut WorldData,
+    ) -> Self::Out {
+        let (a_param_ids, b_param_ids) = system_param_ids.split_at(self.a_param_count);
+        let out = self.a.run_output(a_param_ids, world_data);
+        self.b.run_input(out, b_param_ids, world_data)
+    }
+}
+
+impl<A: OutputSystem, B: InputSystem<A::Out>> IntoOutputSystem<PipeSystemMarker>
+    for PipeSystem<A, B>
+{
+    type Out = B::Out;
+    type System = Self;
+
+    fn into_output_system(self) -> Self::System {
+        self
+    }
+}
+
+impl<A: OutputSystem, B: InputSystem<A::Out, Out = ()>> System for PipeSystem<A, B> {
+    fn system_name(&self) -> &str {
+        &self.name
+    }
+    fn init(
+        &mut self,
+        system_id: SystemId,
+        system_param_ids: &mut Vec<SystemParamId>,
+        world_data: &mut WorldData,
+    ) {
+        OutputSystem::init(self, system_id, system_param_ids, world_data);
+    }
+    fn run(&mut self, system_param_ids: &[SystemParamId], world_data: *mut WorldData) {
+        self.run_output(system_param_ids, world_data);
+    }
+}
+
+impl<A: OutputSystem, B: InputSystem<A::Out, Out = ()>> IntoSystem<PipeSystemMarker>
+    for PipeSystem<A, B>
+{
+    type System = Self;
+
+    fn into_system(self) -> Self::System {
+        self
+    }
+}
+
+pub struct OutputFunctionSystem<Input, Out, F> {
+    pub f: F,
+    pub marker: PhantomData<fn() -> (Input, Out)>,
+    // world change tick of the previous run of this system
+    pub(crate) last_run: Tick,
+}
+
+pub struct InputFunctionSystem<I, Input, Out, F> {
+    pub f: F,
+    pub marker: PhantomData<fn(I) -> (Input, Out)>,
+    // world change tick of the previous run of this system
+    pub(crate) last_run: Tick,
+}
+
+unsafe impl<Out, F: FnMut() -> Out> Send for OutputFunctionSystem<(), Out, F> {}
+unsafe impl<Out, F: FnMut() -> Out> Sync for OutputFunctionSystem<(), Out, F> {}
+
+impl<Out, F: FnMut() -> Out> OutputSystem for OutputFunctionSystem<(), Out, F> {
+    type Out = Out;
+
+    fn system_name(&self) -> &str {
+        std::any::type_name::<F>()
+    }
+    fn init(
+        &mut self,
+        _system_id: SystemId,
+        _system_param_ids: &mut Vec<SystemParamId>,
+        _world_data: &mut WorldData,
+    ) {
+    }
+    fn run_output(
+        &mut self,
+        _system_params: &[SystemParamId],
+        _world_data: *mut WorldData,
+    ) -> Self::Out {
+        (self.f)()
+    }
+}
+
+impl<Out, F: FnMut() -> Out> IntoOutputSystem<()> for F {
+    type Out = Out;
+    type System = OutputFunctionSystem<(), Out, Self>;
+
+    fn into_output_system(self) -> Self::System {
+        OutputFunctionSystem {
+            f: self,
+            marker: Default::default(),
+            last_run: Tick::default(),
+        }
+    }
+}
+
+unsafe impl<I, Out, F: FnMut(In<I>) -> Out> Send for InputFunctionSystem<I, (), Out, F> {}
+unsafe impl<I, Out, F: FnMut(In<I>) -> Out> Sync for InputFunctionSystem<I, (), Out, F> {}
+
+impl<I, Out, F: FnMut(In<I>) -> Out> InputSystem<I> for InputFunctionSystem<I, (), Out, F> {
+    type Out = Out;
+
+    fn system_name(&self) -> &str {
+        std::any::type_name::<F>()
+    }
+    fn init(
+        &mut self,
+        _system_id: SystemId,
+        _system_param_ids: &mut Vec<SystemParamId>,
+        _world_data: &mut WorldData,
+    ) {
+    }
+    fn run_input(
+        &mut self,
+        input: I,
+        _system_params: &[SystemParamId],
+        _world_data: *mut WorldData,
+    ) -> Self::Out {
+        (self.f)(In(input))
+    }
+}
+
+impl<I, Out, F: FnMut(In<I>) -> Out> IntoInputSystem<I, ()> for F {
+    type System = InputFunctionSystem<I, (), Out, Self>;
+
+    fn into_input_system(self) -> Self::System {
+        InputFunctionSystem {
+            f: self,
+            marker: Default::default(),
+            last_run: Tick::default(),
+        }
+    }
+}
+
+fn world_data_ptr(world_data: *mut WorldData) -> *mut WorldData {
+    NonNull::new(world_data)
+        .expect("World data should exist.")
+        .as_ptr()
+}
+
+fn increment_change_tick(world_data: *mut WorldData) -> Tick {
+    let world_data = NonNull::new(world_data).expect("World data should exist.");
+    // #SAFETY: the change tick is atomic and can be incremented by multiple systems at once
+    unsafe { world_data.as_ref().increment_change_tick() }
+}
+
+macro_rules! impl_pipe_systems_for_params {
+    ( $($t:ident), * ) => {
+       unsafe impl<Out, F, $($t : SystemParam,)*> Send for OutputFunctionSystem<($($t,)*), Out, F>
+       where
+         for<'a, 'b> &'a mut F : FnMut($($t,)*) -> Out
+         + FnMut($(<$t as SystemParam>::Item<'b>,)*) -> Out, {}
+
+       unsafe impl<Out, F, $($t : SystemParam,)*> Sync for OutputFunctionSystem<($($t,)*), Out, F>
+       where
+         for<'a, 'b> &'a mut F : FnMut($($t,)*) -> Out
+         + FnMut($(<$t as SystemParam>::Item<'b>,)*) -> Out, {}
+
+       impl<Out, F, $($t : SystemParam,)*> OutputSystem for OutputFunctionSystem<($($t,)*), Out, F>
+       where
+         for<'a, 'b> &'a mut F : FnMut($($t,)*) -> Out
+         + FnMut($(<$t as SystemParam>::Item<'b>,)*) -> Out,
+       {
+           type Out = Out;
+
+           fn system_name(&self) -> &str {
+               std::any::type_name::<F>()
+           }
+
+           fn init(&mut self, system_id: SystemId, system_param_ids: &mut Vec<SystemParamId>, world_data: &mut WorldData) {
+              $(
+                $t::create_system_param_data(system_id, system_param_ids, world_data);
+              )*
+           }
+
+           #[allow(non_snake_case)]
+           fn run_output(&mut self, system_params: &[SystemParamId], world_data: *mut WorldData) -> Out {
+               // the params are passed as a tuple, to infer the param types of the function
+               fn call_inner<Out, $($t,)*>(
+                   mut f: impl FnMut($($t,)*) -> Out,
+                   params: ($($t,)*),
+               ) -> Out {
+                  let ($($t,)*) = params;
+                  f($( $t,)*)
+               }
+               let world_data = world_data_ptr(world_data);
+               let this_run = increment_change_tick(world_data);
+               let system_ticks = SystemTicks { last_run: self.last_run, this_run };
+               let mut system_param_index = 0;
+               $(let $t = unsafe{$t::retrieve(&mut system_param_index, system_params, world_data, system_ticks)};)*
+               let out = call_inner(&mut self.f, ($($t,)*));
+               self.last_run = this_run;
+               out
+           }
+       }
+
+       impl<Out, F, $($t : SystemParam,)*> IntoOutputSystem<($($t,)*)> for F
+           where
+             for<'a, 'b> &'a mut F:
+             FnMut($($t,)*) -> Out + FnMut($(<$t as SystemParam>::Item<'b>,)*) -> Out
+        {
+           type Out = Out;
+           type System = OutputFunctionSystem<( $($t,)* ), Out, Self>;
+
+           fn into_output_system(self) -> Self::System {
+               OutputFunctionSystem{
+                   f : self,
+                   marker : Default::default(),
+                   last_run : Tick::default(),
+               }
+           }
+        }
+
+       unsafe impl<I, Out, F, $($t : SystemParam,)*> Send for InputFunctionSystem<I, ($($t,)*), Out, F>
+       where
+         for<'a, 'b> &'a mut F : FnMut(In<I>, $($t,)*) -> Out
+         + FnMut(In<I>, $(<$t as SystemParam>::Item<'b>,)*) -> Out, {}
+
+       unsafe impl<I, Out, F, $($t : SystemParam,)*> Sync for InputFunctionSystem<I, ($($t,)*), Out, F>
+       where
+         for<'a, 'b> &'a mut F : FnMut(In<I>, $($t,)*) -> Out
+         + FnMut(In<I>, $(<$t as SystemParam>::Item<'b>,)*) -> Out, {}
+
+       impl<I, Out, F, $($t : SystemParam,)*> InputSystem<I> for InputFunctionSystem<I, ($($t,)*), Out, F>
+       where
+         for<'a, 'b> &'a mut F : FnMut(In<I>, $($t,)*) -> Out
+         + FnMut(In<I>, $(<$t as SystemParam>::Item<'b>,)*) -> Out,
+       {
+           type Out = Out;
+
+           fn system_name(&self) -> &str {
+               std::any::type_name::<F>()
+           }
+
+           fn init(&mut self, system_id: SystemId, system_param_ids: &mut Vec<SystemParamId>, world_data: &mut WorldData) {
+              $(
+                $t::create_system_param_data(system_id, system_param_ids, world_data);
+              )*
+           }
+
+           #[allow(non_snake_case)]
+           fn run_input(&mut self, input: I, system_params: &[SystemParamId], world_data: *mut WorldData) -> Out {
+               fn call_inner<I, Out, $($t,)*>(
+                   mut f: impl FnMut(In<I>, $($t,)*) -> Out,
+                   input: In<I>,
+                   params: ($($t,)*),
+               ) -> Out {
+                  let ($($t,)*) = params;
+                  f(input, $( $t,)*)
+               }
+               let world_data = world_data_ptr(world_data);
+               let this_run = increment_change_tick(world_data);
+               let system_ticks = SystemTicks { last_run: self.last_run, this_run };
+               let mut system_param_index = 0;
+               $(let $t = unsafe{$t::retrieve(&mut system_param_index, system_params, world_data, system_ticks)};)*
+               let out = call_inner(&mut self.f, In(input), ($($t,)*));
+               self.last_run = this_run;
+               out
+           }
+       }
+
+       impl<I, Out, F, $($t : SystemParam,)*> IntoInputSystem<I, ($($t,)*)> for F
+           where
+             for<'a, 'b> &'a mut F:
+             FnMut(In<I>, $($t,)*) -> Out + FnMut(In<I>, $(<$t as SystemParam>::Item<'b>,)*) -> Out
+        {
+           type System = InputFunctionSystem<I, ( $($t,)* ), Out, Self>;
+
+           fn into_input_system(self) -> Self::System {
+               InputFunctionSystem{
+                   f : self,
+                   marker : Default::default(),
+                   last_run : Tick::default(),
+               }
+           }
+        }
+    };
+}
+
+#[rustfmt::skip]
+all_tuples!(
+    impl_pipe_systems_for_params,
+    T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15
+);
+
+#[cfg(test)]
+mod test {
+    use crate::ecs::prelude::*;
+
+    struct Health(i32);
+    impl Component for Health {}
+
+    #[derive(Default)]
+    struct Report(Vec<String>);
+
+    fn count_dead(mut query: Query<&Health>) -> usize {
+        query.iter().filter(|health| health.0 <= 0).count()
+    }
+
+    fn report_dead(dead: In<usize>, mut report: ResMut<Report>) {
+        report.0.push(format!("dead: {}", *dead));
+    }
+
+    fn parse_threshold(threshold: Res<String>) -> Result<i32, String> {
+        threshold
+            .parse()
+            .map_err(|_| format!("invalid threshold {}", *threshold))
+    }
+
+    fn double(threshold: In<Result<i32, String>>) -> Result<i32, String> {
+        threshold.0.map(|threshold| threshold * 2)
+    }
+
+    fn report_threshold(threshold: In<Result<i32, String>>, mut report: ResMut<Report>) {
+        match threshold.0 {
+            Ok(threshold) => report.0.push(format!("threshold: {threshold}")),
+            Err(err) => report.0.push(err),
+        }
+    }
+
+    #[test]
+    fn test_pipe() {
+        let mut world = World::new();
+        world.add_resource(Report::default());
+        world.add_resource(String::from("21"));
+        world.add_entity(Health(0));
+        world.add_entity(Health(10));
+        world.add_entity(Health(-5));
+        world.add_systems(
+            (
+                count_dead.pipe(report_dead),
+                parse_threshold.pipe(double).pipe(report_threshold),
+            )
+                .chain(),
+        );
+        world.init_and_run();
+        *world.get_resource_mut::<String>().unwrap() = String::from("abc");
+        world.run();
+
+        assert_eq!(
+            vec![
+                "dead: 2",
+                "threshold: 42",
+                "dead: 2",
+                "invalid threshold abc"
+            ],
+            world.get_resource::<Report>().unwrap().0
+        );
+    }
+
+    #[derive(Default)]
+    struct Runs(Vec<&'static str>);
+
+    fn idle_system(mut runs: ResMut<Runs>) {
+        runs.0.push("idle");
+    }
+    fn other_system(mut runs: ResMut<Runs>) {
+        runs.0.push("other");
+    }
+
+    #[test]
+    fn test_system_instances() {
+        let mut world = World::new();
+        world.add_resource(Runs::default());
+        // constraints are resolved on init, after all instances were added
+        let other_id = world.add_systems(other_system.after(idle_system))[0];
+        let first_id = world.add_systems(idle_system)[0];
+        assert_eq!(first_id, world.add_systems(idle_system)[0]);
+        let second_id = world.add_systems(idle_system.instance())[0];
+        assert_ne!(first_id, second_id);
+        world.init_and_run();
+
+        // constraints reference all instances of a system
+        let constraint = world.systems.get_constraint(&other_id).unwrap();
+        assert_eq!(2, constraint.after.len());
+        assert!(constraint.after.contains(&first_id));
+        assert!(constraint.after.contains(&second_id));
+        assert_eq!(
+            vec!["idle", "idle", "other"],
+            world.get_resource::<Runs>().unwrap().0
+        );
+    }
+}
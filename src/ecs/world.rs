@@ -1,29 +1,52 @@
 // world.rs
 
-use std::{any::TypeId, cell::UnsafeCell, collections::HashMap};
+use std::{
+    alloc::Layout,
+    any::{Any, TypeId},
+    cell::UnsafeCell,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    io::{self, Read, Write},
+    ptr::NonNull,
+    str::FromStr,
+    sync::Arc,
+    thread::ThreadId,
+};
 
 use crate::{
     ecs::{
-        component::{Archetype, ArchetypeId, ComponentId},
+        change_detection::Tick,
+        component::{Archetype, ArchetypeId, ComponentId, ComponentInfo},
+        dump::{self, SceneError, SceneRegistry},
         ecs_dependency_graph::EcsDependencyGraph,
-        entity::{Entities, EntityKey},
+        entity::{Entities, EntityKey, relation::Relation},
+        error::EcsError,
+        event::{self, Event, Events},
         prelude::Component,
-        query::{QueryParam, QueryParamMetaData},
-        resource::ResourceId,
+        query::{
+            QueryParam, QueryParamMetaData, TypedQueryState, dynamic_query::DynamicQuery,
+            query_filter::QueryFilter,
+        },
+        reflect::{DynMut, DynRef, Reflect, TypeInfo, TypeRegistry},
+        resource::{FromWorld, ResourceId},
+        schedule::ScheduleLabel,
         scheduler::ParallelScheduler,
-        storages::{cache::EntityStorageCache, table_storage::TableStorage},
+        snapshot::{self, SerializableComponent, SerializableResource, SnapshotRegistry},
+        storages::{
+            cache::EntityStorageCache, sparse_set::SparseSets, table_storage::TableStorage,
+        },
         system::SystemId,
     },
     utils::{
-        any_map::AnyMap, sorted_vec::SortedVec, tuple_iters::TupleIterator,
-        tuple_types::TupleTypesExt,
+        any_map::AnyMap, ecs_id::EcsId, scoped_threadpool::ScopedThreadPool, sorted_vec::SortedVec,
+        tuple_iters::TupleIterator, tuple_types::TupleTypesExt,
     },
 };
 
 use super::{
     commands::CommandQueuesStorage,
     query::QueryState,
-    scheduler::{Scheduler, SingleThreadScheduler},
+    scheduler::{Scheduler, find_conflicts_in_systems_params, find_missing_resources_of_system},
     storages::entity_storage::EntityStorage,
     system::{
         Systems,
@@ -35,30 +58,75 @@ pub struct World {
     pub data: UnsafeCell<WorldData>,
     pub systems: Systems,
     pub(crate) scheduler: ParallelScheduler,
-    //SingleThreadScheduler,
+    // the startup schedule only runs during the first run
+    startup_done: bool,
 }
 
 pub struct WorldData {
     pub(crate) resources: AnyMap,
     entity_storage: EntityStorage,
     pub(crate) commands_queues: CommandQueuesStorage,
+    // update functions of all event types, which are called once per run
+    event_updates: HashMap<ResourceId, fn(&mut WorldData)>,
+    // threads for parallel query iteration, separate from the scheduler threads,
+    // so that systems running on the scheduler threads can wait for the iteration to finish,
+    // parallel systems share it
+    pub(crate) query_thread_pool: ScopedThreadPool,
+    pub(crate) snapshot_registry: SnapshotRegistry,
+    pub(crate) scene_registry: SceneRegistry,
+    pub(crate) type_registry: TypeRegistry,
+    // state of the Local params of every system
+    pub(crate) system_locals: HashMap<SystemId, Vec<Box<dyn Any + Send>>>,
+    // resources, which are not Send and only accessible on the thread, which added them
+    pub(crate) non_send_resources: AnyMap,
+    non_send_thread: Option<ThreadId>,
 }
 
 unsafe impl Send for WorldData {}
 unsafe impl Sync for WorldData {}
 
+impl Drop for WorldData {
+    // non send resources can't be dropped on another thread, than the one which added them,
+    // so they are leaked instead
+    fn drop(&mut self) {
+        let Some(owner_thread) = self.non_send_thread else {
+            return;
+        };
+        if owner_thread != std::thread::current().id() && self.non_send_resources.len() > 0 {
+            std::mem::forget(std::mem::replace(
+                &mut self.non_send_resources,
+                AnyMap::new(),
+            ));
+            if !std::thread::panicking() {
+                panic!(
+                    "Non send resources can only be dropped on the thread, which added them. They were leaked."
+                );
+            }
+        }
+    }
+}
+
 pub(crate) struct SharedWorldData<'w>(pub(crate) &'w UnsafeCell<WorldData>);
 
 unsafe impl<'w> Send for SharedWorldData<'w> {}
 unsafe impl<'w> Sync for SharedWorldData<'w> {}
 
+// threads of the scheduler and of the parallel query iteration, if not configured otherwise
+const DEFAULT_THREAD_COUNT: usize = 4;
+
 impl World {
     pub fn new() -> Self {
+        Self::with_thread_count(DEFAULT_THREAD_COUNT)
+    }
+
+    /// Creates a world, whose scheduler and parallel query iterations
+    /// each use a thread pool with the given number of threads.
+    pub fn with_thread_count(thread_count: usize) -> Self {
         World {
-            data: WorldData::new().into(),
+            data: WorldData::with_thread_count(thread_count).into(),
             systems: Systems::new(),
-            scheduler: ParallelScheduler::new(4),
-            //SingleThreadScheduler::new(),
+            scheduler: ParallelScheduler::new(thread_count),
+            startup_done: false,
         }
     }
 
@@ -70,16 +138,116 @@ impl World {
         self.data.get_mut().get_resource()
     }
 
+    /// Adds the resource and returns the resource, which it replaced.
+    pub fn insert_or_replace_resource<T: 'static>(&mut self, value: T) -> Option<T> {
+        self.data.get_mut().insert_or_replace_resource(value)
+    }
+
+    /// Creates the resource from the world data, if it does not exist yet.
+    /// Resources implementing Default are created with their default value.
+    pub fn init_resource<T: FromWorld + 'static>(&mut self) -> ResourceId {
+        self.data.get_mut().init_resource::<T>()
+    }
+
+    pub fn remove_resource<T: 'static>(&mut self) -> Option<T> {
+        self.data.get_mut().remove_resource()
+    }
+
+    pub fn contains_resource<T: 'static>(&mut self) -> bool {
+        self.data.get_mut().contains_resource::<T>()
+    }
+
+    /// Adds the event storage for events of type E,
+    /// which gets updated once per run of the world.
+    pub fn add_event<E: Event>(&mut self) -> ResourceId {
+        self.data.get_mut().add_event::<E>()
+    }
+
+    pub fn send_event<E: Event>(&mut self, event: E) {
+        self.data.get_mut().send_event(event);
+    }
+
     pub fn get_resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
         self.data.get_mut().get_resource_mut()
     }
 
+    /// Adds a resource, which is not Send.
+    /// Systems accessing it always run on the thread, which owns the world.
+    pub fn add_non_send_resource<T: 'static>(&mut self, value: T) -> ResourceId {
+        self.data.get_mut().add_non_send_resource(value)
+    }
+
+    pub fn get_non_send_resource<T: 'static>(&mut self) -> Option<&T> {
+        self.data.get_mut().get_non_send_resource()
+    }
+
+    pub fn get_non_send_resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.data.get_mut().get_non_send_resource_mut()
+    }
+
+    pub fn remove_non_send_resource<T: 'static>(&mut self) -> Option<T> {
+        self.data.get_mut().remove_non_send_resource()
+    }
+
     pub fn add_entity<T: TupleTypesExt>(&mut self, input: T) -> EntityKey {
         self.data.get_mut().add_entity(input)
     }
 
     pub fn add_entities_batch<T: TupleTypesExt>(&mut self, input: Vec<T>) -> Vec<EntityKey> {
-        self.data.get_mut().entity_storage.add_entities_batch(input)
+        let world_data = self.data.get_mut();
+        let keys = world_data.entity_storage.add_entities_batch(input);
+        for key in keys.iter() {
+            world_data.exec_on_add_hooks_for::<T>(*key);
+        }
+        keys
+    }
+
+    /// Registers a hook, which is called after a component of type T
+    /// was added to an entity.
+    pub fn register_on_add<T: Component>(
+        &mut self,
+        hook: impl Fn(&mut WorldData, EntityKey) + Send + Sync + 'static,
+    ) {
+        self.data.get_mut().register_on_add::<T>(hook);
+    }
+
+    /// Registers a hook, which is called before a component of type T
+    /// is removed from an entity, while it can still be accessed.
+    pub fn register_on_remove<T: Component>(
+        &mut self,
+        hook: impl Fn(&mut WorldData, EntityKey) + Send + Sync + 'static,
+    ) {
+        self.data.get_mut().register_on_remove::<T>(hook);
+    }
+
+    /// Registers a hook, which is called before a component of type T
+    /// gets overwritten by a new value, while the old value can still be accessed.
+    pub fn register_on_replace<T: Component>(
+        &mut self,
+        hook: impl Fn(&mut WorldData, EntityKey) + Send + Sync + 'static,
+    ) {
+        self.data.get_mut().register_on_replace::<T>(hook);
+    }
+
+    /// Adds the relation (R, target) to the entity.
+    pub fn add_relation<R: Relation>(&mut self, entity_key: EntityKey, target: EntityKey) {
+        self.data.get_mut().add_relation::<R>(entity_key, target);
+    }
+
+    /// Removes the relation (R, target) from the entity.
+    pub fn remove_relation<R: Relation>(&mut self, entity_key: EntityKey, target: EntityKey) {
+        self.data.get_mut().remove_relation::<R>(entity_key, target);
+    }
+
+    /// Returns the targets of all relations of kind R of the entity.
+    pub fn get_relation_targets<R: Relation>(&mut self, entity_key: EntityKey) -> Vec<EntityKey> {
+        self.data.get_mut().get_relation_targets::<R>(entity_key)
+    }
+
+    /// Creates a query state to iterate the entities matching the query outside of systems.
+    /// The state should be kept and reused, to match only new archetypes on later iterations.
+    pub fn query<P: QueryParam, F: QueryFilter>(&mut self) -> TypedQueryState<P, F> {
+        TypedQueryState::new(self.data.get_mut())
     }
 
     pub fn get_entity_components<P: QueryParam>(
@@ -109,6 +277,232 @@ impl World {
             .get_single_component_mut(entity_key)
     }
 
+    /// Registers the component type to be written to and read from snapshots.
+    /// Panics, if another component type is already registered with the same name.
+    pub fn register_serializable_component<T: SerializableComponent>(&mut self) {
+        self.data
+            .get_mut()
+            .snapshot_registry
+            .register_component::<T>();
+    }
+
+    /// Registers the resource type to be written to and read from snapshots.
+    /// Panics, if another resource type is already registered with the same name.
+    pub fn register_serializable_resource<T: SerializableResource>(&mut self) {
+        self.data
+            .get_mut()
+            .snapshot_registry
+            .register_resource::<T>();
+    }
+
+    /// Writes all entities with their registered serializable components
+    /// and all registered serializable resources to a binary snapshot.
+    /// The keys of the entities, including their generations, are preserved.
+    /// Relations and components, which are not registered, are not part of the snapshot.
+    pub fn save_snapshot(&mut self, writer: &mut impl Write) -> io::Result<()> {
+        snapshot::save_snapshot(self.data.get_mut(), writer)
+    }
+
+    /// Replaces all entities of the world with the entities of the snapshot
+    /// and adds or replaces the resources of the snapshot.
+    /// Returns an error without modifying the world, if the snapshot is invalid
+    /// or contains component or resource types, which are not registered as serializable.
+    pub fn load_snapshot(&mut self, reader: &mut impl Read) -> io::Result<()> {
+        snapshot::load_snapshot(self.data.get_mut(), reader)
+    }
+
+    /// Registers the Debug implementation of the component type,
+    /// which is used to write its values to world dumps.
+    pub fn register_debug_component<T: Component + Debug>(&mut self) {
+        self.data.get_mut().register_debug_component::<T>();
+    }
+
+    /// Registers the component type to be written to world dumps
+    /// and read from scenes by its short type name.
+    /// Panics, if another component type is already registered with the same short type name.
+    pub fn register_scene_component<T: Component + Debug + FromStr>(&mut self)
+    where
+        T::Err: Display,
+    {
+        let world_data = self.data.get_mut();
+        world_data.register_debug_component::<T>();
+        world_data.scene_registry.register_component::<T>();
+    }
+
+    /// Returns a human readable dump of all entities with their archetype ids, table rows
+    /// and components. Components are written with their registered Debug implementations.
+    pub fn dump_text(&mut self) -> String {
+        dump::dump_world(&self.data.get_mut().entity_storage)
+    }
+
+    /// Spawns the entities of a scene, written in the format of the world dumps,
+    /// and returns their keys. Only components registered as scene components can be imported.
+    /// Every entity of the scene needs at least one component.
+    /// No entity is spawned, if the scene contains an error.
+    pub fn import_scene(&mut self, text: &str) -> Result<Vec<EntityKey>, SceneError> {
+        dump::import_scene(self.data.get_mut(), text)
+    }
+
+    /// Registers the fields of the component type,
+    /// so that they can be accessed without knowing the type.
+    pub fn register_reflect_component<T: Component + Reflect>(&mut self) {
+        let world_data = self.data.get_mut();
+        world_data.create_or_get_component::<T>();
+        world_data.type_registry.register::<T>();
+    }
+
+    /// Registers the fields of the resource type,
+    /// so that they can be accessed without knowing the type.
+    pub fn register_reflect_resource<T: Reflect>(&mut self) {
+        self.data.get_mut().type_registry.register::<T>();
+    }
+
+    pub fn type_registry(&mut self) -> &TypeRegistry {
+        &self.data.get_mut().type_registry
+    }
+
+    pub fn get_component_id<T: Component>(&mut self) -> ComponentId {
+        self.data.get_mut().create_or_get_component::<T>()
+    }
+
+    pub fn get_component_info(&mut self, comp_id: ComponentId) -> Option<&ComponentInfo> {
+        self.data.get_mut().get_components().get(comp_id.id_usize())
+    }
+
+    /// Returns an untyped reference to the component of the entity.
+    /// Returns None, if the entity does not own the component
+    /// or the component type is not registered for reflection.
+    pub fn get_component_dyn(
+        &mut self,
+        entity_key: EntityKey,
+        comp_id: ComponentId,
+    ) -> Option<DynRef<'_>> {
+        let world_data = self.data.get_mut();
+        let type_info = world_data.get_reflect_info(comp_id)?;
+        let ptr = world_data
+            .entity_storage
+            .get_component_ptr(entity_key, comp_id)?;
+        Some(unsafe { DynRef::new(ptr, type_info) })
+    }
+
+    /// Returns an untyped mutable reference to the component of the entity
+    /// and marks the component as changed.
+    /// Returns None, if the entity does not own the component
+    /// or the component type is not registered for reflection.
+    pub fn get_component_dyn_mut(
+        &mut self,
+        entity_key: EntityKey,
+        comp_id: ComponentId,
+    ) -> Option<DynMut<'_>> {
+        let world_data = self.data.get_mut();
+        world_data.get_reflect_info(comp_id)?;
+        let ptr = world_data
+            .entity_storage
+            .get_component_ptr(entity_key, comp_id)?;
+        world_data
+            .entity_storage
+            .set_component_changed(entity_key, comp_id);
+        let type_info = world_data.get_reflect_info(comp_id)?;
+        Some(unsafe { DynMut::new(ptr, type_info) })
+    }
+
+    /// Returns an untyped reference to the resource.
+    /// Returns None, if the resource does not exist or is not registered for reflection.
+    pub fn get_resource_dyn(&mut self, resource_id: ResourceId) -> Option<DynRef<'_>> {
+        let world_data = self.data.get_mut();
+        let ptr = world_data.resources.get_ptr(&resource_id.type_id())?;
+        let type_info = world_data.type_registry.get(&resource_id.type_id())?;
+        Some(unsafe { DynRef::new(ptr, type_info) })
+    }
+
+    /// Returns an untyped mutable reference to the resource.
+    /// Returns None, if the resource does not exist or is not registered for reflection.
+    pub fn get_resource_dyn_mut(&mut self, resource_id: ResourceId) -> Option<DynMut<'_>> {
+        let world_data = self.data.get_mut();
+        let ptr = world_data.resources.get_ptr(&resource_id.type_id())?;
+        let type_info = world_data.type_registry.get(&resource_id.type_id())?;
+        Some(unsafe { DynMut::new(ptr, type_info) })
+    }
+
+    /// Registers a component without a Rust type, e.g. loaded from a schema at runtime,
+    /// and returns its id. Dynamic components are identified by their name
+    /// and are always stored inside of sparse sets.
+    /// Registering an already registered name returns its component id.
+    /// Panics, if the name is already registered with another layout.
+    pub fn register_dynamic_component(
+        &mut self,
+        name: &str,
+        layout: Layout,
+        drop_fn: Option<unsafe fn(*mut u8)>,
+    ) -> ComponentId {
+        self.data
+            .get_mut()
+            .entity_storage
+            .create_or_get_dynamic_component(name, layout, drop_fn)
+    }
+
+    pub fn get_dynamic_component_id(&mut self, name: &str) -> Option<ComponentId> {
+        self.data
+            .get_mut()
+            .entity_storage
+            .dynamic_compid_map
+            .get(name)
+            .copied()
+    }
+
+    /// Adds the dynamic component to the entity by copying the supplied bytes
+    /// or replaces the component, if the entity already owns it.
+    /// Panics, if the component is not dynamic or the bytes don't match its size.
+    ///
+    /// # Safety
+    /// The bytes need to be a valid value of the component.
+    /// The value is moved into the world and will be dropped with the drop function of the component.
+    pub unsafe fn add_component_dyn(
+        &mut self,
+        entity_key: EntityKey,
+        comp_id: ComponentId,
+        bytes: &[u8],
+    ) {
+        let entity_storage = &mut self.data.get_mut().entity_storage;
+        let cinfo = &entity_storage.components[comp_id.id_usize()];
+        assert!(
+            cinfo.is_dynamic(),
+            "Component {} is not a dynamic component.",
+            cinfo.name
+        );
+        assert_eq!(
+            cinfo.layout.size(),
+            bytes.len(),
+            "Bytes don't match the size of component {}.",
+            cinfo.name
+        );
+        let value_ptr = NonNull::from(bytes).cast::<u8>();
+        let _ = unsafe {
+            entity_storage.add_dynamic_component_to_entity(entity_key, comp_id, value_ptr)
+        };
+    }
+
+    /// Removes and drops the dynamic component of the entity.
+    /// The entity is removed, if it does not contain any components anymore.
+    pub fn remove_component_dyn(&mut self, entity_key: EntityKey, comp_id: ComponentId) {
+        let _ = self
+            .data
+            .get_mut()
+            .entity_storage
+            .remove_dynamic_component_from_entity(entity_key, comp_id);
+    }
+
+    /// Calls the function with every entity matching the dynamic query
+    /// and the pointers to its queried components.
+    /// Writing through the pointers does not mark the components as changed.
+    pub fn for_each_dynamic(
+        &mut self,
+        query: &DynamicQuery,
+        func: impl FnMut(EntityKey, &[NonNull<u8>]),
+    ) {
+        query.for_each(&self.data.get_mut().entity_storage, func);
+    }
+
     pub fn add_systems<
         I,
         ST: IntoSystemTuple<I>,
@@ -123,17 +517,50 @@ impl World {
         self.systems.add_system_builder(value)
     }
 
+    /// Initializes the params of all systems and builds the schedules.
+    /// Panics, if the systems can't be scheduled, see `try_init_systems`.
     pub fn init_systems(&mut self) {
+        if let Err(err) = self.try_init_systems() {
+            panic!("{err}");
+        }
+    }
+
+    /// Initializes the params of all systems and builds the schedules.
+    /// Returns an error, if the params of a system conflict with each other,
+    /// a system requests a resource, which was not added to the world,
+    /// or the constraints of the systems contain loops.
+    pub fn try_init_systems(&mut self) -> Result<(), EcsError> {
         self.systems.init_systems(&mut self.data.get_mut());
+        for system_id in 0..self.systems.system_vec.len() {
+            find_conflicts_in_systems_params(self.data.get_mut(), &self.systems, system_id.into())?;
+        }
+        for system_id in 0..self.systems.system_vec.len() {
+            find_missing_resources_of_system(self.data.get_mut(), &self.systems, system_id.into())?;
+        }
         self.scheduler.init_schedule(
             &mut self.data.get_mut().get_depend_graph_mut(),
             &mut self.systems,
-        );
+        )
     }
 
+    /// Runs the startup schedule during the first run,
+    /// followed by the update and cleanup schedules.
     pub fn run(&mut self) {
+        self.data.get_mut().update_events();
+        if !self.startup_done {
+            self.startup_done = true;
+            self.run_schedule(ScheduleLabel::Startup);
+        }
+        self.run_schedule(ScheduleLabel::Update);
+        self.run_schedule(ScheduleLabel::Cleanup);
+    }
+
+    /// Runs all systems of a single schedule.
+    /// The systems need to be initialized before.
+    pub fn run_schedule(&mut self, schedule: ScheduleLabel) {
         self.data.get_mut().entity_storage.entities.reset_barriers();
-        self.scheduler.execute(&mut self.systems, &mut self.data);
+        self.scheduler
+            .execute(&schedule, &mut self.systems, &mut self.data);
         self.data
             .get_mut()
             .entity_storage
@@ -156,10 +583,22 @@ impl World {
 
 impl WorldData {
     pub fn new() -> Self {
+        Self::with_thread_count(DEFAULT_THREAD_COUNT)
+    }
+
+    pub(crate) fn with_thread_count(thread_count: usize) -> Self {
         WorldData {
             resources: AnyMap::new(),
             entity_storage: EntityStorage::new(),
             commands_queues: CommandQueuesStorage::new(),
+            event_updates: HashMap::new(),
+            query_thread_pool: ScopedThreadPool::new(thread_count),
+            snapshot_registry: SnapshotRegistry::default(),
+            scene_registry: SceneRegistry::default(),
+            type_registry: TypeRegistry::default(),
+            system_locals: HashMap::new(),
+            non_send_resources: AnyMap::new(),
+            non_send_thread: None,
         }
     }
 
@@ -173,7 +612,7 @@ impl WorldData {
 
     pub fn add_entity<T: TupleTypesExt>(&mut self, input: T) -> EntityKey {
         let key = self.entity_storage.add_entity(input);
-        T::exec_on_add_rec(self, key);
+        self.exec_on_add_hooks_for::<T>(key);
         key
     }
 
@@ -183,13 +622,18 @@ impl WorldData {
         input: T,
     ) -> EntityKey {
         let key = self.entity_storage.add_entity_with_reserved_key(key, input);
-        T::exec_on_add_rec(self, key);
+        self.exec_on_add_hooks_for::<T>(key);
         key
     }
 
     pub fn remove_entity(&mut self, entity_key: EntityKey) {
-        //TODO: add remove hooks
-        //T::exec_on_remove_rec(self, key);
+        // remove hooks are called before the components are dropped,
+        // so that they can still be accessed inside of the hooks
+        let mut comp_ids = self.entity_storage.cache.compid_vec_cache.take_cached();
+        self.entity_storage
+            .get_entity_comp_ids(entity_key, &mut comp_ids);
+        self.exec_on_remove_hooks(entity_key, &comp_ids);
+        self.entity_storage.cache.compid_vec_cache.insert(comp_ids);
         self.entity_storage.remove_entity(entity_key);
     }
 
@@ -199,19 +643,147 @@ impl WorldData {
         component: T,
         overwrite: bool,
     ) {
-        //TODO: add hooks
-        let _ = self
+        let comp_id = self.create_or_get_component::<T>();
+        // overwritten component leaves the entity, before the new one is added
+        let replace = overwrite
+            && self
+                .entity_storage
+                .entity_contains_component(entity_key, comp_id);
+        if replace {
+            self.exec_on_replace_hooks(entity_key, &[comp_id]);
+            self.exec_on_remove_hooks(entity_key, &[comp_id]);
+        }
+        let result = self
             .entity_storage
             .add_component_to_entity(entity_key, component, overwrite);
+        if result.is_ok() || replace {
+            self.exec_on_add_hooks(entity_key, &[comp_id]);
+        }
     }
 
     pub fn remove_component_from_entity<T: Component>(&mut self, entity_key: EntityKey) {
-        //TODO: add hooks
+        let comp_id = self.create_or_get_component::<T>();
+        if self
+            .entity_storage
+            .entity_contains_component(entity_key, comp_id)
+        {
+            self.exec_on_remove_hooks(entity_key, &[comp_id]);
+        }
         let _ = self
             .entity_storage
             .remove_component_from_entity::<T>(entity_key);
     }
 
+    /// Adds the relation (R, target) to the entity.
+    /// Adding the same relation twice is ignored.
+    pub fn add_relation<R: Relation>(&mut self, entity_key: EntityKey, target: EntityKey) {
+        let _ = self.entity_storage.add_relation::<R>(entity_key, target);
+    }
+
+    /// Removes the relation (R, target) from the entity.
+    /// The entity is removed, if it does not contain any components or relations anymore.
+    pub fn remove_relation<R: Relation>(&mut self, entity_key: EntityKey, target: EntityKey) {
+        let _ = self.entity_storage.remove_relation::<R>(entity_key, target);
+    }
+
+    /// Returns the targets of all relations of kind R of the entity.
+    pub fn get_relation_targets<R: Relation>(&self, entity_key: EntityKey) -> Vec<EntityKey> {
+        self.entity_storage.get_relation_targets::<R>(entity_key)
+    }
+
+    // Returns the reflected fields of the component type.
+    fn get_reflect_info(&self, comp_id: ComponentId) -> Option<&TypeInfo> {
+        let cinfo = self.entity_storage.components.get(comp_id.id_usize())?;
+        self.type_registry.get(&cinfo.type_id)
+    }
+
+    pub fn register_debug_component<T: Component + Debug>(&mut self) {
+        let comp_id = self.create_or_get_component::<T>();
+        self.entity_storage.components[comp_id.id_usize()].debug =
+            Some(ComponentInfo::debug_ptr::<T>);
+    }
+
+    pub fn register_on_add<T: Component>(
+        &mut self,
+        hook: impl Fn(&mut WorldData, EntityKey) + Send + Sync + 'static,
+    ) {
+        let comp_id = self.create_or_get_component::<T>();
+        self.entity_storage.components[comp_id.id_usize()]
+            .hooks
+            .on_add
+            .push(Arc::new(hook));
+    }
+
+    pub fn register_on_remove<T: Component>(
+        &mut self,
+        hook: impl Fn(&mut WorldData, EntityKey) + Send + Sync + 'static,
+    ) {
+        let comp_id = self.create_or_get_component::<T>();
+        self.entity_storage.components[comp_id.id_usize()]
+            .hooks
+            .on_remove
+            .push(Arc::new(hook));
+    }
+
+    pub fn register_on_replace<T: Component>(
+        &mut self,
+        hook: impl Fn(&mut WorldData, EntityKey) + Send + Sync + 'static,
+    ) {
+        let comp_id = self.create_or_get_component::<T>();
+        self.entity_storage.components[comp_id.id_usize()]
+            .hooks
+            .on_replace
+            .push(Arc::new(hook));
+    }
+
+    fn exec_on_add_hooks_for<T: TupleTypesExt>(&mut self, entity_key: EntityKey) {
+        let mut comp_ids = self.entity_storage.cache.compid_vec_cache.take_cached();
+        T::create_or_get_component(&mut self.entity_storage, &mut comp_ids);
+        self.exec_on_add_hooks(entity_key, &comp_ids);
+        self.entity_storage.cache.compid_vec_cache.insert(comp_ids);
+    }
+
+    fn exec_on_add_hooks(&mut self, entity_key: EntityKey, comp_ids: &[ComponentId]) {
+        for comp_id in comp_ids.iter() {
+            // hooks are cloned, so that they can access the world mutably
+            let hooks = self.entity_storage.components[comp_id.id_usize()]
+                .hooks
+                .on_add
+                .clone();
+            hooks.iter().for_each(|hook| hook(self, entity_key));
+        }
+    }
+
+    fn exec_on_remove_hooks(&mut self, entity_key: EntityKey, comp_ids: &[ComponentId]) {
+        for comp_id in comp_ids.iter() {
+            let hooks = self.entity_storage.components[comp_id.id_usize()]
+                .hooks
+                .on_remove
+                .clone();
+            hooks.iter().for_each(|hook| hook(self, entity_key));
+        }
+    }
+
+    fn exec_on_replace_hooks(&mut self, entity_key: EntityKey, comp_ids: &[ComponentId]) {
+        for comp_id in comp_ids.iter() {
+            let hooks = self.entity_storage.components[comp_id.id_usize()]
+                .hooks
+                .on_replace
+                .clone();
+            hooks.iter().for_each(|hook| hook(self, entity_key));
+        }
+    }
+
+    /// Current value of the world change tick,
+    /// which is used to mark components added or changed outside of systems.
+    pub fn change_tick(&self) -> Tick {
+        self.entity_storage.change_tick()
+    }
+
+    pub(crate) fn increment_change_tick(&self) -> Tick {
+        self.entity_storage.increment_change_tick()
+    }
+
     pub(crate) fn create_or_get_component<T: Component>(&mut self) -> ComponentId {
         self.entity_storage.create_or_get_component::<T>()
     }
@@ -226,21 +798,124 @@ impl WorldData {
 
     pub fn add_resource<T: 'static>(&mut self, value: T) -> ResourceId {
         self.resources.insert(value);
-        let resource_id = ResourceId::new(TypeId::of::<T>());
-        self.entity_storage
-            .depend_graph
-            .insert_resource(resource_id);
+        let resource_id = ResourceId::of::<T>();
+        self.entity_storage.depend_graph.add_resource(resource_id);
         resource_id
     }
 
+    /// Adds the resource and returns the resource, which it replaced.
+    pub fn insert_or_replace_resource<T: 'static>(&mut self, value: T) -> Option<T> {
+        let old_value = self.resources.remove::<T>();
+        self.add_resource(value);
+        old_value
+    }
+
+    /// Creates the resource from the world data, if it does not exist yet.
+    pub fn init_resource<T: FromWorld + 'static>(&mut self) -> ResourceId {
+        if !self.contains_resource::<T>() {
+            let value = T::from_world(self);
+            self.add_resource(value);
+        }
+        ResourceId::of::<T>()
+    }
+
+    /// Removes the resource from the world.
+    /// Systems, which still require it, fail the next time they run.
+    pub fn remove_resource<T: 'static>(&mut self) -> Option<T> {
+        let value = self.resources.remove::<T>()?;
+        let resource_id = ResourceId::of::<T>();
+        if !self.non_send_resources.contains(&resource_id.type_id()) {
+            self.entity_storage
+                .depend_graph
+                .remove_resource(resource_id);
+        }
+        Some(value)
+    }
+
+    pub fn contains_resource<T: 'static>(&self) -> bool {
+        self.resources.contains(&TypeId::of::<T>())
+    }
+
     pub fn get_resource<T: 'static>(&self) -> Option<&T> {
         self.resources.get()
     }
 
+    /// Adds the event storage for events of type E as a resource, if it does not exist yet.
+    pub fn add_event<E: Event>(&mut self) -> ResourceId {
+        let resource_id = event::events_resource_id::<E>();
+        if !self.event_updates.contains_key(&resource_id) {
+            if self.resources.get::<Events<E>>().is_none() {
+                self.add_resource(Events::<E>::new());
+            }
+            self.event_updates
+                .insert(resource_id, event::update_events::<E>);
+        }
+        resource_id
+    }
+
+    pub fn send_event<E: Event>(&mut self, event: E) {
+        self.add_event::<E>();
+        self.resources
+            .get_mut::<Events<E>>()
+            .expect("Events should have been added to the world.")
+            .send(event);
+    }
+
+    // Swaps the event buffers of all event types,
+    // events of the run before the previous one get dropped.
+    pub(crate) fn update_events(&mut self) {
+        let event_updates: Vec<fn(&mut WorldData)> = self.event_updates.values().copied().collect();
+        event_updates.iter().for_each(|update| update(self));
+    }
+
     pub fn get_resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
         self.resources.get_mut()
     }
 
+    /// Adds a resource, which is not Send.
+    /// The thread, which adds the first non send resource, becomes the owner of all of them.
+    /// Accessing them or dropping the world on another thread panics.
+    pub fn add_non_send_resource<T: 'static>(&mut self, value: T) -> ResourceId {
+        self.validate_non_send_thread();
+        self.non_send_thread = Some(std::thread::current().id());
+        self.non_send_resources.insert(value);
+        let resource_id = ResourceId::of::<T>();
+        self.entity_storage.depend_graph.add_resource(resource_id);
+        resource_id
+    }
+
+    pub fn remove_non_send_resource<T: 'static>(&mut self) -> Option<T> {
+        self.validate_non_send_thread();
+        let value = self.non_send_resources.remove::<T>()?;
+        let resource_id = ResourceId::of::<T>();
+        if !self.resources.contains(&resource_id.type_id()) {
+            self.entity_storage
+                .depend_graph
+                .remove_resource(resource_id);
+        }
+        Some(value)
+    }
+
+    pub fn get_non_send_resource<T: 'static>(&self) -> Option<&T> {
+        self.validate_non_send_thread();
+        self.non_send_resources.get()
+    }
+
+    pub fn get_non_send_resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.validate_non_send_thread();
+        self.non_send_resources.get_mut()
+    }
+
+    fn validate_non_send_thread(&self) {
+        if let Some(owner_thread) = self.non_send_thread {
+            assert_eq!(
+                owner_thread,
+                std::thread::current().id(),
+                "Non send resources can only be accessed on the thread, which added them."
+            );
+        }
+    }
+
     pub fn get_entity_components<P: QueryParam>(
         &mut self,
         entity_key: EntityKey,
@@ -259,7 +934,6 @@ impl WorldData {
         self.entity_storage.get_single_component_mut(entity_key)
     }
 
-    #[allow(unused)]
     pub(crate) fn get_depend_graph(&self) -> &EcsDependencyGraph {
         &self.entity_storage.depend_graph
     }
@@ -277,11 +951,14 @@ impl WorldData {
         &mut self.entity_storage.tables
     }
 
+    pub(crate) fn get_entity_storage_mut(&mut self) -> &mut EntityStorage {
+        &mut self.entity_storage
+    }
+
     pub(crate) fn get_entities(&self) -> &Entities {
         &self.entity_storage.entities
     }
 
-    #[allow(unused)]
     pub(crate) fn get_entities_mut(&mut self) -> &mut Entities {
         &mut self.entity_storage.entities
     }
@@ -299,9 +976,20 @@ impl WorldData {
         &self.entity_storage.archetypes
     }
 
+    pub(crate) fn get_components(&self) -> &[ComponentInfo] {
+        &self.entity_storage.components
+    }
+
+    pub(crate) fn get_sparse_sets(&self) -> &SparseSets {
+        &self.entity_storage.sparse_sets
+    }
+
     pub(crate) fn execute_commands(&mut self) {
+        // entries of keys reserved by the commands need to exist, before their entities are spawned
+        self.entity_storage.entities.update_with_barriers();
         while let Some(mut cq) = self.commands_queues.get_command_queue_inuse_mut().pop() {
-            while let Some(command) = cq.get_mut().pop() {
+            // commands are executed in the order they were issued in
+            for command in cq.get_mut().drain(..) {
                 command.exec(self);
             }
             self.commands_queues.get_command_queue_unused_mut().push(cq);
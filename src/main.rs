@@ -79,7 +79,7 @@ fn test_system1(
     mut query: Query<(EntityKey, &Comp1, &mut Comp2), Without<Pos>>,
     mut query2: Query<(EntityKey, &Pos, &mut Pos4, &Pos2)>,
 ) {
-    for (ek, comp1, comp2) in query.iter() {
+    for (ek, comp1, mut comp2) in query.iter() {
         comp2.0 = comp1.1 / 3245345 * prm.abs() as usize;
         comp2.1 = *prm2 / 7137;
         commands.add_component(ek, Pos(4325), true);
@@ -87,7 +87,7 @@ fn test_system1(
         commands.add_component(ek, Comp1(4325, 33333), true);
     }
 
-    for (ek, _pos, pos4, _pos3) in query2.iter() {
+    for (ek, _pos, mut pos4, _pos3) in query2.iter() {
         pos4.0 = 23234;
         pos4.0 -= 2344;
         pos4.1.0 = 23234;
@@ -126,7 +126,7 @@ struct TotalDurAos(Duration);
 fn test_soa(mut total_dur: ResMut<TotalDurSoa>, mut query_soa: Query<(&mut Comp1, &mut Comp2)>) {
     let start = Instant::now();
     for (comp1, comp2) in query_soa.iter() {
-        do_some_work((comp1, comp2));
+        do_some_work((comp1.into_inner(), comp2.into_inner()));
     }
     let el = start.elapsed();
     let nanos = el.as_nanos();
@@ -144,7 +144,7 @@ fn test_aos(
 ) {
     let start = Instant::now();
     for (comp1, comp2) in query_aos.iter() {
-        do_some_work_aos((comp1, comp2));
+        do_some_work_aos((comp1.into_inner(), comp2.into_inner()));
     }
     let el = start.elapsed();
     let nanos = el.as_nanos();
@@ -1,6 +1,7 @@
 use std::{
     any::{Any, TypeId},
     collections::HashMap,
+    ptr::NonNull,
 };
 
 pub trait AnyMapTrait {
@@ -55,6 +56,17 @@ impl AnyMap {
         }
     }
 
+    /// Returns a type erased pointer to the value with the supplied type id.
+    pub fn get_ptr(&mut self, type_id: &TypeId) -> Option<NonNull<u8>> {
+        self.data
+            .get_mut(type_id)
+            .map(|boxed_val| NonNull::from(&mut **boxed_val).cast::<u8>())
+    }
+
+    pub fn contains(&self, type_id: &TypeId) -> bool {
+        self.data.contains_key(type_id)
+    }
+
     pub fn len(&self) -> usize {
         self.data.len()
     }
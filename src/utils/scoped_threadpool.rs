@@ -1,13 +1,15 @@
 // scoped_threadpool.rs
 
 use std::{
+    any::Any,
     marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
     sync::{
         Arc, Mutex,
-        atomic::{AtomicBool, AtomicUsize, Ordering},
+        atomic::{AtomicUsize, Ordering},
         mpmc::{self, Receiver, Sender},
     },
-    thread::{JoinHandle, Thread},
+    thread::{JoinHandle, Thread, ThreadId},
 };
 
 type ExecFunc = Box<dyn FnOnce() + Send + Sync + 'static>;
@@ -17,6 +19,9 @@ struct Task {
     exec_func: ExecFunc,
 }
 
+/// Thread pool, which runs tasks borrowing from the stack of the calling thread.
+/// Multiple threads can open scopes on the same pool at the same time,
+/// their tasks share the threads of the pool.
 pub struct ScopedThreadPool {
     sender: Option<Sender<Task>>,
     threads: Vec<JoinHandle<()>>,
@@ -25,12 +30,16 @@ pub struct ScopedThreadPool {
 struct ScopeData {
     pool_thread: Mutex<Thread>,
     num_running_threads: AtomicUsize,
-    poison: AtomicBool,
+    // payload of the first panicking task, resumed by the scope after all tasks finished
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
 }
 
 pub struct Scope<'scope, 'env: 'scope> {
-    pool: &'scope mut ScopedThreadPool,
+    pool: &'scope ScopedThreadPool,
     data: Arc<ScopeData>,
+    // scopes opened by a task of the same pool run their tasks on the calling thread,
+    // waiting for the pool from one of its own threads could wait forever
+    inline: bool,
     _scope_marker: PhantomData<&'scope mut &'scope ()>,
     _env_marker: PhantomData<&'env mut &'env ()>,
 }
@@ -40,22 +49,27 @@ impl ScopeData {
         Self {
             pool_thread: Mutex::new(pool_thread),
             num_running_threads: AtomicUsize::new(0),
-            poison: AtomicBool::new(false),
+            panic: Mutex::new(None),
         }
     }
 }
 
 impl<'scope, 'env> Scope<'scope, 'env> {
-    fn new(pool: &'scope mut ScopedThreadPool, pool_thread: Thread) -> Self {
+    fn new(pool: &'scope ScopedThreadPool, pool_thread: Thread, inline: bool) -> Self {
         Self {
             pool,
             data: ScopeData::new(pool_thread).into(),
+            inline,
             _scope_marker: PhantomData::default(),
             _env_marker: PhantomData::default(),
         }
     }
 
     pub fn spawn<F: FnOnce() + Send + Sync + 'scope>(&mut self, exec_func: F) {
+        if self.inline {
+            exec_func();
+            return;
+        }
         self.data
             .num_running_threads
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
@@ -74,42 +88,6 @@ impl<'scope, 'env> Scope<'scope, 'env> {
     }
 }
 
-struct UnwindGuard<'a> {
-    num_running_threads: &'a AtomicUsize,
-    poison: &'a AtomicBool,
-    pool_thread: &'a Mutex<Thread>,
-}
-
-impl<'a> UnwindGuard<'a> {
-    fn new(
-        num_running_threads: &'a AtomicUsize,
-        poison: &'a AtomicBool,
-        pool_thread: &'a Mutex<Thread>,
-    ) -> Self {
-        Self {
-            num_running_threads,
-            poison,
-            pool_thread,
-        }
-    }
-}
-
-impl<'a> Drop for UnwindGuard<'a> {
-    fn drop(&mut self) {
-        if self.num_running_threads.load(Ordering::Acquire) > 0 {
-            self.num_running_threads.fetch_sub(1, Ordering::Release);
-        } else {
-            // should not be zero, if there is still a thread active
-            // poison the pool, so that all threads finish execution
-            self.poison.store(true, Ordering::Release);
-        }
-        // thread unwinding has occured, because of a panic,
-        // the threadpool needs to be stopped from executing
-        self.poison.store(true, Ordering::Release);
-        self.pool_thread.lock().unwrap().unpark();
-    }
-}
-
 impl ScopedThreadPool {
     pub fn new(thread_count: usize) -> Self {
         let mut threads = Vec::with_capacity(thread_count);
@@ -128,71 +106,56 @@ impl ScopedThreadPool {
     }
 
     fn worker_loop(receiver: Receiver<Task>) {
-        loop {
-            let task = receiver.recv();
-            match task {
-                Ok(Task { data, exec_func }) => {
-                    // handles panics without having the thread and thus the entire scope, be stuck
-                    let guard = UnwindGuard::new(
-                        &data.num_running_threads,
-                        &data.poison,
-                        &data.pool_thread,
-                    );
-                    exec_func();
-                    if data.num_running_threads.load(Ordering::Acquire) > 0 {
-                        data.num_running_threads.fetch_sub(1, Ordering::Release);
-                    } else {
-                        panic!("Should not be zero, if there is still a thread active.")
-                    }
-                    // notify pool executor thread that a task has finished
-                    data.pool_thread.lock().unwrap().unpark();
-                    std::mem::forget(guard);
-                }
-                Err(_) => break,
+        while let Ok(Task { data, exec_func }) = receiver.recv() {
+            // a panicking task does not take down the thread,
+            // it stays available for the tasks of other scopes
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(exec_func)) {
+                data.panic.lock().unwrap().get_or_insert(payload);
             }
+            data.num_running_threads.fetch_sub(1, Ordering::AcqRel);
+            // notify pool executor thread that a task has finished
+            data.pool_thread.lock().unwrap().unpark();
         }
     }
 
-    pub fn scope<'env, F, T>(&mut self, f: F) -> T
+    /// Runs f, which can spawn tasks borrowing from the environment,
+    /// and waits for all spawned tasks to be finished.
+    /// The first panic of f or of the tasks is resumed on the caller afterwards.
+    /// Scopes opened inside of a task of this pool run their tasks on the calling thread.
+    pub fn scope<'env, F, T>(&self, f: F) -> T
     where
         F: for<'scope> FnOnce(&'scope mut Scope<'scope, 'env>) -> T,
     {
         let pool_thread = std::thread::current();
-        let mut scope = Scope::new(self, pool_thread);
+        let inline = self.is_pool_thread(pool_thread.id());
+        let mut scope = Scope::new(self, pool_thread, inline);
         let scope_data = scope.data.clone();
-        let result = f(&mut scope);
-        //SAFETY: wait for all tasks submitted in scope to be finished
-        loop {
-            let poisoned = scope_data.poison.load(Ordering::Acquire);
-            if poisoned {
-                self.shutdown();
-                break;
-            }
-            let cur_num_running_tasks = scope_data
-                .num_running_threads
-                .load(std::sync::atomic::Ordering::Acquire);
-            if cur_num_running_tasks == 0 {
-                break;
-            }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&mut scope)));
+        //SAFETY: wait for all tasks submitted in scope to be finished,
+        // even if f panicked after spawning some of them
+        while scope_data.num_running_threads.load(Ordering::Acquire) > 0 {
             // pool thread gets unparked by worker thread once it finishes executing its task
-            // or encountered a panic doing so
             std::thread::park();
         }
+        let result = result.unwrap_or_else(|payload| panic::resume_unwind(payload));
+        if let Some(payload) = scope_data.panic.lock().unwrap().take() {
+            panic::resume_unwind(payload);
+        }
         result
     }
 
-    fn execute(&mut self, task: Task) {
-        match self.sender.as_mut().unwrap().send(task) {
-            Ok(_) => {}
-            Err(_) => {}
-        }
+    pub(crate) fn is_pool_thread(&self, thread_id: ThreadId) -> bool {
+        self.threads
+            .iter()
+            .any(|thread| thread.thread().id() == thread_id)
     }
 
-    fn shutdown(&mut self) {
-        drop(self.sender.take());
-        while let Some(thread) = self.threads.pop() {
-            let _res = thread.join();
-        }
+    fn execute(&self, task: Task) {
+        let _ = self
+            .sender
+            .as_ref()
+            .expect("Thread pool should not be shut down.")
+            .send(task);
     }
 }
 
@@ -217,7 +180,7 @@ mod test {
 
     #[test]
     fn test_scoped_threadpool() {
-        let mut pool = ScopedThreadPool::new(4);
+        let pool = ScopedThreadPool::new(4);
         let mut pers1 = Person("bob".into(), "bab".into(), 10);
         let mut pers3 = Person("baba".into(), "bab".into(), 15);
         let pers4 = Person("buba".into(), "bab".into(), 35);
@@ -258,4 +221,30 @@ mod test {
         }
         //assert!(false);
     }
+
+    #[test]
+    fn test_nested_scope() {
+        let pool = ScopedThreadPool::new(1);
+        let mut count = 0;
+        pool.scope(|scope| {
+            let count = &mut count;
+            // the only thread of the pool runs the inner tasks itself instead of waiting for them
+            scope.spawn(|| {
+                pool.scope(|scope| {
+                    scope.spawn(|| *count += 1);
+                });
+            });
+        });
+        assert_eq!(1, count);
+    }
+
+    #[test]
+    #[should_panic(expected = "task panicked")]
+    fn test_task_panic() {
+        let pool = ScopedThreadPool::new(2);
+        pool.scope(|scope| {
+            scope.spawn(|| {});
+            scope.spawn(|| panic!("task panicked"));
+        });
+    }
 }
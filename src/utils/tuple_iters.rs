@@ -4,7 +4,7 @@ use std::ptr::NonNull;
 
 use crate::{
     all_tuples,
-    ecs::{component::Component, entity::EntityKeyIterUnsafe},
+    ecs::{change_detection::Tick, component::Component, entity::EntityKeyIterUnsafe},
 };
 
 pub trait TupleIterator {
@@ -12,6 +12,11 @@ pub trait TupleIterator {
     ///SAFETY: This function does not check if iterator is still in the valid range.
     /// Bounds check needs to be tracked from outside the function.
     unsafe fn next(&mut self, index: usize) -> Self::Item;
+    /// Returns false, if the row at the index should be skipped,
+    /// e.g. its entity does not own a sparse set component of this iterator.
+    fn row_matches(&self, _index: usize) -> bool {
+        true
+    }
 }
 
 pub trait TupleConstructorSource: 'static {
@@ -24,27 +29,34 @@ pub trait TupleConstructorSource: 'static {
 
     fn get_entity_key_iter<'c>(&'c mut self) -> EntityKeyIterUnsafe<'c>;
     unsafe fn get_iter<'c, T: Component>(&'c mut self) -> Self::IterType<'c, T>;
-    unsafe fn get_iter_mut<'c, T: Component>(&'c mut self) -> Self::IterMutType<'c, T>;
+    /// The tick of the current system run is used to mark mutably accessed components as changed.
+    unsafe fn get_iter_mut<'c, T: Component>(
+        &'c mut self,
+        this_run: Tick,
+    ) -> Self::IterMutType<'c, T>;
     unsafe fn get_iter_opt<'c, T: Component>(&'c mut self) -> Option<Self::IterType<'c, T>>;
-    unsafe fn get_iter_mut_opt<'c, T: Component>(&'c mut self) -> Option<Self::IterMutType<'c, T>>;
+    unsafe fn get_iter_mut_opt<'c, T: Component>(
+        &'c mut self,
+        this_run: Tick,
+    ) -> Option<Self::IterMutType<'c, T>>;
 }
 
 pub trait TupleIterConstructor<S: TupleConstructorSource> {
     type Construct<'c>: TupleIterator;
-    unsafe fn construct<'s>(source: NonNull<S>) -> Self::Construct<'s>;
+    unsafe fn construct<'s>(source: NonNull<S>, this_run: Tick) -> Self::Construct<'s>;
 }
 
 impl<T: Component, S: TupleConstructorSource> TupleIterConstructor<S> for &T {
     type Construct<'c> = S::IterType<'c, T>;
-    unsafe fn construct<'s>(mut source: NonNull<S>) -> Self::Construct<'s> {
+    unsafe fn construct<'s>(mut source: NonNull<S>, _this_run: Tick) -> Self::Construct<'s> {
         unsafe { source.as_mut().get_iter() }
     }
 }
 
 impl<T: Component, S: TupleConstructorSource> TupleIterConstructor<S> for &mut T {
     type Construct<'c> = S::IterMutType<'c, T>;
-    unsafe fn construct<'s>(mut source: NonNull<S>) -> Self::Construct<'s> {
-        unsafe { source.as_mut().get_iter_mut() }
+    unsafe fn construct<'s>(mut source: NonNull<S>, this_run: Tick) -> Self::Construct<'s> {
+        unsafe { source.as_mut().get_iter_mut(this_run) }
     }
 }
 
@@ -53,8 +65,8 @@ macro_rules! impl_tuple_iter_constructor{
        impl<S: TupleConstructorSource, $($t : TupleIterConstructor<S>), *> TupleIterConstructor<S> for ($($t),*,){
             #[allow(unused_parens, non_snake_case)]
             type Construct<'c> = ($($t::Construct<'c>), *);
-            unsafe fn construct<'s>(source: NonNull<S>) -> Self::Construct<'s> {
-                unsafe{ ($($t::construct(source)), *) }
+            unsafe fn construct<'s>(source: NonNull<S>, this_run: Tick) -> Self::Construct<'s> {
+                unsafe{ ($($t::construct(source, this_run)), *) }
             }
         }
     }
@@ -78,6 +90,11 @@ macro_rules! impl_tuple_iterator{
                 let ($($t),*) = self;
                 unsafe{ ($($t.next(index)),*) }
             }
+            fn row_matches(&self, index: usize) -> bool {
+                #[allow(non_snake_case)]
+                let ($($t,)*) = self;
+                true $(&& $t.row_matches(index))*
+            }
         }
     }
 }
@@ -6,9 +6,7 @@ use crate::{
     all_tuples,
     ecs::{
         component::{Component, ComponentId, StorageTypes},
-        entity::EntityKey,
         storages::entity_storage::EntityStorage,
-        world::WorldData,
     },
 };
 
@@ -64,27 +62,22 @@ pub trait TupleTypesExt: 'static {
         entity_storage: &mut EntityStorage,
         soa_vec: &mut Vec<ComponentId>,
         aos_vec: &mut Vec<ComponentId>,
+        sparse_vec: &mut Vec<ComponentId>,
     );
     fn self_get_comp_ids_by_storage(
         entity_storage: &mut EntityStorage,
         soa_vec: &mut Vec<ComponentId>,
         aos_vec: &mut Vec<ComponentId>,
+        sparse_vec: &mut Vec<ComponentId>,
     ) {
-        Self::get_comp_ids_by_storage(entity_storage, soa_vec, aos_vec);
+        Self::get_comp_ids_by_storage(entity_storage, soa_vec, aos_vec, sparse_vec);
     }
     fn self_get_value_ptrs_by_storage(
         &mut self,
         soa_vec: &mut Vec<NonNull<u8>>,
         aos_vec: &mut Vec<NonNull<u8>>,
+        sparse_vec: &mut Vec<NonNull<u8>>,
     );
-    fn on_add() -> Option<for<'a> fn(world: &mut WorldData, entity: EntityKey)> {
-        None
-    }
-    fn exec_on_add_rec(world_data: &mut WorldData, entity: EntityKey);
-    fn on_remove() -> Option<for<'a> fn(world_data: &mut WorldData, entity: EntityKey)> {
-        None
-    }
-    fn exec_on_remove_rec(world_data: &mut WorldData, entity: EntityKey);
 }
 
 impl<T: Component> TupleTypesExt for T {
@@ -102,38 +95,26 @@ impl<T: Component> TupleTypesExt for T {
         entity_storage: &mut EntityStorage,
         soa_vec: &mut Vec<ComponentId>,
         aos_vec: &mut Vec<ComponentId>,
+        sparse_vec: &mut Vec<ComponentId>,
     ) {
         match T::STORAGE {
             StorageTypes::TableSoA => soa_vec.push(entity_storage.create_or_get_component::<T>()),
             StorageTypes::TableAoS => aos_vec.push(entity_storage.create_or_get_component::<T>()),
-            StorageTypes::SparseSet => unimplemented!(),
+            StorageTypes::SparseSet => {
+                sparse_vec.push(entity_storage.create_or_get_component::<T>())
+            }
         }
     }
     fn self_get_value_ptrs_by_storage(
         &mut self,
         soa_vec: &mut Vec<NonNull<u8>>,
         aos_vec: &mut Vec<NonNull<u8>>,
+        sparse_vec: &mut Vec<NonNull<u8>>,
     ) {
         match T::STORAGE {
             StorageTypes::TableSoA => self.self_get_elem_ptrs_rec(soa_vec),
             StorageTypes::TableAoS => self.self_get_elem_ptrs_rec(aos_vec),
-            StorageTypes::SparseSet => unimplemented!(),
-        }
-    }
-    fn on_add() -> Option<for<'a> fn(world_data: &mut WorldData, entity: EntityKey)> {
-        T::on_add()
-    }
-    fn exec_on_add_rec(world_data: &mut WorldData, entity: EntityKey) {
-        if let Some(on_add) = Self::on_add() {
-            on_add(world_data, entity);
-        }
-    }
-    fn on_remove() -> Option<for<'a> fn(world_data: &mut WorldData, entity: EntityKey)> {
-        T::on_remove()
-    }
-    fn exec_on_remove_rec(world_data: &mut WorldData, entity: EntityKey) {
-        if let Some(on_remove) = Self::on_add() {
-            on_remove(world_data, entity);
+            StorageTypes::SparseSet => self.self_get_elem_ptrs_rec(sparse_vec),
         }
     }
 }
@@ -147,16 +128,16 @@ impl TupleTypesExt for () {
         _entity_storage: &mut EntityStorage,
         _soa_vec: &mut Vec<ComponentId>,
         _aos_vec: &mut Vec<ComponentId>,
+        _sparse_vec: &mut Vec<ComponentId>,
     ) {
     }
     fn self_get_value_ptrs_by_storage(
         &mut self,
         _soa_vec: &mut Vec<NonNull<u8>>,
         _aos_vec: &mut Vec<NonNull<u8>>,
+        _sparse_vec: &mut Vec<NonNull<u8>>,
     ) {
     }
-    fn exec_on_add_rec(_world_data: &mut WorldData, _entity: EntityKey) {}
-    fn exec_on_remove_rec(_world_data: &mut WorldData, _entity: EntityKey) {}
 }
 
 macro_rules! impl_tuple_ext {
@@ -187,24 +168,20 @@ macro_rules! impl_tuple_ext {
             fn get_comp_ids_by_storage(
                entity_storage: &mut EntityStorage,
                soa_vec: &mut Vec<ComponentId>,
-               aos_vec: &mut Vec<ComponentId>
+               aos_vec: &mut Vec<ComponentId>,
+               sparse_vec: &mut Vec<ComponentId>
             ) {
-               $($t::get_comp_ids_by_storage(entity_storage, soa_vec, aos_vec);)*
+               $($t::get_comp_ids_by_storage(entity_storage, soa_vec, aos_vec, sparse_vec);)*
             }
             fn self_get_value_ptrs_by_storage(
                     &mut self,
                     soa_vec: &mut Vec<NonNull<u8>>,
                     aos_vec: &mut Vec<NonNull<u8>>,
+                    sparse_vec: &mut Vec<NonNull<u8>>,
             ) {
                 #[allow(non_snake_case)]
                 let ( $($t,)+ ) = self;
-                $($t::self_get_value_ptrs_by_storage($t, soa_vec, aos_vec);)*
-            }
-            fn exec_on_add_rec(world_data: &mut WorldData, entity: EntityKey){
-                $($t::exec_on_add_rec(world_data, entity);)*
-            }
-            fn exec_on_remove_rec(world_data: &mut WorldData, entity: EntityKey){
-                $($t::exec_on_remove_rec(world_data, entity);)*
+                $($t::self_get_value_ptrs_by_storage($t, soa_vec, aos_vec, sparse_vec);)*
             }
        }
     };